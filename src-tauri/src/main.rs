@@ -3,15 +3,52 @@
     windows_subsystem = "windows"
 )]
 
+mod storage;
+
+use tauri::{
+    AppHandle, CustomMenuItem, Manager, SystemTray, SystemTrayEvent, SystemTrayMenu, WindowEvent,
+};
+
 // Learn more about Tauri commands at https://tauri.app/v1/guides/features/command
 // #[tauri::command]
 // fn greet(name: &str) -> String {
 //     format!("Hello, {}! You've been greeted from Rust!", name)
 // }
 
+fn show_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_window("main") {
+        window.show().ok();
+        window.set_focus().ok();
+    }
+}
+
 fn main() {
+    let tray_menu = SystemTrayMenu::new()
+        .add_item(CustomMenuItem::new("show", "Show"))
+        .add_item(CustomMenuItem::new("quit", "Quit"));
     tauri::Builder::default()
-        // .invoke_handler(tauri::generate_handler![greet])
+        .system_tray(SystemTray::new().with_menu(tray_menu))
+        .on_system_tray_event(|app, event| match event {
+            SystemTrayEvent::LeftClick { .. } => show_main_window(app),
+            SystemTrayEvent::MenuItemClick { id, .. } => match id.as_str() {
+                "show" => show_main_window(app),
+                "quit" => app.exit(0),
+                _ => {}
+            },
+            _ => {}
+        })
+        // closing the window hides it in the tray, the scheduled notes are still published
+        .on_window_event(|event| {
+            if let WindowEvent::CloseRequested { api, .. } = event.event() {
+                event.window().hide().ok();
+                api.prevent_close();
+            }
+        })
+        .invoke_handler(tauri::generate_handler![
+            storage::storage_load_all,
+            storage::storage_set,
+            storage::storage_remove
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
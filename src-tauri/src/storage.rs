@@ -0,0 +1,75 @@
+// The storage of the web app, a file per key in `<app data dir>/storage`
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+const STORAGE_DIR: &str = "storage";
+const FILE_EXT: &str = "json";
+// what the web app reports as a full storage
+const QUOTA_EXCEEDED: &str = "QuotaExceededError";
+// ENOSPC
+const NO_SPACE: i32 = 28;
+
+fn storage_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path_resolver()
+        .app_data_dir()
+        .ok_or("no app data dir")?
+        .join(STORAGE_DIR);
+    fs::create_dir_all(&dir).map_err(io_error)?;
+    Ok(dir)
+}
+
+// the keys are used as file names, so only simple ones are allowed
+fn key_path(app: &tauri::AppHandle, key: &str) -> Result<PathBuf, String> {
+    let valid = !key.is_empty()
+        && !key.starts_with('.')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+    if !valid {
+        return Err(format!("invalid storage key: {key}"));
+    }
+    Ok(storage_dir(app)?.join(format!("{key}.{FILE_EXT}")))
+}
+
+fn io_error(e: io::Error) -> String {
+    if e.raw_os_error() == Some(NO_SPACE) {
+        QUOTA_EXCEEDED.to_owned()
+    } else {
+        e.to_string()
+    }
+}
+
+#[tauri::command]
+pub fn storage_load_all(app: tauri::AppHandle) -> Result<HashMap<String, String>, String> {
+    let mut data = HashMap::new();
+    for entry in fs::read_dir(storage_dir(&app)?).map_err(io_error)? {
+        let path = entry.map_err(io_error)?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some(FILE_EXT) {
+            continue;
+        }
+        if let Some(key) = path.file_stem().and_then(|stem| stem.to_str()) {
+            data.insert(key.to_owned(), fs::read_to_string(&path).map_err(io_error)?);
+        }
+    }
+    Ok(data)
+}
+
+#[tauri::command]
+pub fn storage_set(app: tauri::AppHandle, key: String, value: String) -> Result<(), String> {
+    let path = key_path(&app, &key)?;
+    // write to a temporary file first, so a crash never leaves a half written file
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, value).map_err(io_error)?;
+    fs::rename(&tmp, &path).map_err(io_error)
+}
+
+#[tauri::command]
+pub fn storage_remove(app: tauri::AppHandle, key: String) -> Result<(), String> {
+    match fs::remove_file(key_path(&app, &key)?) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(io_error(e)),
+        _ => Ok(()),
+    }
+}
@@ -1,13 +1,7 @@
+mod pow;
+mod relay_pool;
 mod websocket_worker;
 
-pub use websocket_worker::{WebsocketWorker, WebsocketWorkerInputMsg, WorkerOutput};
-
-use crate::relay_ctx::RelayContext;
-
-#[derive(Debug)]
-pub enum WorkerMsg {
-    Event(WorkerOutput),
-    RelayInfo,
-    Result,
-    Auth,
-}
+pub use pow::*;
+pub use relay_pool::RelayPool;
+pub use websocket_worker::*;
@@ -0,0 +1,159 @@
+// NIP-13 工作量证明: 在单独的web worker里计算nonce, 页面签名
+use super::websocket_worker::{from_message, to_message};
+
+use nostr_sdk::nostr::nips::nip13::get_leading_zero_bits;
+use nostr_sdk::nostr::{EventBuilder, EventId, Keys, Kind, Tag, Timestamp, UnsignedEvent};
+use nostr_sdk::prelude::XOnlyPublicKey;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{MessageEvent, Worker};
+use yew::Callback;
+
+// built by trunk from `bin/pow_worker.rs`
+const POW_WORKER_PATH: &str = "./pow_worker.js";
+// hashes between two progress messages
+const PROGRESS_STEP: u64 = 20_000;
+
+// the page -> the worker
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowRequest {
+    pub pubkey: XOnlyPublicKey,
+    pub kind: Kind,
+    pub tags: Vec<Tag>,
+    pub content: String,
+    pub difficulty: u8,
+    // a fixed time, e.g. of a scheduled note, otherwise the time of the mining
+    #[serde(default)]
+    pub created_at: Option<Timestamp>,
+}
+
+impl PowRequest {
+    pub fn new(keys: &Keys, builder: EventBuilder, difficulty: u8) -> Self {
+        let unsigned = builder.to_unsigned_event(keys.public_key());
+        Self {
+            pubkey: unsigned.pubkey,
+            kind: unsigned.kind,
+            tags: unsigned.tags,
+            content: unsigned.content,
+            difficulty,
+            created_at: None,
+        }
+    }
+
+    pub fn at(mut self, created_at: Timestamp) -> Self {
+        self.created_at = Some(created_at);
+        self
+    }
+}
+
+// the worker -> the page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PowOutput {
+    Ready,
+    // the hashes tried, and the best difficulty so far
+    Progress(u64, u8),
+    // to be signed in the page
+    Done(UnsignedEvent),
+}
+
+// runs in the worker until done, it's canceled by terminating the worker
+pub fn mine(req: PowRequest, progress: impl Fn(u64, u8)) -> UnsignedEvent {
+    let mut tags = req.tags;
+    let mut created_at = req.created_at.unwrap_or_else(Timestamp::now);
+    let mut best = 0;
+    let mut nonce: u128 = 0;
+    loop {
+        nonce += 1;
+        tags.push(Tag::POW {
+            nonce,
+            difficulty: req.difficulty,
+        });
+        let id = EventId::new(&req.pubkey, created_at, &req.kind, &tags, &req.content);
+        let bits = get_leading_zero_bits(id.inner());
+        if bits >= req.difficulty {
+            return UnsignedEvent {
+                id,
+                pubkey: req.pubkey,
+                created_at,
+                kind: req.kind,
+                tags,
+                content: req.content,
+            };
+        }
+        best = best.max(bits);
+        tags.pop();
+        if nonce as u64 % PROGRESS_STEP == 0 {
+            progress(nonce as u64, best);
+            // a long mining shouldn't get an old timestamp
+            if req.created_at.is_none() {
+                created_at = Timestamp::now();
+            }
+        }
+    }
+}
+
+// a mining in the page, dropping it cancels the mining
+pub struct Miner {
+    worker: Worker,
+    _onmessage: Closure<dyn Fn(MessageEvent)>,
+}
+
+impl Miner {
+    pub fn start(req: PowRequest, on_output: Callback<PowOutput>) -> Result<Self, JsValue> {
+        let worker = Worker::new(POW_WORKER_PATH)?;
+        let onmessage = {
+            let worker = worker.clone();
+            Closure::<dyn Fn(MessageEvent)>::new(move |e: MessageEvent| {
+                match from_message::<PowOutput>(e.data()) {
+                    // the messages before it are lost
+                    Some(PowOutput::Ready) => {
+                        worker.post_message(&to_message(&req)).ok();
+                    }
+                    Some(output) => on_output.emit(output),
+                    None => {}
+                }
+            })
+        };
+        worker.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        Ok(Self {
+            worker,
+            _onmessage: onmessage,
+        })
+    }
+}
+
+impl Drop for Miner {
+    fn drop(&mut self) {
+        self.worker.terminate();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mine_at_fixed_time() {
+        let keys = Keys::generate();
+        let created_at = Timestamp::from(1_700_000_000);
+        let req = PowRequest::new(&keys, EventBuilder::new_text_note("hi", &[]), 8).at(created_at);
+        let unsigned = mine(req, |_, _| {});
+        assert_eq!(unsigned.created_at, created_at);
+        assert!(get_leading_zero_bits(unsigned.id.inner()) >= 8);
+        assert_eq!(
+            unsigned.id,
+            EventId::new(
+                &unsigned.pubkey,
+                created_at,
+                &unsigned.kind,
+                &unsigned.tags,
+                &unsigned.content
+            )
+        );
+        assert!(matches!(
+            unsigned.tags.last(),
+            Some(Tag::POW { difficulty: 8, .. })
+        ));
+    }
+}
@@ -0,0 +1,723 @@
+// 页面一侧的relay连接池, 所有的websocket工作都在worker里.
+// 多个标签页共用一个SharedWorker; 不支持时用BroadcastChannel选出一个标签页, 由它的worker连接relay
+use super::websocket_worker::*;
+
+use gloo_timers::callback::{Interval, Timeout};
+use indexmap::IndexMap;
+use js_sys::{Function, Promise, Reflect};
+use nostr_sdk::nostr::{ClientMessage, Event, EventId, Filter, Keys, Url};
+use nostr_sdk::prelude::XOnlyPublicKey;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, Ref, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    BroadcastChannel, MessageEvent, MessagePort, PageTransitionEvent, SharedWorker, Worker,
+};
+use yew::Callback;
+
+// built by trunk from `bin/worker.rs`
+const WORKER_PATH: &str = "./worker.js";
+const CHANNEL_NAME: &str = "dapp-relay-pool";
+const HEARTBEAT_MS: u32 = 1_000;
+// no heartbeat from the leader for so long, elect a new one
+const LEADER_TIMEOUT_MS: f64 = 3_000.0;
+// wait for the claims of the other tabs before leading
+const CLAIM_WAIT_MS: f64 = 1_000.0;
+// the shared worker may miss the connection while its wasm is loading
+const SHARED_RETRY_MS: u32 = 2_000;
+// the events kept to be sent again, e.g. refused before the auth
+const MAX_SENT_EVENTS: usize = 100;
+// no response for so long, the request may be lost with the worker
+const REQUEST_TIMEOUT_MS: u32 = 30_000;
+
+type OutputHandler = Rc<dyn Fn(Envelope<WorkerOutput>)>;
+
+// a dedicated worker, the messages are queued until it's ready
+struct WorkerHandle {
+    worker: Worker,
+    queue: Rc<RefCell<Option<Vec<JsValue>>>>,
+    _onmessage: Closure<dyn Fn(MessageEvent)>,
+}
+
+impl WorkerHandle {
+    fn spawn(handler: OutputHandler) -> Result<Self, JsValue> {
+        let worker = Worker::new(WORKER_PATH)?;
+        let queue = Rc::new(RefCell::new(Some(vec![])));
+        let onmessage = {
+            let worker = worker.clone();
+            let queue = queue.clone();
+            Closure::<dyn Fn(MessageEvent)>::new(move |e: MessageEvent| {
+                match from_message::<Envelope<WorkerOutput>>(e.data()) {
+                    Some(Envelope {
+                        msg: WorkerOutput::Ready,
+                        ..
+                    }) => {
+                        for data in queue.borrow_mut().take().unwrap_or_default() {
+                            worker.post_message(&data).ok();
+                        }
+                    }
+                    Some(env) => handler(env),
+                    None => {}
+                }
+            })
+        };
+        worker.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        Ok(Self {
+            worker,
+            queue,
+            _onmessage: onmessage,
+        })
+    }
+
+    fn post(&self, data: JsValue) {
+        if let Some(queue) = self.queue.borrow_mut().as_mut() {
+            queue.push(data);
+            return;
+        }
+        if let Err(e) = self.worker.post_message(&data) {
+            log::error!("post to worker error:{e:?}");
+        }
+    }
+}
+
+impl Drop for WorkerHandle {
+    fn drop(&mut self) {
+        // closes the websockets too
+        self.worker.terminate();
+    }
+}
+
+// the port of the shared worker, connected again until the worker answers
+struct SharedHandle {
+    port: Rc<RefCell<Option<MessagePort>>>,
+    queue: Rc<RefCell<Option<Vec<JsValue>>>>,
+    _onmessage: Rc<Closure<dyn Fn(MessageEvent)>>,
+    _retry: Interval,
+}
+
+impl SharedHandle {
+    fn connect(onmessage: &Closure<dyn Fn(MessageEvent)>) -> Result<MessagePort, JsValue> {
+        let port = SharedWorker::new(WORKER_PATH)?.port();
+        port.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        port.start();
+        Ok(port)
+    }
+
+    fn spawn(handler: OutputHandler) -> Result<Self, JsValue> {
+        let queue = Rc::new(RefCell::new(Some(vec![])));
+        let port = Rc::new(RefCell::new(None::<MessagePort>));
+        let onmessage = {
+            let queue = queue.clone();
+            let port = port.clone();
+            Rc::new(Closure::<dyn Fn(MessageEvent)>::new(
+                move |e: MessageEvent| match from_message::<Envelope<WorkerOutput>>(e.data()) {
+                    Some(Envelope {
+                        msg: WorkerOutput::Ready,
+                        ..
+                    }) => {
+                        if let Some(port) = port.borrow().as_ref() {
+                            for data in queue.borrow_mut().take().unwrap_or_default() {
+                                port.post_message(&data).ok();
+                            }
+                        }
+                    }
+                    Some(env) => handler(env),
+                    None => {}
+                },
+            ))
+        };
+        *port.borrow_mut() = Some(Self::connect(&onmessage)?);
+        let retry = {
+            let queue = queue.clone();
+            let port = port.clone();
+            let onmessage = Rc::downgrade(&onmessage);
+            Interval::new(SHARED_RETRY_MS, move || {
+                let onmessage = match onmessage.upgrade() {
+                    Some(onmessage) => onmessage,
+                    None => return,
+                };
+                if queue.borrow().is_none() {
+                    return;
+                }
+                log::warn!("no answer from the shared worker, connect again");
+                // the worker would keep the unanswered port, and answer on it later
+                if let Some(old) = port.borrow_mut().take() {
+                    old.set_onmessage(None);
+                    old.close();
+                }
+                match Self::connect(&onmessage) {
+                    Ok(new_port) => *port.borrow_mut() = Some(new_port),
+                    Err(e) => log::error!("connect shared worker error:{e:?}"),
+                }
+            })
+        };
+        Ok(Self {
+            port,
+            queue,
+            _onmessage: onmessage,
+            _retry: retry,
+        })
+    }
+
+    fn post(&self, data: JsValue) {
+        if let Some(queue) = self.queue.borrow_mut().as_mut() {
+            queue.push(data);
+            return;
+        }
+        if let Some(port) = self.port.borrow().as_ref() {
+            if let Err(e) = port.post_message(&data) {
+                log::error!("post to shared worker error:{e:?}");
+            }
+        }
+    }
+}
+
+impl Drop for SharedHandle {
+    fn drop(&mut self) {
+        if let Some(port) = self.port.borrow_mut().take() {
+            port.set_onmessage(None);
+            port.close();
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum ChannelMsg {
+    Heartbeat(PortId),
+    Claim(PortId),
+    Input(Envelope<WebsocketWorkerInputMsg>),
+    Output(Envelope<WorkerOutput>),
+}
+
+// BroadcastChannel leader election, the leader runs the worker for all the tabs
+struct Election {
+    channel: BroadcastChannel,
+    leader: Cell<Option<PortId>>,
+    last_heartbeat: Cell<f64>,
+    claim_at: Cell<Option<f64>>,
+    worker: RefCell<Option<WorkerHandle>>, // only in the leader
+    onmessage: RefCell<Option<Closure<dyn Fn(MessageEvent)>>>,
+    tick: RefCell<Option<Interval>>,
+}
+
+impl Election {
+    fn post(&self, msg: &ChannelMsg) {
+        if let Err(e) = self.channel.post_message(&to_message(msg)) {
+            log::error!("post to channel error:{e:?}");
+        }
+    }
+}
+
+enum Transport {
+    Shared(SharedHandle),
+    Channel(Election),
+    // no BroadcastChannel, a worker of this tab only
+    Dedicated(WorkerHandle),
+}
+
+struct Inner {
+    port: PortId,
+    transport: RefCell<Option<Transport>>,
+    pending: RefCell<HashMap<u64, Function>>, // the resolve functions of the requests
+    next_id: Cell<u64>,
+    on_output: Callback<WorkerOutput>,
+    // sent again to a new leader
+    start: Cell<Option<XOnlyPublicKey>>,
+    relays: RefCell<Vec<Url>>,
+    filters: RefCell<Option<Vec<Filter>>>,
+    // the messages before a leader is elected
+    queue: RefCell<Option<Vec<WebsocketWorkerInputMsg>>>,
+}
+
+impl Inner {
+    fn deliver(&self, env: Envelope<WorkerOutput>) {
+        if env.port != self.port {
+            return;
+        }
+        match env.msg {
+            WorkerOutput::Ready => {}
+            WorkerOutput::Evicted => {
+                log::warn!("evicted by the relay pool worker");
+                self.restore();
+            }
+            WorkerOutput::Response { id, result } => {
+                if let Some(resolve) = self.pending.borrow_mut().remove(&id) {
+                    resolve.call1(&JsValue::NULL, &to_message(&result)).ok();
+                }
+            }
+            output => self.on_output.emit(output),
+        }
+    }
+
+    fn reject_pending(&self, reason: &str) {
+        let rejected = to_message(&Err::<Vec<Event>, _>(reason.to_owned()));
+        for (_, resolve) in self.pending.borrow_mut().drain() {
+            resolve.call1(&JsValue::NULL, &rejected).ok();
+        }
+    }
+
+    fn reject(&self, id: u64, reason: &str) {
+        if let Some(resolve) = self.pending.borrow_mut().remove(&id) {
+            let rejected = to_message(&Err::<Vec<Event>, _>(reason.to_owned()));
+            resolve.call1(&JsValue::NULL, &rejected).ok();
+        }
+    }
+
+    fn election(&self) -> Option<Ref<'_, Election>> {
+        Ref::filter_map(self.transport.borrow(), |t| match t {
+            Some(Transport::Channel(election)) => Some(election),
+            _ => None,
+        })
+        .ok()
+    }
+
+    fn post(&self, msg: WebsocketWorkerInputMsg) {
+        let env = Envelope {
+            port: self.port,
+            msg,
+        };
+        match self.transport.borrow().as_ref() {
+            Some(Transport::Shared(shared)) => shared.post(to_message(&env)),
+            Some(Transport::Dedicated(worker)) => worker.post(to_message(&env)),
+            Some(Transport::Channel(election)) => match election.leader.get() {
+                Some(leader) if leader == self.port => {
+                    if let Some(worker) = election.worker.borrow().as_ref() {
+                        worker.post(to_message(&env));
+                    }
+                }
+                Some(_) => election.post(&ChannelMsg::Input(env)),
+                None => {
+                    if let Some(queue) = self.queue.borrow_mut().as_mut() {
+                        queue.push(env.msg);
+                    }
+                }
+            },
+            None => {}
+        }
+    }
+
+    fn send(&self, msg: WebsocketWorkerInputMsg) {
+        match &msg {
+            WebsocketWorkerInputMsg::Start(pk) => self.start.set(Some(*pk)),
+            WebsocketWorkerInputMsg::AddRelay(uri) => self.relays.borrow_mut().push(uri.clone()),
+            WebsocketWorkerInputMsg::RemoveRelay(uri) => {
+                self.relays.borrow_mut().retain(|u| u != uri)
+            }
+            WebsocketWorkerInputMsg::Subscribe(filters) => {
+                *self.filters.borrow_mut() = Some(filters.clone())
+            }
+            _ => {}
+        }
+        self.post(msg);
+    }
+
+    // not queued, the queued messages tell the worker the tab is alive too
+    fn heartbeat(&self) {
+        let ready = match self.transport.borrow().as_ref() {
+            Some(Transport::Shared(shared)) => shared.queue.borrow().is_none(),
+            Some(Transport::Dedicated(worker)) => worker.queue.borrow().is_none(),
+            Some(Transport::Channel(_)) => self.queue.borrow().is_none(),
+            None => false,
+        };
+        if ready {
+            self.post(WebsocketWorkerInputMsg::Heartbeat);
+        }
+    }
+
+    // send the state of the tab again, to a worker without it
+    fn restore(&self) {
+        if let Some(pk) = self.start.get() {
+            self.post(WebsocketWorkerInputMsg::Start(pk));
+        }
+        let relays = self.relays.borrow().clone();
+        for uri in relays {
+            self.post(WebsocketWorkerInputMsg::AddRelay(uri));
+        }
+        let filters = self.filters.borrow().clone();
+        if let Some(filters) = filters {
+            self.post(WebsocketWorkerInputMsg::Subscribe(filters));
+        }
+    }
+
+    fn leader_changed(&self, leader: PortId) {
+        if let Some(election) = self.election() {
+            election.leader.set(Some(leader));
+            election.claim_at.set(None);
+            election.last_heartbeat.set(js_sys::Date::now());
+        }
+        log::info!("relay pool leader:{leader}");
+        let queue = self.queue.borrow_mut().take();
+        if let Some(queue) = queue {
+            // the first leader, the queued messages include the state
+            for msg in queue {
+                self.post(msg);
+            }
+            return;
+        }
+        // the old leader is gone with the requests, send the state to the new one
+        self.reject_pending("relay pool leader changed");
+        self.restore();
+    }
+
+    fn lead(self: &Rc<Self>) {
+        let handler: OutputHandler = {
+            let inner = Rc::downgrade(self);
+            Rc::new(move |env: Envelope<WorkerOutput>| {
+                let inner = match inner.upgrade() {
+                    Some(inner) => inner,
+                    None => return,
+                };
+                if env.port == inner.port {
+                    inner.deliver(env);
+                } else if let Some(election) = inner.election() {
+                    election.post(&ChannelMsg::Output(env));
+                }
+            })
+        };
+        let worker = match WorkerHandle::spawn(handler) {
+            Ok(worker) => worker,
+            Err(e) => {
+                log::error!("start relay pool worker error:{e:?}");
+                return;
+            }
+        };
+        if let Some(election) = self.election() {
+            *election.worker.borrow_mut() = Some(worker);
+            election.post(&ChannelMsg::Heartbeat(self.port));
+        }
+        self.leader_changed(self.port);
+    }
+
+    fn tick(self: &Rc<Self>) {
+        let now = js_sys::Date::now();
+        let lead = {
+            let election = match self.election() {
+                Some(election) => election,
+                None => return,
+            };
+            if election.leader.get() == Some(self.port) {
+                election.post(&ChannelMsg::Heartbeat(self.port));
+                return;
+            }
+            if now - election.last_heartbeat.get() < LEADER_TIMEOUT_MS {
+                return;
+            }
+            match election.claim_at.get() {
+                None => {
+                    election.claim_at.set(Some(now));
+                    election.post(&ChannelMsg::Claim(self.port));
+                    false
+                }
+                Some(claim_at) => now - claim_at >= CLAIM_WAIT_MS,
+            }
+        };
+        if lead {
+            self.lead();
+        }
+    }
+
+    fn on_channel(&self, msg: ChannelMsg) {
+        let leader = match self.election() {
+            Some(election) => election.leader.get(),
+            None => return,
+        };
+        let me_leader = leader == Some(self.port);
+        match msg {
+            ChannelMsg::Heartbeat(id) => {
+                // two leaders, the smaller id wins
+                if me_leader && id > self.port {
+                    return;
+                }
+                if me_leader {
+                    if let Some(election) = self.election() {
+                        election.worker.borrow_mut().take();
+                    }
+                }
+                if leader != Some(id) {
+                    self.leader_changed(id);
+                } else if let Some(election) = self.election() {
+                    election.last_heartbeat.set(js_sys::Date::now());
+                }
+            }
+            ChannelMsg::Claim(id) => {
+                if let Some(election) = self.election() {
+                    if me_leader {
+                        election.post(&ChannelMsg::Heartbeat(self.port));
+                    } else if election.claim_at.get().is_some() && id < self.port {
+                        // give way to the smaller id
+                        election.claim_at.set(None);
+                        election.last_heartbeat.set(js_sys::Date::now());
+                    }
+                }
+            }
+            ChannelMsg::Input(env) => {
+                if me_leader {
+                    if let Some(election) = self.election() {
+                        if let Some(worker) = election.worker.borrow().as_ref() {
+                            worker.post(to_message(&env));
+                        }
+                    }
+                }
+            }
+            ChannelMsg::Output(env) => self.deliver(env),
+        }
+    }
+}
+
+fn has_global(name: &str) -> bool {
+    Reflect::has(&js_sys::global(), &JsValue::from_str(name)).unwrap_or(false)
+}
+
+pub struct RelayPool {
+    keys: Keys,
+    inner: Rc<Inner>,
+    sent: RefCell<IndexMap<EventId, Event>>, // the latest events sent
+    pagehide: Closure<dyn Fn()>,
+    pageshow: Closure<dyn Fn(PageTransitionEvent)>,
+    _heartbeat: Interval,
+}
+
+impl RelayPool {
+    pub fn new(keys: &Keys, on_output: Callback<WorkerOutput>) -> Result<Self, JsValue> {
+        let inner = Rc::new(Inner {
+            port: (js_sys::Math::random() * (1u64 << 53) as f64) as PortId,
+            transport: RefCell::new(None),
+            pending: RefCell::new(HashMap::new()),
+            next_id: Cell::new(0),
+            on_output,
+            start: Cell::new(None),
+            relays: RefCell::new(vec![]),
+            filters: RefCell::new(None),
+            queue: RefCell::new(None),
+        });
+        let handler: OutputHandler = {
+            let inner = Rc::downgrade(&inner);
+            Rc::new(move |env| {
+                if let Some(inner) = inner.upgrade() {
+                    inner.deliver(env);
+                }
+            })
+        };
+        let transport = if has_global("SharedWorker") {
+            Transport::Shared(SharedHandle::spawn(handler)?)
+        } else if has_global("BroadcastChannel") {
+            *inner.queue.borrow_mut() = Some(vec![]);
+            Transport::Channel(Election {
+                channel: BroadcastChannel::new(CHANNEL_NAME)?,
+                leader: Cell::new(None),
+                // lead soon if no other tab is leading
+                last_heartbeat: Cell::new(js_sys::Date::now() - LEADER_TIMEOUT_MS),
+                claim_at: Cell::new(None),
+                worker: RefCell::new(None),
+                onmessage: RefCell::new(None),
+                tick: RefCell::new(None),
+            })
+        } else {
+            Transport::Dedicated(WorkerHandle::spawn(handler)?)
+        };
+        *inner.transport.borrow_mut() = Some(transport);
+        if let Some(election) = inner.election() {
+            let onmessage = {
+                let inner = Rc::downgrade(&inner);
+                Closure::<dyn Fn(MessageEvent)>::new(move |e: MessageEvent| {
+                    if let (Some(inner), Some(msg)) =
+                        (inner.upgrade(), from_message::<ChannelMsg>(e.data()))
+                    {
+                        inner.on_channel(msg);
+                    }
+                })
+            };
+            election
+                .channel
+                .set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+            *election.onmessage.borrow_mut() = Some(onmessage);
+            let tick = {
+                let inner = Rc::downgrade(&inner);
+                Interval::new(HEARTBEAT_MS, move || {
+                    if let Some(inner) = inner.upgrade() {
+                        inner.tick();
+                    }
+                })
+            };
+            *election.tick.borrow_mut() = Some(tick);
+        }
+        // release the relays and subscriptions of the tab in the shared worker
+        let pagehide = {
+            let inner = Rc::downgrade(&inner);
+            Closure::<dyn Fn()>::new(move || {
+                if let Some(inner) = inner.upgrade() {
+                    inner.send(WebsocketWorkerInputMsg::Detach);
+                }
+            })
+        };
+        // restored from the back/forward cache after `Detach`
+        let pageshow = {
+            let inner = Rc::downgrade(&inner);
+            Closure::<dyn Fn(PageTransitionEvent)>::new(move |e: PageTransitionEvent| {
+                if let (true, Some(inner)) = (e.persisted(), inner.upgrade()) {
+                    inner.restore();
+                }
+            })
+        };
+        if let Some(window) = web_sys::window() {
+            window
+                .add_event_listener_with_callback("pagehide", pagehide.as_ref().unchecked_ref())
+                .ok();
+            window
+                .add_event_listener_with_callback("pageshow", pageshow.as_ref().unchecked_ref())
+                .ok();
+        }
+        // the worker evicts the silent tabs, e.g. crashed without `pagehide`
+        let heartbeat = {
+            let inner = Rc::downgrade(&inner);
+            Interval::new(HEARTBEAT_MS, move || {
+                if let Some(inner) = inner.upgrade() {
+                    inner.heartbeat();
+                }
+            })
+        };
+        inner.send(WebsocketWorkerInputMsg::Start(keys.public_key()));
+        Ok(Self {
+            keys: keys.clone(),
+            inner,
+            sent: RefCell::new(IndexMap::new()),
+            pagehide,
+            pageshow,
+            _heartbeat: heartbeat,
+        })
+    }
+
+    pub fn keys(&self) -> Keys {
+        self.keys.clone()
+    }
+
+    pub fn add_relay(&self, uri: Url) {
+        self.inner.send(WebsocketWorkerInputMsg::AddRelay(uri));
+    }
+
+    pub fn remove_relay(&self, uri: Url) {
+        self.inner.send(WebsocketWorkerInputMsg::RemoveRelay(uri));
+    }
+
+    pub fn subscribe(&self, filters: Vec<Filter>) {
+        self.inner.send(WebsocketWorkerInputMsg::Subscribe(filters));
+    }
+
+    // sent to the other tabs
+    pub fn share(&self, state: SharedState) {
+        self.inner.send(WebsocketWorkerInputMsg::Share(state));
+    }
+
+    async fn request(
+        &self,
+        timeout_ms: u32,
+        msg: impl FnOnce(u64) -> WebsocketWorkerInputMsg,
+    ) -> Result<Vec<Event>, String> {
+        let id = self.inner.next_id.get();
+        self.inner.next_id.set(id + 1);
+        let promise = Promise::new(&mut |resolve, _| {
+            self.inner.pending.borrow_mut().insert(id, resolve);
+        });
+        self.inner.send(msg(id));
+        // e.g. the tab was detached, or evicted by the worker
+        let _timeout = {
+            let inner = Rc::downgrade(&self.inner);
+            Timeout::new(timeout_ms, move || {
+                if let Some(inner) = inner.upgrade() {
+                    inner.reject(id, "relay pool request timeout");
+                }
+            })
+        };
+        let data = JsFuture::from(promise)
+            .await
+            .map_err(|e| format!("{e:?}"))?;
+        from_message::<Result<Vec<Event>, String>>(data)
+            .unwrap_or_else(|| Err("invalid response".to_owned()))
+    }
+
+    // an event sent lately by `send_msg`
+    pub fn sent_event(&self, id: &EventId) -> Option<Event> {
+        self.sent.borrow().get(id).cloned()
+    }
+
+    // send the subscription to the relay again
+    pub fn resubscribe(&self, uri: Url) {
+        self.inner.send(WebsocketWorkerInputMsg::Resubscribe(uri));
+    }
+
+    pub async fn send_msg(&self, msg: ClientMessage) -> Result<(), String> {
+        if let ClientMessage::Event(event) = &msg {
+            let mut sent = self.sent.borrow_mut();
+            sent.insert(event.id, *event.clone());
+            if sent.len() > MAX_SENT_EVENTS {
+                sent.shift_remove_index(0);
+            }
+        }
+        self.request(REQUEST_TIMEOUT_MS, |id| WebsocketWorkerInputMsg::Send {
+            id,
+            relay: None,
+            msg,
+        })
+        .await
+        .map(|_| ())
+    }
+
+    pub async fn send_msg_to(&self, relay: Url, msg: ClientMessage) -> Result<(), String> {
+        self.request(REQUEST_TIMEOUT_MS, |id| WebsocketWorkerInputMsg::Send {
+            id,
+            relay: Some(relay),
+            msg,
+        })
+        .await
+        .map(|_| ())
+    }
+
+    pub async fn send_event(&self, event: Event) -> Result<(), String> {
+        self.send_msg(ClientMessage::new_event(event)).await
+    }
+
+    pub async fn get_events_of(
+        &self,
+        filters: Vec<Filter>,
+        timeout: Duration,
+    ) -> Result<Vec<Event>, String> {
+        let timeout_ms = timeout.as_millis() as u32;
+        self.request(timeout_ms + REQUEST_TIMEOUT_MS, |id| {
+            WebsocketWorkerInputMsg::GetEventsOf {
+                id,
+                filters,
+                timeout_ms: timeout_ms as u64,
+            }
+        })
+        .await
+    }
+}
+
+impl Drop for RelayPool {
+    fn drop(&mut self) {
+        if let Some(window) = web_sys::window() {
+            window
+                .remove_event_listener_with_callback(
+                    "pagehide",
+                    self.pagehide.as_ref().unchecked_ref(),
+                )
+                .ok();
+            window
+                .remove_event_listener_with_callback(
+                    "pageshow",
+                    self.pageshow.as_ref().unchecked_ref(),
+                )
+                .ok();
+        }
+        self.inner.send(WebsocketWorkerInputMsg::Detach);
+        // the requests never get a response now
+        self.inner.reject_pending("relay pool closed");
+        // terminates the worker of this tab
+        self.inner.transport.borrow_mut().take();
+    }
+}
@@ -1,92 +1,816 @@
-use gloo_timers::callback::Timeout;
-use nostr_sdk::nostr::Url;
+// 在web worker里运行的relay连接池: 连接, 订阅, 验证签名和去重, 只把结果发给页面
+// 多个标签页共用一个worker, 每个标签页是一个port, 同一账号的标签页共用一个连接池
+use crate::ctxs::ScheduledNote;
+use gloo_timers::future::TimeoutFuture;
+use nostr_sdk::nostr::{
+    ClientMessage, Event, EventId, Filter, Keys, RelayMessage, SubscriptionId, Url,
+};
+use nostr_sdk::prelude::XOnlyPublicKey;
+use nostr_sdk::{Client, RelayPoolNotification};
 use serde::{Deserialize, Serialize};
-use std::sync::atomic::{AtomicU32, Ordering};
-use yew_agent::{HandlerId, Private, WorkerLink};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+use std::time::Duration;
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::spawn_local;
 
-pub struct WebsocketWorker {
-    link: WorkerLink<Self>,
-    uri: Option<Url>,
-    handle_id: Option<HandlerId>,
-    id: u32,
+// a random id of each tab
+pub type PortId = u64;
+
+// every message is tagged with the tab it is from or to
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Envelope<T> {
+    pub port: PortId,
+    pub msg: T,
 }
 
-#[derive(Serialize, Deserialize)]
+// the stored events of the relays, an empty list is an answer only if a relay sent EOSE,
+// not if they timed out or none is connected
+async fn get_events_of(
+    client: &Client,
+    filters: Vec<Filter>,
+    timeout: Duration,
+) -> Result<Vec<Event>, String> {
+    let mut events: Vec<Event> = vec![];
+    let mut answered = false;
+    for (uri, relay) in client.relays().await {
+        // an error if the relay times out before EOSE
+        match relay.get_events_of(filters.clone(), Some(timeout)).await {
+            Ok(stored) => {
+                answered = true;
+                for event in stored {
+                    if event.verify().is_ok() && !events.iter().any(|e| e.id == event.id) {
+                        events.push(event);
+                    }
+                }
+            }
+            Err(e) => log::warn!("get events from {uri} error:{e:?}"),
+        }
+    }
+    if answered {
+        Ok(events)
+    } else {
+        Err("no relay answered".to_owned())
+    }
+}
+
+// the page -> the worker
+#[derive(Debug, Serialize, Deserialize)]
 pub enum WebsocketWorkerInputMsg {
-    Uri(String),
-    Exit,
+    // only the public key, the events are signed in the page
+    Start(XOnlyPublicKey),
+    AddRelay(Url),
+    RemoveRelay(Url),
+    Subscribe(Vec<Filter>),
+    // send the subscription to the relay again, e.g. after the auth
+    Resubscribe(Url),
+    // to all the relays, or only to one
+    Send {
+        id: u64,
+        relay: Option<Url>,
+        msg: ClientMessage,
+    },
+    GetEventsOf {
+        id: u64,
+        filters: Vec<Filter>,
+        timeout_ms: u64,
+    },
+    // sent to the other tabs
+    Share(SharedState),
+    // the tab is alive, the silent ones are evicted
+    Heartbeat,
+    // the tab is closed, or kept in the back/forward cache
+    Detach,
+}
+
+// the worker -> the page
+#[derive(Debug, Serialize, Deserialize)]
+pub enum WorkerOutput {
+    // the worker is listening, the messages before it are queued in the page
+    Ready,
+    // verified and not seen before by the tab
+    Event(Url, Event),
+    // the messages except the events, and the events of the subscriptions sent to one relay
+    Message(Url, RelayMessage),
+    // the result of `Send` or `GetEventsOf`
+    Response {
+        id: u64,
+        result: Result<Vec<Event>, String>,
+    },
+    // EOSE of the subscription of all the tabs, the events from now on are new
+    Live(Url),
+    // from another tab
+    Shared(SharedState),
+    // sent when it changes, only one tab publishes the scheduled notes
+    Leader(Leader),
+    // the tab was evicted after a long silence, e.g. frozen in the background, its state is lost
+    Evicted,
+}
+
+// the tab with the smallest id among the tabs of its account, and among all the tabs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Leader {
+    pub account: bool,
+    pub all: bool,
+}
+
+// the state kept the same in all the tabs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SharedState {
+    // read_until and read_ids of the notifications, with their created_at
+    Read(u64, Vec<(EventId, u64)>),
+    // the queue of the scheduled notes, edited in any tab and published by the leader
+    Schedule(Vec<ScheduledNote>),
+}
+
+// the messages are json strings, the nostr types have no stable binary encoding
+pub fn to_message<T: Serialize>(msg: &T) -> JsValue {
+    match serde_json::to_string(msg) {
+        Ok(json) => JsValue::from_str(&json),
+        Err(e) => {
+            log::error!("encode worker message error:{e:?}");
+            JsValue::NULL
+        }
+    }
+}
+
+pub fn from_message<T: for<'de> Deserialize<'de>>(data: JsValue) -> Option<T> {
+    let json = data.as_string()?;
+    match serde_json::from_str(&json) {
+        Ok(msg) => Some(msg),
+        Err(e) => {
+            log::error!("decode worker message error:{e:?}");
+            None
+        }
+    }
+}
+
+// the ids of the latest events, to drop the same event from other relays
+#[derive(Default)]
+struct SeenEvents {
+    ids: HashSet<EventId>,
+    order: VecDeque<EventId>,
+}
+
+impl SeenEvents {
+    const MAX_SEEN: usize = 10_000;
+
+    fn insert(&mut self, id: EventId) -> bool {
+        if !self.ids.insert(id) {
+            return false;
+        }
+        self.order.push_back(id);
+        if self.order.len() > Self::MAX_SEEN {
+            if let Some(old) = self.order.pop_front() {
+                self.ids.remove(&old);
+            }
+        }
+        true
+    }
+}
+
+// NIP-01 matching, the nostr crate has none; `search` and `limit` are left to the relay.
+// the hashtags are compared ignoring the case, a tab would rather get one more event than miss one
+fn filter_match(filter: &Filter, event: &Event) -> bool {
+    let tag_values = |name: &str| {
+        event
+            .tags
+            .iter()
+            .map(|tag| tag.as_vec())
+            .filter(|tag| tag.len() > 1 && tag[0] == name)
+            .map(|mut tag| tag.swap_remove(1))
+            .collect::<Vec<_>>()
+    };
+    let has_tag = |name: &str, values: &[String]| {
+        tag_values(name)
+            .iter()
+            .any(|v| values.iter().any(|value| value.eq_ignore_ascii_case(v)))
+    };
+    let hex = |values: Vec<String>| {
+        values
+            .into_iter()
+            .map(|v| v.to_lowercase())
+            .collect::<Vec<_>>()
+    };
+    filter.ids.as_ref().map_or(true, |ids| {
+        let id = event.id.to_hex();
+        ids.iter()
+            .any(|prefix| id.starts_with(&prefix.to_lowercase()))
+    }) && filter
+        .authors
+        .as_ref()
+        .map_or(true, |authors| authors.contains(&event.pubkey))
+        && filter
+            .kinds
+            .as_ref()
+            .map_or(true, |kinds| kinds.contains(&event.kind))
+        && filter.events.as_ref().map_or(true, |ids| {
+            has_tag("e", &hex(ids.iter().map(|id| id.to_hex()).collect()))
+        })
+        && filter.pubkeys.as_ref().map_or(true, |pks| {
+            has_tag("p", &hex(pks.iter().map(|pk| pk.to_string()).collect()))
+        })
+        && filter
+            .hashtags
+            .as_ref()
+            .map_or(true, |tags| has_tag("t", tags))
+        && filter
+            .references
+            .as_ref()
+            .map_or(true, |refs| has_tag("r", refs))
+        && filter.since.map_or(true, |since| event.created_at >= since)
+        && filter.until.map_or(true, |until| event.created_at <= until)
+        && filter.custom.iter().all(|(key, values)| {
+            let name = match key.strip_prefix('#') {
+                Some(name) => name,
+                // not a tag query, e.g. an extension of a relay
+                None => return true,
+            };
+            let values = values
+                .as_array()
+                .map(|values| {
+                    values
+                        .iter()
+                        .filter_map(|v| v.as_str().map(str::to_owned))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            has_tag(name, &values)
+        })
+}
+
+#[derive(Default)]
+struct Port {
+    // the account logged in the tab, bound by `Start`
+    account: Option<XOnlyPublicKey>,
+    relays: HashSet<Url>,
+    filters: Vec<Filter>,
+    seen: SeenEvents,
+    direct_subs: HashSet<String>, // 只发给单个relay的订阅, 其事件带着订阅id转发
+    leader: Option<Leader>,       // 最近一次发给这个标签页的选举结果
+    alive: bool,                  // 上次`evict_silent`之后收到过消息
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct WorkerOutput {
-    pub value: u32,
-    pub uri: String,
+// the relay pool of an account, shared by the tabs logged in with it
+struct Account {
+    client: Client,
+    relays: Rc<RefCell<HashSet<Url>>>, // 这个账号的标签页用到的relay, 删除之后不再重连
 }
 
-pub enum ThisWorkerMsg {
-    Loop,
-    Stop,
+pub struct WebsocketWorker {
+    accounts: HashMap<XOnlyPublicKey, Account>,
+    respond: Rc<dyn Fn(PortId, WorkerOutput)>,
+    ports: Rc<RefCell<HashMap<PortId, Port>>>,
 }
-static PRIVATE_ID_COUNTER: AtomicU32 = AtomicU32::new(1);
 
-impl yew_agent::Worker for WebsocketWorker {
-    type Input = WebsocketWorkerInputMsg;
-    type Message = ThisWorkerMsg;
-    type Output = WorkerOutput;
-    type Reach = Private<Self>;
+impl WebsocketWorker {
+    const MAX_RETRIES: u32 = 10;
+    const RETRY_INTERVAL: u32 = 1_000;
+    // the timers of a hidden tab may run only once a minute
+    pub const EVICT_INTERVAL: u32 = 120_000;
 
-    fn create(link: WorkerLink<Self>) -> Self {
-        let id = PRIVATE_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+    pub fn new(respond: impl Fn(PortId, WorkerOutput) + 'static) -> Self {
         Self {
-            id,
-            link,
-            uri: None,
-            handle_id: None,
+            accounts: HashMap::new(),
+            respond: Rc::new(respond),
+            ports: Rc::new(RefCell::new(HashMap::new())),
         }
     }
 
-    fn update(&mut self, msg: Self::Message) {
-        // no messaging
-        log::info!("WebsocketWorker update");
-        match msg {
-            ThisWorkerMsg::Loop => {
-                let hdl = self.handle_id;
-                let lnk = self.link.clone();
-                let uri = format!("{}-{}", self.uri.as_ref().unwrap().as_str(), self.id);
-                log::info!("{uri}");
-                // let timeout = Timeout::new(1_000, move || {
-                //     lnk.send_message(ThisWorkerMsg::Loop);
-                //     if let Some(handle_id) = hdl {
-                //         lnk.respond(handle_id, WorkerOutput { value: 1000, uri });
-                //     }
-                //     // Do something after the one second timeout is up!
-                // });
+    async fn connect(client: Client, relays: Rc<RefCell<HashSet<Url>>>, uri: Url) {
+        for retry in 0..=Self::MAX_RETRIES {
+            if !relays.borrow().contains(&uri) {
+                return;
+            }
+            match client.connect_relay(uri.to_string()).await {
+                Ok(_) => return,
+                Err(e) => {
+                    log::warn!("connect {uri} error:{e:?}");
+                    TimeoutFuture::new(Self::RETRY_INTERVAL * (retry + 1)).await;
+                }
+            }
+        }
+    }
+
+    fn account_of(&self, port: PortId) -> Option<XOnlyPublicKey> {
+        self.ports.borrow().get(&port).and_then(|p| p.account)
+    }
+
+    fn client_of(&self, port: PortId) -> Option<Client> {
+        let pk = self.account_of(port)?;
+        self.accounts.get(&pk).map(|account| account.client.clone())
+    }
+
+    // the tabs of the account
+    fn port_ids(ports: &HashMap<PortId, Port>, pk: &XOnlyPublicKey) -> Vec<PortId> {
+        ports
+            .iter()
+            .filter(|(_, port)| port.account.as_ref() == Some(pk))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    // the leaders of the started tabs
+    fn leaders(ports: &HashMap<PortId, Port>) -> Vec<(PortId, Leader)> {
+        let all = ports
+            .iter()
+            .filter(|(_, port)| port.account.is_some())
+            .map(|(id, _)| *id)
+            .min();
+        ports
+            .iter()
+            .filter_map(|(id, port)| {
+                let pk = port.account.as_ref()?;
+                let account = Self::port_ids(ports, pk).into_iter().min() == Some(*id);
+                let all = all == Some(*id);
+                Some((*id, Leader { account, all }))
+            })
+            .collect()
+    }
+
+    // tell the tabs whose leader changed, after a tab is started or closed
+    fn elect(&self) {
+        let leaders = Self::leaders(&self.ports.borrow());
+        let changed = leaders
+            .into_iter()
+            .filter(|(id, leader)| {
+                self.ports
+                    .borrow_mut()
+                    .get_mut(id)
+                    .map(|port| port.leader.replace(*leader) != Some(*leader))
+                    .unwrap_or(false)
+            })
+            .collect::<Vec<_>>();
+        for (id, leader) in changed {
+            (self.respond)(id, WorkerOutput::Leader(leader));
+        }
+    }
+
+    fn start(&mut self, port: PortId, pk: XOnlyPublicKey) {
+        let old = self
+            .ports
+            .borrow_mut()
+            .get_mut(&port)
+            .and_then(|p| p.account.replace(pk));
+        if !self.accounts.contains_key(&pk) {
+            let client = Self::spawn_client(pk, self.respond.clone(), self.ports.clone());
+            self.accounts.insert(
+                pk,
+                Account {
+                    client,
+                    relays: Rc::new(RefCell::new(HashSet::new())),
+                },
+            );
+        }
+        if old != Some(pk) {
+            self.sync_relays(&pk);
+            self.resubscribe(&pk);
+        }
+        // another account, e.g. switched in the tab
+        if let Some(old) = old.filter(|old| *old != pk) {
+            self.release(&old);
+        }
+        self.elect();
+    }
 
-                // // Since we don't plan on cancelling the timeout, call `forget`.
-                // timeout.forget();
+    // shut down the client of the account if no tab uses it, or drop the relays and filters of the tab
+    fn release(&mut self, pk: &XOnlyPublicKey) {
+        if !Self::port_ids(&self.ports.borrow(), pk).is_empty() {
+            self.sync_relays(pk);
+            self.resubscribe(pk);
+            return;
+        }
+        if let Some(account) = self.accounts.remove(pk) {
+            account.relays.borrow_mut().clear();
+            spawn_local(async move {
+                if let Err(e) = account.client.shutdown().await {
+                    log::warn!("shutdown error:{e:?}");
+                }
+            });
+        }
+    }
+
+    fn spawn_client(
+        pk: XOnlyPublicKey,
+        respond: Rc<dyn Fn(PortId, WorkerOutput)>,
+        ports: Rc<RefCell<HashMap<PortId, Port>>>,
+    ) -> Client {
+        let client = Client::new(&Keys::from_public_key(pk));
+        let mut notifications = client.notifications();
+        let relay_client = client.clone();
+        spawn_local(async move {
+            while let Ok(notification) = notifications.recv().await {
+                match notification {
+                    RelayPoolNotification::Event(uri, event) => {
+                        if event.verify().is_err() {
+                            log::warn!("invalid signature {} from {uri}", event.id);
+                            continue;
+                        }
+                        // only to the tabs subscribed to it, the others get their
+                        // `get_events_of` in the response and their direct subscriptions as messages
+                        for (id, port) in ports.borrow_mut().iter_mut() {
+                            if port.account != Some(pk)
+                                || !port.filters.iter().any(|f| filter_match(f, &event))
+                            {
+                                continue;
+                            }
+                            if port.seen.insert(event.id) {
+                                respond(*id, WorkerOutput::Event(uri.clone(), event.clone()));
+                            }
+                        }
+                    }
+                    RelayPoolNotification::Message(uri, msg) => {
+                        if let RelayMessage::EndOfStoredEvents(sub) = &msg {
+                            Self::check_live(&relay_client, &respond, &ports, &pk, &uri, sub);
+                        }
+                        let sub = match &msg {
+                            RelayMessage::Event {
+                                subscription_id, ..
+                            } => Some(subscription_id.to_string()),
+                            _ => None,
+                        };
+                        // one tab answers the challenge for the account, the smallest id
+                        // is the same whatever the order of the map
+                        let auth_port = match &msg {
+                            RelayMessage::Auth { .. } => {
+                                Self::port_ids(&ports.borrow(), &pk).into_iter().min()
+                            }
+                            _ => None,
+                        };
+                        for (id, port) in ports.borrow().iter() {
+                            if port.account != Some(pk) {
+                                continue;
+                            }
+                            let forward = match (&sub, auth_port) {
+                                (Some(sub), _) => port.direct_subs.contains(sub),
+                                (None, Some(auth_port)) => *id == auth_port,
+                                (None, None) => true,
+                            };
+                            if forward {
+                                respond(*id, WorkerOutput::Message(uri.clone(), msg.clone()));
+                            }
+                        }
+                    }
+                    RelayPoolNotification::Shutdown => return,
+                }
+            }
+        });
+        client
+    }
+
+    // the EOSE of the other subscriptions, e.g. `get_events_of`, isn't live
+    fn check_live(
+        client: &Client,
+        respond: &Rc<dyn Fn(PortId, WorkerOutput)>,
+        ports: &Rc<RefCell<HashMap<PortId, Port>>>,
+        pk: &XOnlyPublicKey,
+        uri: &Url,
+        sub: &SubscriptionId,
+    ) {
+        let (client, respond, ports) = (client.clone(), respond.clone(), ports.clone());
+        let (pk, uri, sub) = (*pk, uri.clone(), sub.clone());
+        spawn_local(async move {
+            if let Some(relay) = client.relays().await.get(&uri) {
+                if relay.subscription().await.id == sub {
+                    for id in Self::port_ids(&ports.borrow(), &pk) {
+                        respond(id, WorkerOutput::Live(uri.clone()));
+                    }
+                }
+            }
+        });
+    }
+
+    // connect the relays used by any tab of the account, and disconnect the others
+    fn sync_relays(&self, pk: &XOnlyPublicKey) {
+        let account = match self.accounts.get(pk) {
+            Some(account) => account,
+            None => return,
+        };
+        let client = account.client.clone();
+        let wanted = self
+            .ports
+            .borrow()
+            .values()
+            .filter(|port| port.account.as_ref() == Some(pk))
+            .flat_map(|port| port.relays.iter().cloned())
+            .collect::<HashSet<_>>();
+        let (added, removed) = {
+            let cur = account.relays.borrow();
+            (
+                wanted.difference(&cur).cloned().collect::<Vec<_>>(),
+                cur.difference(&wanted).cloned().collect::<Vec<_>>(),
+            )
+        };
+        *account.relays.borrow_mut() = wanted;
+        for uri in added {
+            let client = client.clone();
+            let relays = account.relays.clone();
+            spawn_local(async move {
+                if let Err(e) = client.add_relay(uri.to_string()).await {
+                    log::warn!("add {uri} error:{e:?}");
+                    return;
+                }
+                Self::connect(client, relays, uri).await;
+            });
+        }
+        for uri in removed {
+            let client = client.clone();
+            spawn_local(async move {
+                if let Err(e) = client.remove_relay(uri.to_string()).await {
+                    log::warn!("remove {uri} error:{e:?}");
+                }
+            });
+        }
+    }
+
+    fn filters(&self, pk: &XOnlyPublicKey) -> Vec<Filter> {
+        let mut filters = vec![];
+        for filter in self
+            .ports
+            .borrow()
+            .values()
+            .filter(|port| port.account.as_ref() == Some(pk))
+            .flat_map(|port| port.filters.iter())
+        {
+            if !filters.contains(filter) {
+                filters.push(filter.clone());
             }
-            ThisWorkerMsg::Stop => {}
         }
+        filters
+    }
+
+    // one subscription with the filters of all the tabs of the account
+    fn resubscribe(&self, pk: &XOnlyPublicKey) {
+        let client = match self.accounts.get(pk) {
+            Some(account) => account.client.clone(),
+            None => return,
+        };
+        let filters = self.filters(pk);
+        if filters.is_empty() {
+            return;
+        }
+        spawn_local(async move { client.subscribe(filters).await });
+    }
+
+    fn resubscribe_relay(&self, pk: &XOnlyPublicKey, uri: Url) {
+        let client = match self.accounts.get(pk) {
+            Some(account) => account.client.clone(),
+            None => return,
+        };
+        let filters = self.filters(pk);
+        if filters.is_empty() {
+            return;
+        }
+        spawn_local(async move {
+            if let Some(relay) = client.relays().await.get(&uri) {
+                if let Err(e) = relay.subscribe(filters).await {
+                    log::warn!("resubscribe {uri} error:{e:?}");
+                }
+            }
+        });
+    }
+
+    fn detach(&mut self, port: PortId) {
+        let account = self.account_of(port);
+        self.ports.borrow_mut().remove(&port);
+        if let Some(pk) = account {
+            self.release(&pk);
+        }
+        self.elect();
     }
 
-    fn handle_input(&mut self, msg: Self::Input, id: HandlerId) {
+    // called every `EVICT_INTERVAL`, detach the tabs silent since the last call, e.g. crashed
+    pub fn evict_silent(&mut self) -> Vec<PortId> {
+        let silent = self
+            .ports
+            .borrow_mut()
+            .iter_mut()
+            .filter_map(|(id, port)| (!std::mem::replace(&mut port.alive, false)).then_some(*id))
+            .collect::<Vec<_>>();
+        for port in &silent {
+            log::info!("evict silent tab {port}");
+            self.detach(*port);
+        }
+        silent
+    }
+
+    pub fn handle_input(&mut self, port: PortId, msg: WebsocketWorkerInputMsg) {
+        let known = self.ports.borrow().contains_key(&port);
+        self.ports.borrow_mut().entry(port).or_default().alive = true;
+        let account = self.account_of(port);
         match msg {
-            WebsocketWorkerInputMsg::Uri(uri) => {
-                if let Ok(_uri) = Url::parse(&uri) {
-                    self.uri = Some(_uri);
-                    self.link.send_message(ThisWorkerMsg::Loop);
+            WebsocketWorkerInputMsg::Start(pk) => self.start(port, pk),
+            WebsocketWorkerInputMsg::AddRelay(uri) => {
+                if let Some(p) = self.ports.borrow_mut().get_mut(&port) {
+                    p.relays.insert(uri);
+                }
+                if let Some(pk) = account {
+                    self.sync_relays(&pk);
+                }
+            }
+            WebsocketWorkerInputMsg::RemoveRelay(uri) => {
+                if let Some(p) = self.ports.borrow_mut().get_mut(&port) {
+                    p.relays.remove(&uri);
+                }
+                if let Some(pk) = account {
+                    self.sync_relays(&pk);
+                }
+            }
+            WebsocketWorkerInputMsg::Subscribe(filters) => {
+                if let Some(p) = self.ports.borrow_mut().get_mut(&port) {
+                    p.filters = filters;
+                }
+                if let Some(pk) = account {
+                    self.resubscribe(&pk);
+                }
+            }
+            WebsocketWorkerInputMsg::Resubscribe(uri) => {
+                if let Some(pk) = account {
+                    self.resubscribe_relay(&pk, uri);
+                }
+            }
+            WebsocketWorkerInputMsg::Send { id, relay, msg } => {
+                if relay.is_some() {
+                    if let Some(p) = self.ports.borrow_mut().get_mut(&port) {
+                        match &msg {
+                            ClientMessage::Req {
+                                subscription_id, ..
+                            } => {
+                                p.direct_subs.insert(subscription_id.to_string());
+                            }
+                            ClientMessage::Close(subscription_id) => {
+                                p.direct_subs.remove(&subscription_id.to_string());
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                let client = self.client_of(port);
+                let respond = self.respond.clone();
+                spawn_local(async move {
+                    let result = match (client, relay) {
+                        (Some(client), Some(uri)) => client
+                            .send_msg_to(uri.to_string(), msg)
+                            .await
+                            .map_err(|e| e.to_string()),
+                        (Some(client), None) => {
+                            client.send_msg(msg).await.map_err(|e| e.to_string())
+                        }
+                        (None, _) => Err("worker isn't started".to_owned()),
+                    };
+                    respond(
+                        port,
+                        WorkerOutput::Response {
+                            id,
+                            result: result.map(|_| vec![]),
+                        },
+                    );
+                });
+            }
+            WebsocketWorkerInputMsg::GetEventsOf {
+                id,
+                filters,
+                timeout_ms,
+            } => {
+                let client = self.client_of(port);
+                let respond = self.respond.clone();
+                spawn_local(async move {
+                    let result = match client {
+                        Some(client) => {
+                            get_events_of(&client, filters, Duration::from_millis(timeout_ms)).await
+                        }
+                        None => Err("worker isn't started".to_owned()),
+                    };
+                    respond(port, WorkerOutput::Response { id, result });
+                });
+            }
+            // the state of the account to its other tabs, the queue of all the accounts to all the tabs
+            WebsocketWorkerInputMsg::Share(state) => {
+                let ids = match (&state, account) {
+                    (SharedState::Schedule(_), _) => self.ports.borrow().keys().cloned().collect(),
+                    (_, Some(pk)) => Self::port_ids(&self.ports.borrow(), &pk),
+                    (_, None) => vec![],
+                };
+                for id in ids.into_iter().filter(|id| *id != port) {
+                    (self.respond)(id, WorkerOutput::Shared(state.clone()));
+                }
+            }
+            WebsocketWorkerInputMsg::Heartbeat => {
+                if !known {
+                    (self.respond)(port, WorkerOutput::Evicted);
                 }
             }
-            WebsocketWorkerInputMsg::Exit => self.link.send_message(ThisWorkerMsg::Stop),
+            WebsocketWorkerInputMsg::Detach => self.detach(port),
         }
-        self.handle_id = Some(id);
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr_sdk::nostr::{EventBuilder, Kind, Tag, Timestamp};
+    use serde_json::{json, Map};
 
-    fn name_of_resource() -> &'static str {
-        "worker.js"
+    fn event(keys: &Keys, kind: Kind, tags: Vec<Tag>) -> Event {
+        EventBuilder::new(kind, "", &tags).to_event(keys).unwrap()
     }
 
-    fn resource_path_is_relative() -> bool {
-        true
+    #[test]
+    fn filter_match_fields() {
+        let keys = Keys::generate();
+        let other = Keys::generate();
+        let e = event(
+            &keys,
+            Kind::TextNote,
+            vec![
+                Tag::PubKey(other.public_key(), None),
+                Tag::Hashtag("Nostr".to_owned()),
+            ],
+        );
+        assert!(filter_match(&Filter::new(), &e));
+        assert!(filter_match(&Filter::new().author(keys.public_key()), &e));
+        assert!(!filter_match(&Filter::new().author(other.public_key()), &e));
+        assert!(filter_match(&Filter::new().kind(Kind::TextNote), &e));
+        assert!(!filter_match(&Filter::new().kind(Kind::Metadata), &e));
+        assert!(filter_match(&Filter::new().pubkey(other.public_key()), &e));
+        assert!(!filter_match(&Filter::new().pubkey(keys.public_key()), &e));
+        assert!(filter_match(&Filter::new().hashtag("nostr"), &e));
+        assert!(filter_match(&Filter::new().id(&e.id.to_hex()[..8]), &e));
+        assert!(filter_match(&Filter::new().since(e.created_at), &e));
+        assert!(!filter_match(&Filter::new().since(e.created_at + 1u64), &e));
+        assert!(!filter_match(&Filter::new().until(Timestamp::from(0)), &e));
+        // all the fields of a filter
+        assert!(!filter_match(
+            &Filter::new()
+                .kind(Kind::TextNote)
+                .author(other.public_key()),
+            &e
+        ));
+    }
+
+    #[test]
+    fn filter_match_custom_tags() {
+        let keys = Keys::generate();
+        let e = event(
+            &keys,
+            Kind::Custom(30000),
+            vec![Tag::Identifier("friends".to_owned())],
+        );
+        let mut custom = Map::new();
+        custom.insert("#d".to_owned(), json!(["friends", "family"]));
+        assert!(filter_match(&Filter::new().custom(custom.clone()), &e));
+        custom.insert("#d".to_owned(), json!(["family"]));
+        assert!(!filter_match(&Filter::new().custom(custom), &e));
+    }
+
+    #[test]
+    fn evict_silent_ports() {
+        let evicted = Rc::new(RefCell::new(vec![]));
+        let mut worker = {
+            let evicted = evicted.clone();
+            WebsocketWorker::new(move |port, msg| {
+                if matches!(msg, WorkerOutput::Evicted) {
+                    evicted.borrow_mut().push(port);
+                }
+            })
+        };
+        worker.handle_input(1, WebsocketWorkerInputMsg::Heartbeat);
+        worker.handle_input(2, WebsocketWorkerInputMsg::Heartbeat);
+        // unknown to the worker, told to send its state again
+        assert_eq!(*evicted.borrow(), vec![1, 2]);
+        assert!(worker.evict_silent().is_empty());
+        worker.handle_input(1, WebsocketWorkerInputMsg::Heartbeat);
+        assert_eq!(worker.evict_silent(), vec![2]);
+        assert_eq!(*evicted.borrow(), vec![1, 2]);
+        assert_eq!(worker.evict_silent(), vec![1]);
+        assert!(worker.ports.borrow().is_empty());
+    }
+
+    #[test]
+    fn leaders_of_accounts() {
+        let a = Keys::generate().public_key();
+        let b = Keys::generate().public_key();
+        let mut ports = HashMap::new();
+        for (id, account) in [(5, Some(a)), (3, Some(b)), (7, Some(a)), (1, None)] {
+            ports.insert(
+                id,
+                Port {
+                    account,
+                    ..Default::default()
+                },
+            );
+        }
+        let mut leaders = WebsocketWorker::leaders(&ports);
+        leaders.sort_by_key(|(id, _)| *id);
+        let leader = |account, all| Leader { account, all };
+        assert_eq!(
+            leaders,
+            vec![
+                (3, leader(true, true)),
+                (5, leader(true, false)),
+                (7, leader(false, false)),
+            ]
+        );
     }
 }
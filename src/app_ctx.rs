@@ -1,21 +1,60 @@
-use std::rc::Rc;
-
 use crate::app_ws::*;
 use crate::ctxs::*;
 use crate::fallback::PleaseWait;
+use gloo_timers::callback::Interval;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
 use yew::prelude::*;
-#[derive(Clone, Debug, PartialEq, Default)]
-pub enum Theme {
-    Dark,
-    #[default]
-    Light,
-}
 
 #[function_component(AppCtx)]
 pub fn app_ctx() -> Html {
     let Fallback = html! {<PleaseWait />};
-    let theme = use_memo(|_| Theme::Light, ());
-    let locale = use_reducer(|| I18nLocale::Zhcn);
+    let theme = use_reducer(Theme::init);
+    {
+        // follow the system when it changes
+        let theme = theme.clone();
+        use_effect_with_deps(
+            move |_| {
+                let query = dark_media_query();
+                let onchange = Closure::<dyn Fn(web_sys::MediaQueryListEvent)>::new(
+                    move |e: web_sys::MediaQueryListEvent| {
+                        theme.dispatch(ThemeAction::SystemChanged(e.matches()))
+                    },
+                );
+                if let Some(query) = &query {
+                    query
+                        .add_event_listener_with_callback(
+                            "change",
+                            onchange.as_ref().unchecked_ref(),
+                        )
+                        .ok();
+                }
+                move || {
+                    if let Some(query) = &query {
+                        query
+                            .remove_event_listener_with_callback(
+                                "change",
+                                onchange.as_ref().unchecked_ref(),
+                            )
+                            .ok();
+                    }
+                }
+            },
+            (),
+        );
+    }
+    let locale = use_reducer(I18nLocale::init);
+    let clock = use_reducer(Clock::default);
+    {
+        let clock = clock.clone();
+        use_effect_with_deps(
+            move |_| {
+                let tick = Interval::new(CLOCK_TICK_MS, move || clock.dispatch(ClockAction::Tick));
+                move || drop(tick)
+            },
+            (),
+        );
+    }
     // let modal = use_reducer(|| Modal(false));
     let user = use_reducer(|| User::load());
     let user_contact = use_reducer(|| UserContact::load());
@@ -23,11 +62,20 @@ pub fn app_ctx() -> Html {
     let user_event = use_reducer(|| UserEvent::default());
     let subsciption = use_reducer(|| Subscription::default());
     let send_msg = use_reducer(|| SendMsgEvent::default());
+    let search = use_reducer(|| UserSearch::default());
+    let notification = use_reducer(|| UserNotification::load());
+    let mute = use_reducer(|| UserMute::load());
+    let pow = use_reducer(|| Pow::load());
+    let relay_status = use_reducer(|| RelayStatus::default());
+    let drafts = use_reducer(|| Drafts::load());
+    let media_server = use_reducer(|| MediaServer::load());
+    let content_warning = use_reducer(|| ContentWarnings::load());
+    let schedule = use_reducer(|| Schedule::load());
 
     html!(
         <>
         <Suspense fallback={Fallback}>
-            <ContextProvider<Rc<Theme>> context={theme}>
+            <ContextProvider<ThemeContext> context={theme}>
                     <ContextProvider<I18nLocaleContext> context={locale}>
                             <ContextProvider<UserContext> context={user}>
                                 <ContextProvider<UserEventContext> context={user_event}>
@@ -35,7 +83,27 @@ pub fn app_ctx() -> Html {
                                         <ContextProvider<SendMsgEventContext> context={send_msg}>
                                             <ContextProvider<SubscriptionContext> context={subsciption}>
                                                 <ContextProvider<UserContactContext> context={user_contact}>
-                                                    <AppClient/>
+                                                    <ContextProvider<UserSearchContext> context={search}>
+                                                        <ContextProvider<UserNotificationContext> context={notification}>
+                                                            <ContextProvider<UserMuteContext> context={mute}>
+                                                                <ContextProvider<PowContext> context={pow}>
+                                                                    <ContextProvider<RelayStatusContext> context={relay_status}>
+                                                                        <ContextProvider<ClockContext> context={clock}>
+                                                                            <ContextProvider<DraftContext> context={drafts}>
+                                                                                <ContextProvider<MediaServerContext> context={media_server}>
+                                                                                    <ContextProvider<ContentWarningContext> context={content_warning}>
+                                                                                        <ContextProvider<ScheduleContext> context={schedule}>
+                                                                                            <AppClient/>
+                                                                                        </ContextProvider<ScheduleContext>>
+                                                                                    </ContextProvider<ContentWarningContext>>
+                                                                                </ContextProvider<MediaServerContext>>
+                                                                            </ContextProvider<DraftContext>>
+                                                                        </ContextProvider<ClockContext>>
+                                                                    </ContextProvider<RelayStatusContext>>
+                                                                </ContextProvider<PowContext>>
+                                                            </ContextProvider<UserMuteContext>>
+                                                        </ContextProvider<UserNotificationContext>>
+                                                    </ContextProvider<UserSearchContext>>
                                                 </ContextProvider<UserContactContext>>
                                             </ContextProvider<SubscriptionContext>>
                                         </ContextProvider<SendMsgEventContext>>
@@ -43,7 +111,7 @@ pub fn app_ctx() -> Html {
                                 </ContextProvider<UserEventContext>>
                         </ContextProvider<UserContext>>
                     </ContextProvider<I18nLocaleContext>>
-            </ContextProvider<Rc<Theme>>>
+            </ContextProvider<ThemeContext>>
         </Suspense>
     </>
     )
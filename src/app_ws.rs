@@ -1,27 +1,43 @@
+use crate::agent::{Leader, RelayPool, SharedState, WorkerOutput};
 use crate::ctxs::*;
 use crate::route::*;
+use crate::tauri;
+use crate::utils::{fetch_relay_document, front_n_chars};
+use nostr_sdk::nostr::nips::nip11::RelayInformationDocument;
 use nostr_sdk::{
-    Client, ClientMessage, Contact, Filter, Kind, RelayMessage, RelayPoolNotification, Timestamp,
-    Url,
+    ClientMessage, Contact, Event, EventBuilder, EventId, Filter, Kind, Metadata, RelayMessage,
+    RelayPoolNotification, SubscriptionId, Tag, Timestamp, Url,
 };
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use std::rc::Rc;
+use std::time::Duration;
 
+use gloo_timers::future::TimeoutFuture;
+use nostr_sdk::prelude::XOnlyPublicKey;
 use wasm_bindgen_futures::spawn_local;
 use yew::prelude::*;
 use yew_router::prelude::*;
 
 #[derive(Debug)]
 pub enum AppMsg {
-    ReconnectWebsocket(Url, usize),
     RelayCtx(RelayContext),
     UserEventCtx(UserEventContext),
     UserCtx(UserContext),
     RelayPoolNotification(RelayPoolNotification),
     SendMsgEventCtx(SendMsgEventContext),
     UserContactCtx(UserContactContext),
+    SearchCtx(UserSearchContext),
+    NotificationCtx(UserNotificationContext),
+    MuteCtx(UserMuteContext),
+    PowCtx(PowContext),
+    RelayStatusCtx(RelayStatusContext),
+    ClockCtx(ClockContext),
+    ScheduleCtx(ScheduleContext),
+    Live(Url),           // EOSE of the subscription
+    Shared(SharedState), // from another tab
+    Leader(Leader),      // the scheduled notes are published by one tab
 
     Noop,
 }
@@ -29,10 +45,9 @@ pub enum AppMsg {
 #[derive(Debug, Clone)]
 pub struct WsInfo {
     pub uri: Url,
-    pub retry: i32, // 记录失败重连之后重试次数， 0表示是成功连接的
 }
 pub struct AppClient {
-    client: Option<Rc<RefCell<Client>>>,
+    client: Option<Rc<RelayPool>>, // the connections are in a web worker
     cur_relays: Rc<RefCell<HashMap<usize, WsInfo>>>,
     send_msg_handler: SendMsgEventContext,
     _send_msg_listener: ContextHandle<SendMsgEventContext>,
@@ -44,35 +59,49 @@ pub struct AppClient {
     _user_handle_listener: ContextHandle<UserContext>,
     user_contact_listener: ContextHandle<UserContactContext>,
     user_contact_handle: UserContactContext,
+    search_handle: UserSearchContext,
+    _search_listener: ContextHandle<UserSearchContext>,
+    search_sub_id: Option<SubscriptionId>, // 当前发送给relay的搜索订阅
+    search_event_ids: HashSet<EventId>,    // 搜索得到的事件， 不放入消息列表
+    relay_docs: Rc<RefCell<HashMap<Url, RelayInformationDocument>>>, // NIP-11
+    notification_handle: UserNotificationContext,
+    _notification_listener: ContextHandle<UserNotificationContext>,
+    shared_read: (u64, usize), // 最近一次同步给其他标签页的已读状态
+    started_at: Timestamp,     // 只对启动之后的通知弹出桌面提醒
+    contact_sync_pk: Option<XOnlyPublicKey>, // 已经获取过kind 3的用户
+    published_contacts: Option<Vec<Contact>>, // 最近一次发布(或从relay获取)的kind 3
+    fetching_follows: Option<XOnlyPublicKey>, // 正在获取谁的关注列表
+    mute_handle: UserMuteContext,
+    _mute_listener: ContextHandle<UserMuteContext>,
+    pow_handle: PowContext,
+    _pow_listener: ContextHandle<PowContext>,
+    relay_status_handle: RelayStatusContext,
+    _relay_status_listener: ContextHandle<RelayStatusContext>,
+    _clock_listener: ContextHandle<ClockContext>, // 定时清除过期的事件, 发送到时间的消息
+    schedule_handle: ScheduleContext,
+    _schedule_listener: ContextHandle<ScheduleContext>,
+    auth_events: HashMap<EventId, Url>, // NIP-42 认证事件, 等待relay的OK
+    auth_retry: HashMap<Url, (Timestamp, Vec<Event>)>, // 因为没有认证被拒绝的事件, 认证之后重发, 超时则放弃
+    leader: Leader,                                    // 由哪个标签页发送定时消息, worker选出
+    shared_schedule: Vec<ScheduledNote>,               // 最近一次同步给其他标签页的定时消息
+    missed_checked: bool,                              // 第一次成为leader时检查错过的定时消息
+    subscribed: Option<(XOnlyPublicKey, Vec<XOnlyPublicKey>, Vec<XOnlyPublicKey>)>, // 当前订阅的账号, 联系人和分组成员
 }
 
 impl AppClient {
-    const MAX_RETRIES: i32 = 10;
-
-    async fn reconnect(
-        client: Rc<RefCell<Client>>,
-        cur_relays: Rc<RefCell<HashMap<usize, WsInfo>>>,
-        retry_connect: Callback<(Url, usize)>,
-        uri: Url,
-        id: usize,
-    ) {
-        if let Err(e) = client.borrow().connect_relay(uri.to_string()).await {
-            log::error!("connect {uri} error{e:?}");
-            if let nostr_sdk::client::Error::RelayPool(_re) = e {
-                // retry
-                if let Some(mut _info) = cur_relays.borrow_mut().get_mut(&id) {
-                    if _info.retry < Self::MAX_RETRIES {
-                        _info.retry += 1;
-                        retry_connect.emit((uri.clone(), id));
-                    }
-                } else {
-                    log::warn!("no such {uri}")
-                }
-            }
-        }
-    }
+    const NIP_SEARCH: u16 = 50;
+    // the prefix of the OK message refused without NIP-42 auth
+    const AUTH_REQUIRED: &'static str = "auth-required:";
+    // the events refused before the auth are given up if it doesn't finish in time
+    const AUTH_RETRY_TIMEOUT: u64 = 60;
+    const MAX_SEARCH_RESULTS: usize = 50;
+    const MAX_NOTIFICATIONS: usize = 100;
+    const MAX_GROUP_NOTES: usize = 200;
+    const MAX_CONTACT_FETCHES: usize = 3;
+    const CONTACT_FETCH_INTERVAL: u32 = 2_000;
+    const CONTACT_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
 
-    fn connect(&mut self, ctx: &Context<Self>) -> bool {
+    fn connect(&mut self) -> bool {
         let mut update = false;
         let all_relays = self.relay_handle.list.borrow();
         let remove_relays = self
@@ -85,17 +114,18 @@ impl AppClient {
         if !remove_relays.is_empty() {
             update = true;
             for (k, _r) in &remove_relays {
-                self.cur_relays.borrow_mut().remove(k);
+                if let Some(info) = self.cur_relays.borrow_mut().remove(k) {
+                    self.relay_docs.borrow_mut().remove(&info.uri);
+                    self.auth_retry.remove(&info.uri);
+                    self.relay_status_handle
+                        .dispatch(RelayStatusAction::Remove(info.uri));
+                }
             }
-            if let Some(client) = self.client.clone() {
-                spawn_local(async move {
-                    // disconnect old ones
-                    for (_, relay) in remove_relays {
-                        if let Err(e) = client.borrow().disconnect_relay(relay.uri).await {
-                            log::warn!("{e:?}");
-                        };
-                    }
-                });
+            if let Some(client) = &self.client {
+                // disconnect old ones
+                for (_, relay) in remove_relays {
+                    client.remove_relay(relay.uri);
+                }
             }
         }
         let news = all_relays
@@ -110,95 +140,728 @@ impl AppClient {
                     info.id,
                     WsInfo {
                         uri: info.uri.clone().unwrap(),
-                        retry: 0,
                     },
                 );
             }
-            if let Some(client) = self.client.clone() {
-                let relay_infos = self.cur_relays.clone();
-                let retry_connect = ctx
-                    .link()
-                    .callback(|(uri, id)| AppMsg::ReconnectWebsocket(uri, id));
-                spawn_local(async move {
-                    let client = client;
-                    let relay_infos = relay_infos;
-                    let retry_connect = retry_connect;
-                    for info in news {
-                        let uri = info.uri.as_ref().unwrap();
-                        if let Err(e) = client.borrow().add_relay(uri.to_string()).await {
-                            log::warn!("add {uri} error:{e:?}");
-                            continue;
-                        }
-                        Self::reconnect(
-                            client.clone(),
-                            relay_infos.clone(),
-                            retry_connect.clone(),
-                            uri.clone(),
-                            info.id,
-                        )
-                        .await
-                    }
-                });
-            }
-        }
-        if let Some(client) = self.client.clone() {
-            let callback = ctx
-                .link()
-                .callback(|notification| AppMsg::RelayPoolNotification(notification));
+            let relay_docs = self.relay_docs.clone();
+            let pow_handle = self.pow_handle.clone();
+            let doc_uris = news
+                .iter()
+                .map(|info| info.uri.clone().unwrap())
+                .collect::<Vec<_>>();
             spawn_local(async move {
-                loop {
-                    let mut notifications = client.borrow().notifications();
-
-                    while let Ok(notification) = notifications.recv().await {
-                        match notification {
-                            RelayPoolNotification::Shutdown => return, // exit the loop
-                            _ => callback.emit(notification),
+                for uri in doc_uris {
+                    match fetch_relay_document(&uri).await {
+                        Ok((doc, limitation)) => {
+                            if let Some(difficulty) = limitation.min_pow_difficulty {
+                                pow_handle.dispatch(PowAction::Learned(uri.clone(), difficulty));
+                            }
+                            relay_docs.borrow_mut().insert(uri, doc);
                         }
+                        Err(e) => log::warn!("fetch relay document of {uri} error:{e:?}"),
                     }
                 }
             });
+            if let Some(client) = &self.client {
+                for info in news {
+                    client.add_relay(info.uri.unwrap());
+                }
+            }
         }
         update
     }
 
-    fn subscribe_contacts(&self) {
+    // resubscribed only when the contacts or the group members change,
+    // not on the other changes of the contacts, e.g. the recent ones or the metadata
+    fn subscribe_contacts(&mut self) {
         if let Some(client) = &self.client {
-            let keys = client.borrow().keys();
+            let keys = client.keys();
             let client = client.clone();
+            let mut contact_pks = self
+                .user_contact_handle
+                .data
+                .borrow()
+                .values()
+                .map(|contact| contact.pubkey.unwrap())
+                .collect::<Vec<_>>();
+            contact_pks.sort();
+            let group_members = self.user_contact_handle.group_members();
+            let subscribed = (
+                keys.public_key(),
+                contact_pks.clone(),
+                group_members.clone(),
+            );
+            if self.subscribed.as_ref() == Some(&subscribed) {
+                return;
+            }
+            self.subscribed = Some(subscribed);
             let subscription = Filter::new().pubkey(keys.public_key());
             // .since(Timestamp::now());
             let subscription = subscription
-                .pubkeys(
-                    self.user_contact_handle
-                        .data
-                        .borrow()
-                        .values()
-                        .map(|contact| contact.pubkey.unwrap())
-                        .collect::<Vec<_>>(),
-                )
+                .pubkeys(contact_pks.clone())
                 .since(Timestamp::now());
+            // the profiles of contacts, for nip05 and avatar
+            let metadata = Filter::new().authors(contact_pks).kind(Kind::Metadata);
+            // mentions, replies, reactions and reposts of the user, including the history
+            let notification = Filter::new()
+                .pubkey(keys.public_key())
+                .kinds(vec![Kind::TextNote, Kind::Reaction, Kind::Repost])
+                .limit(Self::MAX_NOTIFICATIONS);
+            // the mute list synced from other devices
+            let mute = Filter::new()
+                .author(keys.public_key())
+                .kind(MUTE_LIST_KIND)
+                .limit(1);
+            // the contact groups, and the notes of their members for the group timelines
+            let follow_sets = Filter::new()
+                .author(keys.public_key())
+                .kind(FOLLOW_SET_KIND);
+            let mut filters = vec![subscription, metadata, notification, mute, follow_sets];
+            if !group_members.is_empty() {
+                filters.push(
+                    Filter::new()
+                        .authors(group_members)
+                        .kind(Kind::TextNote)
+                        .limit(Self::MAX_GROUP_NOTES),
+                );
+            }
+            client.subscribe(filters);
+            // the views show the stored events are loading until EOSE
+            for info in self.cur_relays.borrow().values() {
+                self.relay_status_handle
+                    .dispatch(RelayStatusAction::Loading(info.uri.clone()));
+            }
+        }
+    }
+
+    fn update_contact_metadata(&self, pk: XOnlyPublicKey, content: &str) {
+        let metadata = match Metadata::from_json(content) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                log::warn!("metadata of {pk} error:{e:?}");
+                return;
+            }
+        };
+        let info = self.user_contact_handle.data.borrow().get(&pk).cloned();
+        if let Some(mut info) = info {
+            let avatar_url = metadata.picture.or(info.avatar_url.clone());
+            if info.nip05 != metadata.nip05 || info.avatar_url != avatar_url {
+                info.nip05 = metadata.nip05;
+                info.avatar_url = avatar_url;
+                self.user_contact_handle
+                    .dispatch(UserContactAction::Update(info));
+            }
+        }
+    }
+
+    // NIP-42, the relays enabled by the user only
+    fn authenticate(&mut self, uri: Url, challenge: String) {
+        if !self.relay_handle.auth_enabled(&uri) {
+            log::info!("{uri} asks for the auth, but it isn't enabled");
+            self.relay_status_handle
+                .dispatch(RelayStatusAction::Auth(uri, AuthState::Required));
+            return;
+        }
+        let client = if let Some(client) = self.client.clone() {
+            client
+        } else {
+            return;
+        };
+        match EventBuilder::auth(challenge, uri.clone()).to_event(&client.keys()) {
+            Ok(event) => {
+                self.auth_events.insert(event.id, uri.clone());
+                self.relay_status_handle.dispatch(RelayStatusAction::Auth(
+                    uri.clone(),
+                    AuthState::Authenticating,
+                ));
+                spawn_local(async move {
+                    if let Err(e) = client
+                        .send_msg_to(uri.clone(), ClientMessage::new_auth(event))
+                        .await
+                    {
+                        log::warn!("send auth to {uri} error:{e:?}");
+                    }
+                });
+            }
+            Err(e) => log::error!("sign auth event error:{e:?}"),
+        }
+    }
+
+    fn auth_result(&mut self, uri: Url, ok: bool, message: String) {
+        let retry = self
+            .auth_retry
+            .remove(&uri)
+            .map(|(_, events)| events)
+            .unwrap_or_default();
+        if !ok {
+            log::warn!("auth of {uri} failed:{message}");
+            for event in retry {
+                self.user_event_handle
+                    .dispatch(UserEventAction::Visible(event.id, false));
+            }
+            self.relay_status_handle
+                .dispatch(RelayStatusAction::Auth(uri, AuthState::Failed(message)));
+            return;
+        }
+        self.relay_status_handle.dispatch(RelayStatusAction::Auth(
+            uri.clone(),
+            AuthState::Authenticated,
+        ));
+        if let Some(client) = self.client.clone() {
+            // the subscription was refused before the auth
+            client.resubscribe(uri.clone());
+            self.relay_status_handle
+                .dispatch(RelayStatusAction::Loading(uri.clone()));
             spawn_local(async move {
-                client.borrow().subscribe(vec![subscription]).await;
+                for event in retry {
+                    if let Err(e) = client
+                        .send_msg_to(uri.clone(), ClientMessage::new_event(event))
+                        .await
+                    {
+                        log::warn!("send again to {uri} error:{e:?}");
+                    }
+                }
             });
         }
     }
 
-    fn set_nostr_contacts(&self) -> bool {
-        let mut update = false;
-        let contacts = self
+    // keep the event refused with `auth-required:` while the auth of the relay is on the way
+    fn retry_after_auth(&mut self, uri: &Url, event_id: &EventId) -> bool {
+        if !self.relay_handle.auth_enabled(uri)
+            || self.relay_status_handle.auth(uri) != AuthState::Authenticating
+        {
+            return false;
+        }
+        match self.client.as_ref().and_then(|c| c.sent_event(event_id)) {
+            Some(event) => {
+                self.auth_retry
+                    .entry(uri.clone())
+                    .or_insert_with(|| (Timestamp::now(), vec![]))
+                    .1
+                    .push(event);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // no answer to the auth, the events waiting for it are refused
+    fn drop_auth_retry(&mut self, now: Timestamp) {
+        let timeout = self
+            .auth_retry
+            .iter()
+            .filter(|(_, (since, _))| *since + Self::AUTH_RETRY_TIMEOUT <= now)
+            .map(|(uri, _)| uri.clone())
+            .collect::<Vec<_>>();
+        for uri in timeout {
+            let events = self
+                .auth_retry
+                .remove(&uri)
+                .map(|(_, events)| events)
+                .unwrap_or_default();
+            log::warn!(
+                "auth of {uri} timed out, {} events aren't sent",
+                events.len()
+            );
+            for event in events {
+                self.user_event_handle
+                    .dispatch(UserEventAction::Visible(event.id, false));
+            }
+        }
+    }
+
+    // the other tabs mark the same notifications read
+    fn share_read(&mut self) {
+        let read = (
+            self.notification_handle.read_until,
+            self.notification_handle.read_ids.borrow().len(),
+        );
+        if read == self.shared_read {
+            return;
+        }
+        self.shared_read = read;
+        if let Some(client) = &self.client {
+            client.share(SharedState::Read(
+                read.0,
+                self.notification_handle
+                    .read_ids
+                    .borrow()
+                    .iter()
+                    .map(|(id, created_at)| (*id, *created_at))
+                    .collect(),
+            ));
+        }
+    }
+
+    fn is_notification(&self, event: &Event) -> bool {
+        let me = if let Some(client) = &self.client {
+            client.keys().public_key()
+        } else {
+            return false;
+        };
+        event.pubkey != me
+            && NotificationKind::classify(event).is_some()
+            && event
+                .tags
+                .iter()
+                .any(|t| matches!(t, Tag::PubKey(pk, _) if *pk == me))
+    }
+
+    fn notify(&self, event: Event) {
+        if tauri::is_tauri() && event.created_at.as_u64() >= self.started_at.as_u64() {
+            if let Some((kind, _)) = NotificationKind::classify(&event) {
+                let body = match kind {
+                    NotificationKind::Mention => rust_i18n::t!("mentioned you"),
+                    NotificationKind::Reply => rust_i18n::t!("replied to your note"),
+                    NotificationKind::Reaction => rust_i18n::t!("reacted to your note"),
+                    NotificationKind::Repost => rust_i18n::t!("reposted your note"),
+                };
+                let author = front_n_chars(event.pubkey.to_string(), 12);
+                tauri::send_notification(&author, &format!("{body}: {}", event.content));
+            }
+        }
+        self.notification_handle
+            .dispatch(UserNotificationAction::Add(event));
+    }
+
+    fn is_my_mute_list(&self, event: &Event) -> bool {
+        event.kind == MUTE_LIST_KIND
+            && self
+                .client
+                .as_ref()
+                .map(|client| client.keys().public_key() == event.pubkey)
+                .unwrap_or(false)
+    }
+
+    fn is_my_follow_set(&self, event: &Event) -> bool {
+        event.kind == FOLLOW_SET_KIND
+            && self
+                .client
+                .as_ref()
+                .map(|client| client.keys().public_key() == event.pubkey)
+                .unwrap_or(false)
+    }
+
+    fn update_group(&self, event: &Event) {
+        let name = event.tags.iter().find_map(|t| match t {
+            Tag::Identifier(name) => Some(name.clone()),
+            _ => None,
+        });
+        if let Some(name) = name.filter(|name| !name.is_empty()) {
+            let members = event
+                .tags
+                .iter()
+                .filter_map(|t| match t {
+                    Tag::PubKey(pk, _) => Some(*pk),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            self.user_contact_handle
+                .dispatch(UserContactAction::RemoteGroup(
+                    name,
+                    members,
+                    event.created_at.as_u64(),
+                ));
+        }
+    }
+
+    // publish the follow sets changed on this device
+    fn publish_groups(&self) {
+        let client = if let Some(client) = self.client.clone() {
+            client
+        } else {
+            return;
+        };
+        let keys = client.keys();
+        let events = self
             .user_contact_handle
-            .data
+            .dirty_groups
+            .borrow_mut()
+            .drain()
+            .filter_map(
+                |name| match self.user_contact_handle.follow_set_event(&name, &keys) {
+                    Ok(event) => Some(event),
+                    Err(e) => {
+                        log::warn!("make follow set {name} error:{e:?}");
+                        None
+                    }
+                },
+            )
+            .collect::<Vec<_>>();
+        if events.is_empty() {
+            return;
+        }
+        spawn_local(async move {
+            for event in events {
+                if let Err(e) = client.send_event(event).await {
+                    log::warn!("publish follow set error:{e:?}");
+                }
+            }
+        });
+    }
+
+    fn update_mute_list(&self, event: &Event) {
+        if let Some(client) = &self.client {
+            let (entries, decrypted) = UserMute::entries_of_event(event, &client.keys());
+            self.mute_handle.dispatch(UserMuteAction::Remote(
+                event.pubkey,
+                entries,
+                event.created_at.as_u64(),
+                decrypted,
+            ));
+        }
+    }
+
+    fn publish_mute_list(&self) {
+        if let Some(client) = self.client.clone() {
+            let event = match self.mute_handle.to_event(&client.keys()) {
+                Ok(event) => event,
+                Err(e) => {
+                    log::warn!("make mute list error:{e:?}");
+                    return;
+                }
+            };
+            spawn_local(async move {
+                if let Err(e) = client.send_event(event).await {
+                    log::warn!("publish mute list error:{e:?}");
+                }
+            });
+        }
+    }
+
+    // remove the events received before they were muted
+    fn hide_muted(&self) {
+        let muted = self
+            .user_event_handle
+            .events
+            .borrow()
+            .values()
+            .filter(|msg| self.mute_handle.is_muted(&msg.event))
+            .map(|msg| msg.event.id)
+            .collect::<HashSet<_>>();
+        if !muted.is_empty() {
+            self.user_event_handle
+                .dispatch(UserEventAction::RemoveBatch(muted));
+        }
+        let muted = self
+            .notification_handle
+            .groups
+            .borrow()
+            .values()
+            .flat_map(|group| group.events.iter())
+            .filter(|e| self.mute_handle.is_muted(e))
+            .map(|e| e.id)
+            .collect::<HashSet<_>>();
+        if !muted.is_empty() {
+            self.notification_handle
+                .dispatch(UserNotificationAction::RemoveEvents(muted));
+        }
+    }
+
+    // NIP-40, the expired events are dropped even if relays keep serving them
+    fn drop_expired(&self, now: Timestamp) {
+        let expired = self
+            .user_event_handle
+            .events
             .borrow()
             .values()
-            .map(|contact| Contact {
-                pk: contact.pubkey.unwrap(),
-                relay_url: contact.relay.as_ref().map(|u| u.to_string()),
-                alias: contact.nickname.clone(),
+            .filter(|msg| is_expired(&msg.event, now))
+            .map(|msg| msg.event.id)
+            .collect::<HashSet<_>>();
+        if !expired.is_empty() {
+            self.user_event_handle
+                .dispatch(UserEventAction::RemoveBatch(expired));
+        }
+        let expired = self
+            .notification_handle
+            .groups
+            .borrow()
+            .values()
+            .flat_map(|group| group.events.iter())
+            .filter(|e| is_expired(e, now))
+            .map(|e| e.id)
+            .collect::<HashSet<_>>();
+        if !expired.is_empty() {
+            self.notification_handle
+                .dispatch(UserNotificationAction::RemoveEvents(expired));
+        }
+        let expired = self
+            .search_handle
+            .hits
+            .borrow()
+            .values()
+            .filter(|hit| is_expired(&hit.event, now))
+            .map(|hit| hit.event.id)
+            .collect::<HashSet<_>>();
+        if !expired.is_empty() {
+            self.search_handle
+                .dispatch(UserSearchAction::Remove(expired));
+        }
+    }
+
+    // the notes scheduled before a time the app wasn't running, checked once by the first leader,
+    // a tab opened later would mark the ones the leader is about to publish
+    fn mark_missed(&mut self) {
+        if !self.leader.all || self.missed_checked {
+            return;
+        }
+        self.missed_checked = true;
+        if self
+            .schedule_handle
+            .notes
+            .borrow()
+            .iter()
+            .any(|note| note.sending)
+        {
+            self.schedule_handle.dispatch(ScheduleAction::Interrupted);
+        }
+        if !self.schedule_handle.review_missed {
+            return;
+        }
+        let missed = self
+            .schedule_handle
+            .notes
+            .borrow()
+            .iter()
+            .filter(|note| note.publish_at < self.started_at)
+            .map(|note| note.id)
+            .collect::<HashSet<_>>();
+        if !missed.is_empty() {
+            self.schedule_handle
+                .dispatch(ScheduleAction::Missed(missed));
+        }
+    }
+
+    // the other tabs see the notes queued, edited or published in this one
+    fn share_schedule(&mut self) {
+        let notes = self.schedule_handle.notes.borrow().clone();
+        if notes == self.shared_schedule {
+            return;
+        }
+        self.shared_schedule = notes.clone();
+        if let Some(client) = &self.client {
+            client.share(SharedState::Schedule(notes));
+        }
+    }
+
+    // publish the scheduled notes that are due, the ones of other accounts
+    // are published if they were signed when they were queued.
+    // only by one tab: the signed ones by the leader of all the tabs, the others by the leader of their account
+    fn publish_scheduled(&self) {
+        let client = if let Some(client) = self.client.clone() {
+            client
+        } else {
+            return;
+        };
+        let keys = client.keys();
+        let now = Timestamp::now();
+        let due = self
+            .schedule_handle
+            .notes
+            .borrow()
+            .iter()
+            .filter(|note| note.is_due(now))
+            .filter(|note| {
+                if note.signed.is_some() {
+                    self.leader.all
+                } else {
+                    self.leader.account
+                }
+            })
+            .filter_map(|note| note.event(&keys).map(|event| (note.clone(), event)))
+            .collect::<Vec<_>>();
+        for (note, event) in due {
+            // not sent again by the next tick, and still queued if the app is closed now
+            self.schedule_handle
+                .dispatch(ScheduleAction::Sending(note.id));
+            let client = client.clone();
+            let schedule_handle = self.schedule_handle.clone();
+            let user_event_handle = self.user_event_handle.clone();
+            spawn_local(async move {
+                match client.send_event(event.clone()).await {
+                    Ok(_) => {
+                        schedule_handle.dispatch(ScheduleAction::Published(note.id));
+                        tauri::send_notification(
+                            &rust_i18n::t!("scheduled note published"),
+                            &front_n_chars(&event.content, 80),
+                        );
+                        user_event_handle.dispatch(UserEventAction::Add(event));
+                    }
+                    Err(e) => {
+                        log::warn!("publish scheduled note error:{e:?}");
+                        schedule_handle.dispatch(ScheduleAction::Failed(note, e));
+                    }
+                }
+            });
+        }
+    }
+
+    // send the NIP-50 search request to the relays that support it
+    fn search(&mut self) {
+        let sub_id = self.search_handle.sub_id.clone();
+        if sub_id == self.search_sub_id {
+            return;
+        }
+        let old_sub_id = self.search_sub_id.take();
+        self.search_sub_id = sub_id.clone();
+        self.search_event_ids.clear();
+        let client = if let Some(client) = self.client.clone() {
+            client
+        } else {
+            return;
+        };
+        let search_relays = self
+            .relay_docs
+            .borrow()
+            .iter()
+            .filter(|(_, doc)| {
+                doc.supported_nips
+                    .as_ref()
+                    .map(|nips| nips.contains(&Self::NIP_SEARCH))
+                    .unwrap_or(false)
             })
+            .map(|(uri, _)| uri.clone())
             .collect::<Vec<_>>();
+        let query = self.search_handle.query.clone();
+        spawn_local(async move {
+            for uri in search_relays {
+                if let Some(old) = old_sub_id.clone() {
+                    if let Err(e) = client
+                        .send_msg_to(uri.clone(), ClientMessage::close(old))
+                        .await
+                    {
+                        log::warn!("close search on {uri} error:{e:?}");
+                    }
+                }
+                if let Some(sub_id) = sub_id.clone() {
+                    let filter = Filter::new()
+                        .kind(Kind::TextNote)
+                        .search(query.as_str())
+                        .limit(Self::MAX_SEARCH_RESULTS);
+                    if let Err(e) = client
+                        .send_msg_to(uri.clone(), ClientMessage::new_req(sub_id, vec![filter]))
+                        .await
+                    {
+                        log::warn!("search on {uri} error:{e:?}");
+                    }
+                }
+            }
+        });
+    }
+
+    // copy the follows of someone into the import preview
+    fn fetch_follows(&mut self) {
+        let pk = match &self.user_contact_handle.import {
+            Some(ContactImport::Fetching(pk)) => *pk,
+            _ => {
+                self.fetching_follows = None;
+                return;
+            }
+        };
+        if self.fetching_follows == Some(pk) {
+            return;
+        }
+        let client = if let Some(client) = self.client.clone() {
+            client
+        } else {
+            return;
+        };
+        self.fetching_follows = Some(pk);
+        let user_contact_handle = self.user_contact_handle.clone();
+        spawn_local(async move {
+            let filter = Filter::new().author(pk).kind(Kind::ContactList).limit(1);
+            match client
+                .get_events_of(vec![filter], Self::CONTACT_FETCH_TIMEOUT)
+                .await
+            {
+                Ok(events) => {
+                    let contacts = events
+                        .into_iter()
+                        .max_by_key(|e| e.created_at.as_u64())
+                        .map(UserContact::contacts_of_event)
+                        .unwrap_or_default();
+                    user_contact_handle.dispatch(UserContactAction::FollowsFetched(
+                        pk,
+                        contacts.iter().map(UserContactInfo::from).collect(),
+                    ));
+                }
+                Err(e) => {
+                    log::warn!("fetch follows of {pk} error:{e:?}");
+                    user_contact_handle.dispatch(UserContactAction::FollowsFailed(pk, e));
+                }
+            }
+        });
+    }
+
+    // fetch the latest kind 3 of the user before publishing anything
+    fn fetch_nostr_contacts(&mut self) {
+        let client = if let Some(client) = self.client.clone() {
+            client
+        } else {
+            return;
+        };
+        let me = client.keys().public_key();
+        if self.contact_sync_pk == Some(me) {
+            return;
+        }
+        self.contact_sync_pk = Some(me);
+        let user_contact_handle = self.user_contact_handle.clone();
+        spawn_local(async move {
+            let filter = Filter::new().author(me).kind(Kind::ContactList).limit(1);
+            // whether a relay answered with EOSE, a timeout or no relay isn't an empty list
+            let mut answered = false;
+            for _ in 0..Self::MAX_CONTACT_FETCHES {
+                // wait for the relays connecting
+                TimeoutFuture::new(Self::CONTACT_FETCH_INTERVAL).await;
+                let events = client
+                    .get_events_of(vec![filter.clone()], Self::CONTACT_FETCH_TIMEOUT)
+                    .await;
+                match events {
+                    Ok(events) => {
+                        answered = true;
+                        if let Some(latest) =
+                            events.into_iter().max_by_key(|e| e.created_at.as_u64())
+                        {
+                            let contacts = UserContact::contacts_of_event(latest);
+                            user_contact_handle.dispatch(UserContactAction::Remote(contacts));
+                            return;
+                        }
+                    }
+                    Err(e) => log::warn!("fetch contact list error:{e:?}"),
+                }
+            }
+            if answered {
+                user_contact_handle.dispatch(UserContactAction::Remote(vec![]));
+            } else {
+                user_contact_handle.dispatch(UserContactAction::RemoteFailed);
+            }
+        });
+    }
+
+    fn set_nostr_contacts(&mut self) -> bool {
+        if self.user_contact_handle.sync != ContactSync::Synced {
+            log::debug!("contact list isn't synced, no publish");
+            return false;
+        }
+        let contacts = self.user_contact_handle.nostr_contacts();
+        match self.published_contacts.as_ref() {
+            Some(published) if published == &contacts => return false,
+            None if contacts.is_empty() => return false,
+            _ => {}
+        }
+        let mut update = false;
         if let Some(client) = self.client.clone() {
+            let event =
+                match EventBuilder::set_contact_list(contacts.clone()).to_event(&client.keys()) {
+                    Ok(event) => event,
+                    Err(e) => {
+                        log::warn!("make contact list error:{e:?}");
+                        return false;
+                    }
+                };
+            self.published_contacts = Some(contacts);
             spawn_local(async move {
-                if let Err(e) = client.borrow().set_contact_list(contacts).await {
+                if let Err(e) = client.send_event(event).await {
                     log::warn!("{e:?}");
                 }
             });
@@ -236,6 +899,34 @@ impl Component for AppClient {
             .link()
             .context(ctx.link().callback(AppMsg::RelayCtx))
             .expect("No UserEventCtx Context Provided");
+        let (search_handle, _search_listener) = ctx
+            .link()
+            .context(ctx.link().callback(AppMsg::SearchCtx))
+            .expect("No UserSearchContext Provided");
+        let (notification_handle, _notification_listener) = ctx
+            .link()
+            .context(ctx.link().callback(AppMsg::NotificationCtx))
+            .expect("No UserNotificationContext Provided");
+        let (mute_handle, _mute_listener) = ctx
+            .link()
+            .context(ctx.link().callback(AppMsg::MuteCtx))
+            .expect("No UserMuteContext Provided");
+        let (pow_handle, _pow_listener) = ctx
+            .link()
+            .context(ctx.link().callback(AppMsg::PowCtx))
+            .expect("No PowContext Provided");
+        let (relay_status_handle, _relay_status_listener) = ctx
+            .link()
+            .context(ctx.link().callback(AppMsg::RelayStatusCtx))
+            .expect("No RelayStatusContext Provided");
+        let (_, _clock_listener) = ctx
+            .link()
+            .context(ctx.link().callback(AppMsg::ClockCtx))
+            .expect("No ClockContext Provided");
+        let (schedule_handle, _schedule_listener) = ctx
+            .link()
+            .context(ctx.link().callback(AppMsg::ScheduleCtx))
+            .expect("No ScheduleContext Provided");
         let mut _self = Self {
             user_event_handle,
             user_contact_listener,
@@ -249,23 +940,39 @@ impl Component for AppClient {
             _user_handle_listener,
             relay_handle,
             cur_relays: Rc::new(RefCell::new(HashMap::new())),
+            search_handle,
+            _search_listener,
+            search_sub_id: None,
+            search_event_ids: HashSet::new(),
+            relay_docs: Rc::new(RefCell::new(HashMap::new())),
+            notification_handle,
+            shared_read: (0, 0),
+            _notification_listener,
+            started_at: Timestamp::now(),
+            contact_sync_pk: None,
+            published_contacts: None,
+            fetching_follows: None,
+            mute_handle,
+            _mute_listener,
+            pow_handle,
+            _pow_listener,
+            relay_status_handle,
+            _relay_status_listener,
+            _clock_listener,
+            schedule_handle,
+            _schedule_listener,
+            auth_events: HashMap::new(),
+            auth_retry: HashMap::new(),
+            leader: Leader::default(),
+            shared_schedule: vec![],
+            missed_checked: false,
+            subscribed: None,
         };
         _self
     }
     fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
         let mut _update = false;
         match msg {
-            AppMsg::ReconnectWebsocket(uri, id) => {
-                if let Some(client) = self.client.clone() {
-                    let relay_infos = self.cur_relays.clone();
-                    let retry_connect = ctx
-                        .link()
-                        .callback(|(uri, id)| AppMsg::ReconnectWebsocket(uri, id));
-                    spawn_local(async move {
-                        Self::reconnect(client, relay_infos, retry_connect, uri, id).await
-                    });
-                }
-            }
             AppMsg::SendMsgEventCtx(msg_ctx) => {
                 if let Some(_msg) = &msg_ctx.msg {
                     // _update = self.handle_client_msg(ctx, msg);
@@ -273,10 +980,23 @@ impl Component for AppClient {
                         let client = client.clone();
                         let msg = _msg.clone();
                         let user_event_handle = self.user_event_handle.clone();
+                        let user_contact_handle = self.user_contact_handle.clone();
                         spawn_local(async move {
-                            if let Err(e) = client.borrow().send_msg(msg.clone()).await {
+                            if let Err(e) = client.send_msg(msg.clone()).await {
                                 log::debug!("{e:?}");
                             } else if let ClientMessage::Event(event) = msg {
+                                // the contacts messaged, replied to or mentioned, in one dispatch
+                                let pks = event
+                                    .tags
+                                    .iter()
+                                    .filter_map(|tag| match tag {
+                                        Tag::PubKey(pk, _) => Some(*pk),
+                                        _ => None,
+                                    })
+                                    .collect::<Vec<_>>();
+                                if !pks.is_empty() {
+                                    user_contact_handle.dispatch(UserContactAction::Recent(pks));
+                                }
                                 user_event_handle.dispatch(UserEventAction::Add(*event.clone()));
                             }
                         })
@@ -289,18 +1009,50 @@ impl Component for AppClient {
                 if let Some(keys) = user_ctx.keys.clone() {
                     if self.client.is_some() {
                         // disconnect old
-                        if self.client.as_ref().unwrap().borrow().keys() != keys {
-                            let client = self.client.take().unwrap();
+                        if self.client.as_ref().unwrap().keys() != keys {
+                            // the worker of the old one is terminated when it's dropped
+                            self.client = None;
                             log::warn!("disconnect old connections");
-                            spawn_local(async move {
-                                client.borrow().disconnect().await.unwrap();
-                            });
+                            self.cur_relays.borrow_mut().clear();
+                            self.published_contacts = None;
+                            self.auth_events.clear();
+                            self.auth_retry.clear();
+                            self.leader = Leader::default();
+                            self.relay_status_handle.dispatch(RelayStatusAction::Clear);
+                            self.notification_handle
+                                .dispatch(UserNotificationAction::Clear);
+                            self.user_contact_handle
+                                .dispatch(UserContactAction::ResetSync);
                         }
                     }
-                    // make new client
-                    self.client = Some(Rc::new(RefCell::new(Client::new(&keys))));
-                    _update = self.connect(ctx);
-                    self.set_nostr_contacts();
+                    // the mute list of the account logged in before isn't kept, nor published with these keys
+                    self.mute_handle
+                        .dispatch(UserMuteAction::Owner(keys.public_key()));
+                    if self.client.is_none() {
+                        // make new client
+                        let callback = ctx.link().callback(|output| match output {
+                            WorkerOutput::Event(uri, event) => AppMsg::RelayPoolNotification(
+                                RelayPoolNotification::Event(uri, event),
+                            ),
+                            WorkerOutput::Message(uri, msg) => AppMsg::RelayPoolNotification(
+                                RelayPoolNotification::Message(uri, msg),
+                            ),
+                            WorkerOutput::Shared(state) => AppMsg::Shared(state),
+                            WorkerOutput::Live(uri) => AppMsg::Live(uri),
+                            WorkerOutput::Leader(leader) => AppMsg::Leader(leader),
+                            _ => AppMsg::Noop,
+                        });
+                        match RelayPool::new(&keys, callback) {
+                            Ok(client) => {
+                                self.client = Some(Rc::new(client));
+                                // the new worker has no subscription
+                                self.subscribed = None;
+                                _update = self.connect();
+                            }
+                            Err(e) => log::error!("start relay pool worker error:{e:?}"),
+                        }
+                    }
+                    self.fetch_nostr_contacts();
                     self.subscribe_contacts();
                 } else {
                     log::warn!("no user keys");
@@ -308,15 +1060,47 @@ impl Component for AppClient {
             }
             AppMsg::RelayPoolNotification(notification) => {
                 match notification {
-                    RelayPoolNotification::Message(_, rmsg) => {
-                        if let RelayMessage::Ok {
-                            event_id,
-                            status,
-                            message: _,
-                        } = rmsg
-                        {
-                            self.user_event_handle
-                                .dispatch(UserEventAction::Visible(event_id, status));
+                    RelayPoolNotification::Message(uri, rmsg) => {
+                        match rmsg {
+                            RelayMessage::Ok {
+                                event_id,
+                                status,
+                                message,
+                            } => {
+                                if let Some(auth_uri) = self.auth_events.remove(&event_id) {
+                                    self.auth_result(auth_uri, status, message);
+                                } else if !status
+                                    && message.starts_with(Self::AUTH_REQUIRED)
+                                    && self.retry_after_auth(&uri, &event_id)
+                                {
+                                    log::info!("{event_id} is sent again after the auth of {uri}");
+                                } else {
+                                    self.user_event_handle
+                                        .dispatch(UserEventAction::Visible(event_id, status));
+                                }
+                            }
+                            RelayMessage::Auth { challenge } => self.authenticate(uri, challenge),
+                            RelayMessage::Notice { message } => {
+                                // only shown, a NOTICE isn't about a subscription
+                                log::info!("notice from {uri}: {message}");
+                                self.relay_status_handle
+                                    .dispatch(RelayStatusAction::Notice(uri, message));
+                            }
+                            RelayMessage::Event {
+                                subscription_id,
+                                event,
+                            } if Some(&subscription_id) == self.search_sub_id.as_ref() => {
+                                self.search_event_ids.insert(event.id);
+                                if !self.mute_handle.is_muted(&event)
+                                    && !is_expired(&event, Timestamp::now())
+                                {
+                                    self.search_handle.dispatch(UserSearchAction::Hits(
+                                        SearchSource::Relay(uri),
+                                        vec![*event],
+                                    ));
+                                }
+                            }
+                            _ => {}
                         }
                         // if let Some(msg) = &self.send_msg_handler.msg {
                         //     log::debug!("{msg:?}");
@@ -324,7 +1108,36 @@ impl Component for AppClient {
                     }
 
                     RelayPoolNotification::Event(_, event) => {
-                        if event.kind == Kind::TextNote {
+                        if self.search_event_ids.contains(&event.id) {
+                            log::debug!("search result {}", event.id);
+                        } else if self.is_my_mute_list(&event) {
+                            self.update_mute_list(&event);
+                        } else if self.is_my_follow_set(&event) {
+                            self.update_group(&event);
+                        } else if is_expired(&event, Timestamp::now()) {
+                            log::debug!("expired {}", event.id);
+                        } else if self.mute_handle.is_muted(&event) {
+                            // the filter stage before the events, notifications and contacts
+                            log::debug!("muted {}", event.id);
+                        } else if event.kind == Kind::TextNote && !self.pow_handle.is_enough(&event)
+                        {
+                            log::debug!("not enough pow {}", event.id);
+                        } else if self.is_notification(&event) {
+                            // a note of a contact mentioning me is in the timeline too
+                            if event.kind == Kind::TextNote
+                                && self
+                                    .user_contact_handle
+                                    .data
+                                    .borrow()
+                                    .contains_key(&event.pubkey)
+                            {
+                                self.user_event_handle
+                                    .dispatch(UserEventAction::AddVisible(event.clone()));
+                            }
+                            self.notify(event);
+                        } else if event.kind == Kind::Metadata {
+                            self.update_contact_metadata(event.pubkey, &event.content);
+                        } else if event.kind == Kind::TextNote {
                             self.user_event_handle
                                 .dispatch(UserEventAction::AddVisible(event));
                         } else {
@@ -336,11 +1149,93 @@ impl Component for AppClient {
                     } //todo
                 }
             }
-            AppMsg::RelayCtx(_) => _update = self.connect(ctx),
-            AppMsg::UserContactCtx(_) => {
+            AppMsg::RelayCtx(_) => _update = self.connect(),
+            AppMsg::UserContactCtx(user_contact_handle) => {
+                let synced = self.user_contact_handle.sync == ContactSync::Synced;
+                let failed = self.user_contact_handle.sync == ContactSync::Failed;
+                let preview = match &self.user_contact_handle.sync {
+                    ContactSync::Preview(preview) => Some(preview.clone()),
+                    _ => None,
+                };
+                self.user_contact_handle = user_contact_handle;
+                if !synced && self.user_contact_handle.sync == ContactSync::Synced {
+                    // what is on relays now, only publish the changes made from here on.
+                    // without a preview there is no diff or nothing on relays, publish the local ones once
+                    self.published_contacts = preview.map(|preview| {
+                        preview
+                            .remote
+                            .iter()
+                            .map(|info| Contact {
+                                pk: info.pubkey.unwrap(),
+                                relay_url: info.relay.as_ref().map(|u| u.to_string()),
+                                alias: info.nickname.clone(),
+                            })
+                            .collect()
+                    });
+                }
+                if failed && self.user_contact_handle.sync == ContactSync::Pending {
+                    // retried by the user
+                    self.contact_sync_pk = None;
+                    self.fetch_nostr_contacts();
+                }
                 self.subscribe_contacts();
+                self.publish_groups();
+                self.fetch_follows();
                 _update = self.set_nostr_contacts();
             }
+            AppMsg::NotificationCtx(notification_handle) => {
+                self.notification_handle = notification_handle;
+                self.share_read();
+            }
+            AppMsg::PowCtx(pow_handle) => self.pow_handle = pow_handle,
+            AppMsg::Live(uri) => {
+                self.relay_status_handle
+                    .dispatch(RelayStatusAction::Live(uri));
+            }
+            AppMsg::RelayStatusCtx(relay_status_handle) => {
+                self.relay_status_handle = relay_status_handle
+            }
+            AppMsg::Shared(SharedState::Read(read_until, read_ids)) => {
+                let until = read_until.max(self.notification_handle.read_until);
+                let mut ids = self.notification_handle.read_ids.borrow().clone();
+                ids.extend(read_ids);
+                ids.retain(|_, created_at| *created_at > until);
+                // the same state after the dispatch, not shared back
+                self.shared_read = (until, ids.len());
+                self.notification_handle
+                    .dispatch(UserNotificationAction::Synced(read_until, ids));
+            }
+            AppMsg::ClockCtx(clock) => {
+                self.drop_expired(clock.now);
+                self.drop_auth_retry(clock.now);
+                self.publish_scheduled();
+            }
+            AppMsg::ScheduleCtx(schedule_handle) => {
+                self.schedule_handle = schedule_handle;
+                self.share_schedule();
+                self.publish_scheduled();
+            }
+            AppMsg::Shared(SharedState::Schedule(notes)) => {
+                // the same queue after the dispatch, not shared back
+                self.shared_schedule = notes.clone();
+                self.schedule_handle.dispatch(ScheduleAction::Synced(notes));
+            }
+            AppMsg::Leader(leader) => {
+                self.leader = leader;
+                self.mark_missed();
+                self.publish_scheduled();
+            }
+            AppMsg::MuteCtx(mute_handle) => {
+                self.mute_handle = mute_handle;
+                if self.mute_handle.publish {
+                    self.publish_mute_list();
+                }
+                self.hide_muted();
+            }
+            AppMsg::SearchCtx(search_handle) => {
+                self.search_handle = search_handle;
+                self.search();
+            }
             _ => {
                 log::info!("default {:?}", msg);
             }
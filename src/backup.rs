@@ -0,0 +1,524 @@
+// 全部应用状态的备份与恢复: 带版本号的文件, 可以用口令加密
+use crate::ctxs::*;
+
+use nostr_sdk::nostr::hashes::{sha256, Hash, HashEngine, Hmac, HmacEngine};
+use nostr_sdk::nostr::nips::nip04;
+use nostr_sdk::nostr::secp256k1::{rand, SecretKey};
+use nostr_sdk::nostr::{Event, Keys, Timestamp};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+// 2: the key derivation and a MAC of the encrypted payload are recorded
+// 3: every store with its schema version, the older files have the stores of the time as fields
+pub const BACKUP_VERSION: u32 = 3;
+const KDF_PBKDF2_SHA256: &str = "pbkdf2-hmac-sha256";
+const KDF_ROUNDS: u32 = 100_000;
+// more would hang the page when the file is opened
+const MAX_KDF_ROUNDS: u32 = 10_000_000;
+
+// the stores of the app, the ones missing in an older backup are None
+#[derive(Debug, Clone, Default)]
+pub struct Backup {
+    pub user: User,
+    pub contacts: UserContact,
+    pub relays: Relay,
+    pub mute: UserMute,
+    pub notifications: UserNotification,
+    pub locale: Option<I18nLocale>,
+    pub theme: Option<Theme>,
+    pub pow: Option<Pow>,
+    pub media_server: Option<MediaServer>,
+    pub content_warnings: Option<ContentWarnings>,
+    pub drafts: Option<Drafts>,
+    pub schedule: Option<Schedule>,
+    pub events: Vec<Event>,    // the cached events, optional
+    pub invalid_events: usize, // dropped when importing
+}
+
+// every store as `LoadStore` keeps it, keyed by `load_store_key()`, migrated when it's opened
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Payload {
+    #[serde(default)]
+    stores: BTreeMap<String, Value>,
+    #[serde(default)]
+    events: Vec<Event>,
+}
+
+impl Payload {
+    fn insert<T: LoadStoreKey + Serialize>(&mut self, store: &T) -> anyhow::Result<()> {
+        self.stores
+            .insert(T::load_store_key().to_owned(), encode_store(store)?);
+        Ok(())
+    }
+
+    fn get<T>(&self) -> anyhow::Result<Option<T>>
+    where
+        T: LoadStoreKey,
+        for<'de> T: Deserialize<'de>,
+    {
+        let key = T::load_store_key();
+        self.stores
+            .get(key)
+            .map(|value| decode_store(value.clone()).map_err(|e| anyhow::anyhow!("{key}: {e}")))
+            .transpose()
+    }
+
+    // before version 3: {"user": .., "contacts": .., ..}, with the layouts of the schema version 1
+    fn from_fields(mut value: Value) -> Self {
+        let mut payload = Self {
+            events: value
+                .get_mut("events")
+                .map(Value::take)
+                .and_then(|events| serde_json::from_value(events).ok())
+                .unwrap_or_default(),
+            ..Self::default()
+        };
+        let fields = [
+            ("user", User::load_store_key()),
+            ("contacts", UserContact::load_store_key()),
+            ("relays", Relay::load_store_key()),
+            ("mute", UserMute::load_store_key()),
+            ("notifications", UserNotification::load_store_key()),
+        ];
+        for (field, key) in fields {
+            if let Some(data) = value.get_mut(field).map(Value::take) {
+                payload.stores.insert(
+                    key.to_owned(),
+                    serde_json::json!({ "version": 1, "data": data }),
+                );
+            }
+        }
+        payload
+    }
+}
+
+// how the key is derived from the passphrase, the version 1 files have the default
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Kdf {
+    pub name: String,
+    pub rounds: u32,
+}
+
+impl Default for Kdf {
+    fn default() -> Self {
+        Self {
+            name: KDF_PBKDF2_SHA256.to_owned(),
+            rounds: KDF_ROUNDS,
+        }
+    }
+}
+
+// the payload is the json of `Backup`, or its ciphertext when `salt` is set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupFile {
+    pub version: u32,
+    pub created_at: u64,
+    #[serde(default)]
+    pub salt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kdf: Option<Kdf>,
+    // HMAC-SHA256 of the ciphertext, checked before decrypting
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
+    pub payload: String,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> anyhow::Result<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        anyhow::bail!("invalid hex length");
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| Ok(u8::from_str_radix(&hex[i..i + 2], 16)?))
+        .collect()
+}
+
+fn hmac_sha256(key: &[u8], data: &[&[u8]]) -> [u8; 32] {
+    let mut engine = HmacEngine::<sha256::Hash>::new(key);
+    for d in data {
+        engine.input(d);
+    }
+    Hmac::<sha256::Hash>::from_engine(engine).to_byte_array()
+}
+
+// PBKDF2-HMAC-SHA256 (RFC 8018)
+fn pbkdf2_sha256(password: &[u8], salt: &[u8], rounds: u32, out: &mut [u8]) {
+    for (i, chunk) in out.chunks_mut(32).enumerate() {
+        let index = (i as u32 + 1).to_be_bytes();
+        let mut u = hmac_sha256(password, &[salt, &index]);
+        let mut block = u;
+        for _ in 1..rounds {
+            u = hmac_sha256(password, &[&u]);
+            for (b, v) in block.iter_mut().zip(u.iter()) {
+                *b ^= v;
+            }
+        }
+        chunk.copy_from_slice(&block[..chunk.len()]);
+    }
+}
+
+// the payload is nip04 encrypted to the first half of the derived key, the second half is for the MAC
+fn passphrase_keys(passphrase: &str, salt: &[u8], kdf: &Kdf) -> anyhow::Result<(Keys, [u8; 32])> {
+    if kdf.name != KDF_PBKDF2_SHA256 {
+        anyhow::bail!("unsupported key derivation {}", kdf.name);
+    }
+    if kdf.rounds == 0 || kdf.rounds > MAX_KDF_ROUNDS {
+        anyhow::bail!("unsupported key derivation rounds {}", kdf.rounds);
+    }
+    let mut key = [0u8; 64];
+    pbkdf2_sha256(passphrase.as_bytes(), salt, kdf.rounds, &mut key);
+    let sk = SecretKey::from_slice(&key[..32])?;
+    let mut mac_key = [0u8; 32];
+    mac_key.copy_from_slice(&key[32..]);
+    Ok((Keys::new(sk), mac_key))
+}
+
+impl Backup {
+    pub fn pubkey(&self) -> Option<String> {
+        self.user
+            .keys
+            .as_ref()
+            .map(|keys| keys.public_key().to_string())
+    }
+
+    fn to_payload(&self) -> anyhow::Result<Payload> {
+        let mut payload = Payload {
+            events: self.events.clone(),
+            ..Payload::default()
+        };
+        payload.insert(&self.user)?;
+        payload.insert(&self.contacts)?;
+        payload.insert(&self.relays)?;
+        payload.insert(&self.mute)?;
+        payload.insert(&self.notifications)?;
+        if let Some(locale) = &self.locale {
+            payload.insert(locale)?;
+        }
+        if let Some(theme) = &self.theme {
+            payload.insert(theme)?;
+        }
+        if let Some(pow) = &self.pow {
+            payload.insert(pow)?;
+        }
+        if let Some(media_server) = &self.media_server {
+            payload.insert(media_server)?;
+        }
+        if let Some(content_warnings) = &self.content_warnings {
+            payload.insert(content_warnings)?;
+        }
+        if let Some(drafts) = &self.drafts {
+            payload.insert(drafts)?;
+        }
+        if let Some(schedule) = &self.schedule {
+            payload.insert(schedule)?;
+        }
+        Ok(payload)
+    }
+
+    fn from_payload(payload: Payload) -> anyhow::Result<Self> {
+        Ok(Self {
+            user: payload
+                .get()?
+                .ok_or_else(|| anyhow::anyhow!("no user in the backup"))?,
+            contacts: payload.get()?.unwrap_or_default(),
+            relays: payload.get()?.unwrap_or_default(),
+            mute: payload.get()?.unwrap_or_default(),
+            notifications: payload.get()?.unwrap_or_default(),
+            locale: payload.get()?,
+            theme: payload.get()?,
+            pow: payload.get()?,
+            media_server: payload.get()?,
+            content_warnings: payload.get()?,
+            drafts: payload.get()?,
+            schedule: payload.get()?,
+            events: payload.events,
+            invalid_events: 0,
+        })
+    }
+}
+
+impl BackupFile {
+    pub fn new(backup: &Backup, passphrase: Option<&str>) -> anyhow::Result<Self> {
+        Self::with_kdf(backup, passphrase, Kdf::default())
+    }
+
+    fn with_kdf(backup: &Backup, passphrase: Option<&str>, kdf: Kdf) -> anyhow::Result<Self> {
+        let json = serde_json::to_string(&backup.to_payload()?)?;
+        let mut file = Self {
+            version: BACKUP_VERSION,
+            created_at: Timestamp::now().as_u64(),
+            salt: None,
+            kdf: None,
+            mac: None,
+            payload: json,
+        };
+        if let Some(passphrase) = passphrase.filter(|p| !p.is_empty()) {
+            let salt = rand::random::<[u8; 16]>();
+            let (keys, mac_key) = passphrase_keys(passphrase, &salt, &kdf)?;
+            let payload = nip04::encrypt(&keys.secret_key()?, &keys.public_key(), &file.payload)?;
+            file.mac = Some(to_hex(&hmac_sha256(&mac_key, &[payload.as_bytes()])));
+            file.salt = Some(to_hex(&salt));
+            file.kdf = Some(kdf);
+            file.payload = payload;
+        }
+        Ok(file)
+    }
+
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let file = serde_json::from_str::<Self>(text)?;
+        if file.version == 0 || file.version > BACKUP_VERSION {
+            anyhow::bail!("unsupported backup version {}", file.version);
+        }
+        Ok(file)
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.salt.is_some()
+    }
+
+    // decrypt and validate, the events with invalid signatures are dropped
+    pub fn open(&self, passphrase: Option<&str>) -> anyhow::Result<Backup> {
+        let json = match (&self.salt, passphrase) {
+            (Some(salt), Some(passphrase)) => {
+                let kdf = self.kdf.clone().unwrap_or_default();
+                let (keys, mac_key) = passphrase_keys(passphrase, &from_hex(salt)?, &kdf)?;
+                // the version 1 files have no MAC, a wrong passphrase fails the decryption
+                if let Some(mac) = &self.mac {
+                    let expected = hmac_sha256(&mac_key, &[self.payload.as_bytes()]);
+                    if from_hex(mac)? != expected {
+                        anyhow::bail!("wrong passphrase");
+                    }
+                }
+                nip04::decrypt(&keys.secret_key()?, &keys.public_key(), &self.payload).map_err(
+                    |_| match self.mac {
+                        Some(_) => anyhow::anyhow!("damaged backup"),
+                        None => anyhow::anyhow!("wrong passphrase"),
+                    },
+                )?
+            }
+            (Some(_), None) => anyhow::bail!("passphrase required"),
+            (None, _) => self.payload.clone(),
+        };
+        let value = serde_json::from_str::<Value>(&json)?;
+        let payload = if self.version < 3 {
+            Payload::from_fields(value)
+        } else {
+            serde_json::from_value(value)?
+        };
+        let mut backup = Backup::from_payload(payload)?;
+        let total = backup.events.len();
+        backup.events.retain(|e| e.verify().is_ok());
+        backup.invalid_events = total - backup.events.len();
+        Ok(backup)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pbkdf2_hex(password: &str, salt: &str, rounds: u32, len: usize) -> String {
+        let mut out = vec![0u8; len];
+        pbkdf2_sha256(password.as_bytes(), salt.as_bytes(), rounds, &mut out);
+        to_hex(&out)
+    }
+
+    // the test vectors of RFC 7914 section 11 and the widely used PBKDF2-HMAC-SHA256 ones
+    #[test]
+    fn pbkdf2_test_vectors() {
+        assert_eq!(
+            pbkdf2_hex("password", "salt", 1, 32),
+            "120fb6cffcf8b32c43e7225256c4f837a86548c92ccc35480805987cb70be17b"
+        );
+        assert_eq!(
+            pbkdf2_hex("password", "salt", 2, 32),
+            "ae4d0c95af6b46d32d0adff928f06dd02a303f8ef3c251dfd6e2d85a95474c43"
+        );
+        assert_eq!(
+            pbkdf2_hex("password", "salt", 4096, 32),
+            "c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134a"
+        );
+        assert_eq!(
+            pbkdf2_hex(
+                "passwordPASSWORDpassword",
+                "saltSALTsaltSALTsaltSALTsaltSALTsalt",
+                4096,
+                40
+            ),
+            "348c89dbcbd32b2f32d814b8116e84cf2b17347ebc1800181c4e2a1fb8dd53e1c635518c7dac47e9"
+        );
+        assert_eq!(
+            pbkdf2_hex("passwd", "salt", 1, 64),
+            "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc\
+             49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783"
+        );
+    }
+
+    fn backup() -> Backup {
+        let mut user = User::default();
+        user.keys = Some(Keys::generate());
+        Backup {
+            user,
+            ..Backup::default()
+        }
+    }
+
+    fn fast_kdf() -> Kdf {
+        Kdf {
+            rounds: 10,
+            ..Kdf::default()
+        }
+    }
+
+    fn reparse(file: &BackupFile) -> BackupFile {
+        BackupFile::parse(&serde_json::to_string(file).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn round_trip_plain() {
+        let backup = backup();
+        let file = reparse(&BackupFile::new(&backup, None).unwrap());
+        assert!(!file.is_encrypted());
+        assert!(file.kdf.is_none() && file.mac.is_none());
+        let opened = file.open(None).unwrap();
+        assert_eq!(opened.pubkey(), backup.pubkey());
+    }
+
+    #[test]
+    fn round_trip_stores() {
+        let mut backup = backup();
+        backup.notifications.read_until = 5;
+        backup.locale = I18nLocale::from_code("en");
+        let mut content_warnings = ContentWarnings::default();
+        content_warnings.mode = ContentWarningMode::AlwaysHide;
+        backup.content_warnings = Some(content_warnings);
+        let file = reparse(&BackupFile::new(&backup, None).unwrap());
+        let payload = serde_json::from_str::<Value>(&file.payload).unwrap();
+        assert_eq!(
+            payload["stores"]["notifications"]["version"],
+            UserNotification::schema_version()
+        );
+        assert!(payload["stores"].get("theme").is_none());
+        let opened = file.open(None).unwrap();
+        assert_eq!(opened.notifications.read_until, 5);
+        assert_eq!(opened.locale, I18nLocale::from_code("en"));
+        assert_eq!(
+            opened.content_warnings.map(|cw| cw.mode),
+            Some(ContentWarningMode::AlwaysHide)
+        );
+        assert!(opened.theme.is_none() && opened.schedule.is_none());
+    }
+
+    #[test]
+    fn unreadable_store() {
+        let payload = serde_json::json!({
+            "stores": {
+                "nostr-user-info": encode_store(&backup().user).unwrap(),
+                "theme": { "version": Theme::schema_version() + 1, "data": {} },
+            },
+        });
+        let file = BackupFile {
+            payload: payload.to_string(),
+            ..BackupFile::new(&backup(), None).unwrap()
+        };
+        let err = file.open(None).unwrap_err().to_string();
+        assert!(err.starts_with("theme:"), "{err}");
+    }
+
+    #[test]
+    fn round_trip_encrypted() {
+        let backup = backup();
+        let file = reparse(&BackupFile::with_kdf(&backup, Some("secret"), fast_kdf()).unwrap());
+        assert!(file.is_encrypted());
+        assert_eq!(file.kdf, Some(fast_kdf()));
+        assert!(!file.payload.contains(&backup.pubkey().unwrap()));
+        let opened = file.open(Some("secret")).unwrap();
+        assert_eq!(opened.pubkey(), backup.pubkey());
+        // the default derivation
+        let file = reparse(&BackupFile::new(&backup, Some("secret")).unwrap());
+        assert_eq!(file.kdf, Some(Kdf::default()));
+        assert_eq!(file.open(Some("secret")).unwrap().pubkey(), backup.pubkey());
+    }
+
+    #[test]
+    fn wrong_passphrase() {
+        let file = BackupFile::with_kdf(&backup(), Some("secret"), fast_kdf()).unwrap();
+        let err = file.open(Some("Secret")).unwrap_err();
+        assert_eq!(err.to_string(), "wrong passphrase");
+        let err = file.open(None).unwrap_err();
+        assert_eq!(err.to_string(), "passphrase required");
+    }
+
+    #[test]
+    fn damaged_payload() {
+        let mut file = BackupFile::with_kdf(&backup(), Some("secret"), fast_kdf()).unwrap();
+        file.payload.insert(0, 'A');
+        assert_eq!(
+            file.open(Some("secret")).unwrap_err().to_string(),
+            "wrong passphrase"
+        );
+    }
+
+    #[test]
+    fn unsupported_kdf() {
+        let mut file = BackupFile::with_kdf(&backup(), Some("secret"), fast_kdf()).unwrap();
+        file.kdf = Some(Kdf {
+            name: "scrypt".to_owned(),
+            rounds: 10,
+        });
+        assert!(file.open(Some("secret")).is_err());
+        file.kdf = Some(Kdf {
+            rounds: MAX_KDF_ROUNDS + 1,
+            ..Kdf::default()
+        });
+        assert!(file.open(Some("secret")).is_err());
+    }
+
+    // version 1: the default derivation, no MAC, the stores as fields
+    #[test]
+    fn open_version_1() {
+        let backup = backup();
+        let salt = [7u8; 16];
+        let (keys, _) = passphrase_keys("secret", &salt, &Kdf::default()).unwrap();
+        let id = nostr_sdk::nostr::EventId::from_slice(&[1; 32]).unwrap();
+        let json = serde_json::json!({
+            "user": backup.user,
+            "contacts": UserContact::default(),
+            "relays": Relay::default(),
+            "notifications": { "read_until": 5, "read_ids": [id.to_hex()] },
+            "events": [],
+        })
+        .to_string();
+        let payload =
+            nip04::encrypt(&keys.secret_key().unwrap(), &keys.public_key(), json).unwrap();
+        let text = serde_json::json!({
+            "version": 1,
+            "created_at": 0,
+            "salt": to_hex(&salt),
+            "payload": payload,
+        })
+        .to_string();
+        let file = BackupFile::parse(&text).unwrap();
+        let opened = file.open(Some("secret")).unwrap();
+        assert_eq!(opened.pubkey(), backup.pubkey());
+        // migrated from the layout of the time
+        assert_eq!(opened.notifications.read_until, 5);
+        assert!(opened.notifications.read_ids.borrow().contains_key(&id));
+        assert!(opened.locale.is_none());
+        assert!(file.open(Some("wrong")).is_err());
+    }
+
+    #[test]
+    fn newer_version_rejected() {
+        let text = format!(
+            r#"{{"version":{},"created_at":0,"payload":"{{}}"}}"#,
+            BACKUP_VERSION + 1
+        );
+        assert!(BackupFile::parse(&text).is_err());
+    }
+}
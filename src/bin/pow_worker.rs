@@ -0,0 +1,30 @@
+use dapp::agent::*;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{DedicatedWorkerGlobalScope, MessageEvent};
+
+fn main() {
+    #[cfg(debug_assertions)]
+    wasm_logger::init(wasm_logger::Config::new(log::Level::Debug));
+    #[cfg(not(debug_assertions))]
+    wasm_logger::init(wasm_logger::Config::new(log::Level::Info));
+    let scope = js_sys::global().unchecked_into::<DedicatedWorkerGlobalScope>();
+    let onmessage = {
+        let scope = scope.clone();
+        Closure::<dyn Fn(MessageEvent)>::new(move |e: MessageEvent| {
+            if let Some(req) = from_message::<PowRequest>(e.data()) {
+                let event = mine(req, |hashes, best| {
+                    scope
+                        .post_message(&to_message(&PowOutput::Progress(hashes, best)))
+                        .ok();
+                });
+                scope
+                    .post_message(&to_message(&PowOutput::Done(event)))
+                    .ok();
+            }
+        })
+    };
+    scope.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+    onmessage.forget();
+    scope.post_message(&to_message(&PowOutput::Ready)).ok();
+}
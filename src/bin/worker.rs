@@ -1,6 +1,111 @@
-// use dapp::agent::WebsocketWorker;
-// use yew_agent::PrivateWorker;
-
-fn main() {
-    // WebsocketWorker::register();
-}
+use dapp::agent::*;
+use gloo_timers::callback::Interval;
+use js_sys::Reflect;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{DedicatedWorkerGlobalScope, MessageEvent, MessagePort, SharedWorkerGlobalScope};
+
+// one relay pool for all the tabs, each tab talks through its own port
+fn run_shared(scope: SharedWorkerGlobalScope) {
+    let ports = Rc::new(RefCell::new(HashMap::<PortId, MessagePort>::new()));
+    let respond = {
+        let ports = ports.clone();
+        move |port: PortId, msg: WorkerOutput| {
+            if let Some(p) = ports.borrow().get(&port) {
+                if let Err(e) = p.post_message(&to_message(&Envelope { port, msg })) {
+                    log::error!("post to tab {port} error:{e:?}");
+                }
+            }
+        }
+    };
+    let worker = Rc::new(RefCell::new(WebsocketWorker::new(respond)));
+    // a tab may be gone without `Detach`, e.g. crashed
+    {
+        let ports = ports.clone();
+        let worker = worker.clone();
+        Interval::new(WebsocketWorker::EVICT_INTERVAL, move || {
+            for port in worker.borrow_mut().evict_silent() {
+                ports.borrow_mut().remove(&port);
+            }
+        })
+        .forget();
+    }
+    let onconnect = Closure::<dyn Fn(MessageEvent)>::new(move |e: MessageEvent| {
+        let port = e.ports().get(0).unchecked_into::<MessagePort>();
+        let onmessage = {
+            let ports = ports.clone();
+            let worker = worker.clone();
+            let port = port.clone();
+            Closure::<dyn Fn(MessageEvent)>::new(move |e: MessageEvent| {
+                if let Some(env) = from_message::<Envelope<WebsocketWorkerInputMsg>>(e.data()) {
+                    let detach = matches!(env.msg, WebsocketWorkerInputMsg::Detach);
+                    ports.borrow_mut().insert(env.port, port.clone());
+                    worker.borrow_mut().handle_input(env.port, env.msg);
+                    if detach {
+                        ports.borrow_mut().remove(&env.port);
+                    }
+                }
+            })
+        };
+        port.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        onmessage.forget();
+        port.start();
+        // the tab id is unknown until its first message
+        let ready = Envelope {
+            port: 0,
+            msg: WorkerOutput::Ready,
+        };
+        port.post_message(&to_message(&ready)).ok();
+    });
+    scope.set_onconnect(Some(onconnect.as_ref().unchecked_ref()));
+    onconnect.forget();
+}
+
+// a worker of one tab, or of the leader tab forwarding the others
+fn run_dedicated(scope: DedicatedWorkerGlobalScope) {
+    let respond = {
+        let scope = scope.clone();
+        move |port: PortId, msg: WorkerOutput| {
+            if let Err(e) = scope.post_message(&to_message(&Envelope { port, msg })) {
+                log::error!("post to page error:{e:?}");
+            }
+        }
+    };
+    let worker = Rc::new(RefCell::new(WebsocketWorker::new(respond)));
+    // the other tabs of the leader may be gone without `Detach`
+    {
+        let worker = worker.clone();
+        Interval::new(WebsocketWorker::EVICT_INTERVAL, move || {
+            worker.borrow_mut().evict_silent();
+        })
+        .forget();
+    }
+    let onmessage = Closure::<dyn Fn(MessageEvent)>::new(move |e: MessageEvent| {
+        if let Some(env) = from_message::<Envelope<WebsocketWorkerInputMsg>>(e.data()) {
+            worker.borrow_mut().handle_input(env.port, env.msg);
+        }
+    });
+    scope.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+    onmessage.forget();
+    let ready = Envelope {
+        port: 0,
+        msg: WorkerOutput::Ready,
+    };
+    scope.post_message(&to_message(&ready)).ok();
+}
+
+fn main() {
+    #[cfg(debug_assertions)]
+    wasm_logger::init(wasm_logger::Config::new(log::Level::Debug));
+    #[cfg(not(debug_assertions))]
+    wasm_logger::init(wasm_logger::Config::new(log::Level::Info));
+    let global = js_sys::global();
+    if Reflect::has(&global, &JsValue::from_str("onconnect")).unwrap_or(false) {
+        run_shared(global.unchecked_into());
+    } else {
+        run_dedicated(global.unchecked_into());
+    }
+}
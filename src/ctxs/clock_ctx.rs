@@ -0,0 +1,36 @@
+// 定时更新的当前时间, 让"3分钟前"这样的相对时间跟着变化
+use nostr_sdk::nostr::Timestamp;
+use std::rc::Rc;
+use yew::Reducible;
+
+// the relative times are in minutes at least
+pub const CLOCK_TICK_MS: u32 = 30_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clock {
+    pub now: Timestamp,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self {
+            now: Timestamp::now(),
+        }
+    }
+}
+
+pub enum ClockAction {
+    Tick,
+}
+
+impl Reducible for Clock {
+    type Action = ClockAction;
+
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        match action {
+            ClockAction::Tick => Rc::new(Self::default()),
+        }
+    }
+}
+
+pub type ClockContext = yew::UseReducerHandle<Clock>;
@@ -5,21 +5,87 @@ use nostr_sdk::nostr::prelude::*;
 
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
-use std::collections::{HashSet, LinkedList};
+use std::collections::{HashMap, HashSet, LinkedList};
 use std::rc::Rc;
-use std::str::FromStr;
-use yew::prelude::*;
+use yew::{Reducible, UseReducerHandle};
 
 const MAX_RECENTS: usize = 10;
+// NIP-51 follow set
+pub const FOLLOW_SET_KIND: Kind = Kind::ParameterizedReplaceable(30000);
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct UserContact {
     pub data: Rc<RefCell<IndexMap<XOnlyPublicKey, UserContactInfo>>>,
     pub recent: Rc<RefCell<LinkedList<XOnlyPublicKey>>>,
+    #[serde(default)]
+    pub recent_at: Rc<RefCell<HashMap<XOnlyPublicKey, u64>>>, // 最近一次互动的时间
+    #[serde(default)]
+    pub groups: Rc<RefCell<IndexMap<String, ContactGroup>>>, // 联系人分组, 一个联系人可以属于多个分组
+    #[serde(skip)]
+    pub dirty_groups: Rc<RefCell<HashSet<String>>>, // 本地修改过, 需要发布的分组
+    #[serde(skip)]
+    pub import: Option<ContactImport>, // 正在导入的联系人
+    #[serde(skip)]
+    pub sync: ContactSync, // 与relay上的联系人列表(kind 3)的同步状态
     #[serde(skip)]
     count: u64, //for making the reducible UserContact in responsetive to change,  add the count field
 }
 
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContactGroup {
+    pub members: Vec<XOnlyPublicKey>,
+    pub updated_at: u64, // created_at of the follow set on relays, or the last local edit
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum ContactSync {
+    // the latest kind 3 hasn't been fetched from relays, nothing is published
+    #[default]
+    Pending,
+    // the remote list differs from the local one, wait for the user to resolve
+    Preview(Rc<ContactSyncPreview>),
+    Synced,
+    // no relay answered, nothing is published until it's fetched again
+    Failed,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContactSyncPreview {
+    pub remote: Vec<UserContactInfo>,
+    pub remote_only: Vec<UserContactInfo>,
+    pub local_only: Vec<UserContactInfo>,
+    pub conflicts: Vec<(UserContactInfo, UserContactInfo)>, // (local, remote)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContactImport {
+    // fetching the kind 3 of the user from relays
+    Fetching(XOnlyPublicKey),
+    Preview(Rc<ContactImportPreview>),
+    // the follows couldn't be fetched, with the error
+    Failed(XOnlyPublicKey, String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContactImportPreview {
+    pub source: String,
+    pub candidates: Vec<ContactImportCandidate>, // deduped by public key
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContactImportCandidate {
+    pub info: UserContactInfo,
+    pub existing: bool, // already in the contacts
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContactSyncResolution {
+    MergeKeepRemote,
+    MergeKeepLocal,
+    UseRemote,
+    KeepLocal,
+}
+
 impl PartialEq for UserContact {
     fn eq(&self, other: &Self) -> bool {
         self.count == other.count
@@ -31,44 +97,71 @@ pub struct UserContactInfo {
     pub avatar_url: Option<String>,
     pub relay: Option<Url>,
     pub nickname: Option<String>,
+    #[serde(default)]
+    pub nip05: Option<String>,
+}
+
+impl From<&Contact> for UserContactInfo {
+    fn from(contact: &Contact) -> Self {
+        Self {
+            pubkey: Some(contact.pk),
+            avatar_url: None,
+            relay: contact.relay_url.as_ref().and_then(|u| Url::parse(u).ok()),
+            nickname: contact.alias.clone().filter(|alias| !alias.is_empty()),
+            nip05: None,
+        }
+    }
 }
 
 impl UserContactInfo {
+    // fill the empty fields from the other one, the existing values are kept
+    fn merge(&self, other: &Self) -> Self {
+        Self {
+            pubkey: self.pubkey.or(other.pubkey),
+            avatar_url: self.avatar_url.clone().or(other.avatar_url.clone()),
+            relay: self.relay.clone().or(other.relay.clone()),
+            nickname: self
+                .nickname
+                .clone()
+                .filter(|name| !name.is_empty())
+                .or(other.nickname.clone()),
+            nip05: self.nip05.clone().or(other.nip05.clone()),
+        }
+    }
+
+    // both have a relay or nickname, but they are different
+    fn is_conflict(&self, other: &Self) -> bool {
+        let differ = |a: &Option<String>, b: &Option<String>| match (a, b) {
+            (Some(a), Some(b)) => !a.is_empty() && !b.is_empty() && a != b,
+            _ => false,
+        };
+        differ(
+            &self.relay.as_ref().map(|u| u.to_string()),
+            &other.relay.as_ref().map(|u| u.to_string()),
+        ) || differ(&self.nickname, &other.nickname)
+    }
+
     pub fn empty() -> Self {
         Self {
             pubkey: None,
             avatar_url: None,
             relay: None,
             nickname: None,
+            nip05: None,
         }
     }
 }
 
 impl Default for UserContact {
     fn default() -> Self {
-        let pk =
-            Keys::from_pk_str("npub180cvv07tjdrrgpa0j7j7tmnyl2yr6yr7l8j4s3evf6u64th6gkwsyjh6w6")
-                .unwrap();
-        let pk = pk.public_key();
-        let mut map = IndexMap::new();
-        let mut d = UserContactInfo {
-            pubkey: Some(pk.clone()),
-            avatar_url: None,
-            relay: Some(Url::from_str("wss://relay.damus.io").unwrap()),
-            nickname: Some("lhy1".to_owned()),
-        };
-        map.insert(pk.clone(), d.clone());
-        let mut list = LinkedList::new();
-        list.push_front(pk);
-        let pk = nostr_sdk::nostr::Keys::generate().public_key();
-        // let pk = pk.x_only_public_key().0;
-        d.pubkey = Some(pk.clone());
-        map.insert(pk.clone(), d);
-        list.push_front(pk);
-
         Self {
-            data: Rc::new(RefCell::new(map)),
-            recent: Rc::new(RefCell::new(list)),
+            data: Rc::new(RefCell::new(IndexMap::new())),
+            recent: Rc::new(RefCell::new(LinkedList::new())),
+            recent_at: Rc::new(RefCell::new(HashMap::new())),
+            groups: Rc::new(RefCell::new(IndexMap::new())),
+            dirty_groups: Rc::new(RefCell::new(HashSet::new())),
+            import: None,
+            sync: ContactSync::default(),
             count: 0,
         }
     }
@@ -82,6 +175,191 @@ impl LoadStoreKey for UserContact {
 impl UserContact {
     // const USER_CONTACT_KEY: &'static str = "user-contacts";
 
+    // the kind 3 contact list of the local contacts
+    pub fn nostr_contacts(&self) -> Vec<Contact> {
+        self.data
+            .borrow()
+            .values()
+            .map(|contact| Contact {
+                pk: contact.pubkey.unwrap(),
+                relay_url: contact.relay.as_ref().map(|u| u.to_string()),
+                alias: contact.nickname.clone(),
+            })
+            .collect()
+    }
+
+    // the contacts in the tags of a kind 3
+    pub fn contacts_of_event(event: Event) -> Vec<Contact> {
+        event
+            .tags
+            .into_iter()
+            .filter_map(|tag| match tag {
+                Tag::PubKey(pk, relay_url) => Some(Contact::new(pk, relay_url, None)),
+                Tag::ContactList {
+                    pk,
+                    relay_url,
+                    alias,
+                } => Some(Contact::new(pk, relay_url, alias)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn is_in_group(&self, group: &str, pk: &XOnlyPublicKey) -> bool {
+        self.groups
+            .borrow()
+            .get(group)
+            .map(|g| g.members.contains(pk))
+            .unwrap_or(false)
+    }
+
+    // the members of all groups, for the group timelines
+    pub fn group_members(&self) -> Vec<XOnlyPublicKey> {
+        let mut members = self
+            .groups
+            .borrow()
+            .values()
+            .flat_map(|g| g.members.iter().copied())
+            .collect::<Vec<_>>();
+        members.sort();
+        members.dedup();
+        members
+    }
+
+    // kind 30000, the group name is the `d` tag, and the members are the `p` tags
+    // the follow set of the group, or a NIP-09 deletion of it when the group is removed
+    pub fn follow_set_event(&self, group: &str, keys: &Keys) -> anyhow::Result<Event> {
+        let builder = match self.groups.borrow().get(group) {
+            Some(g) => {
+                let mut tags = vec![Tag::Identifier(group.to_owned())];
+                tags.extend(g.members.iter().map(|pk| Tag::PubKey(*pk, None)));
+                EventBuilder::new(FOLLOW_SET_KIND, "", &tags)
+            }
+            None => {
+                let address = Tag::A {
+                    kind: FOLLOW_SET_KIND,
+                    public_key: keys.public_key(),
+                    identifier: group.to_owned(),
+                    relay_url: String::new(),
+                };
+                EventBuilder::new(Kind::EventDeletion, "", &[address])
+            }
+        };
+        Ok(builder.to_event(keys)?)
+    }
+
+    fn import_preview(&self, source: String, infos: Vec<UserContactInfo>) -> ContactImport {
+        let data = self.data.borrow();
+        let mut seen = HashSet::new();
+        let candidates = infos
+            .into_iter()
+            .filter(|info| info.pubkey.map(|pk| seen.insert(pk)).unwrap_or(false))
+            .map(|info| ContactImportCandidate {
+                existing: data.contains_key(info.pubkey.as_ref().unwrap()),
+                info,
+            })
+            .collect();
+        ContactImport::Preview(Rc::new(ContactImportPreview { source, candidates }))
+    }
+
+    fn touch_group(&self, group: &str) {
+        if let Some(g) = self.groups.borrow_mut().get_mut(group) {
+            g.updated_at = Timestamp::now().as_u64().max(g.updated_at + 1);
+        }
+        self.dirty_groups.borrow_mut().insert(group.to_owned());
+    }
+
+    // drop the removed contacts from the recents
+    fn prune_recent(&self) {
+        let data = self.data.borrow();
+        let mut recent = self.recent.borrow_mut();
+        *recent = recent
+            .iter()
+            .filter(|pk| data.contains_key(*pk))
+            .copied()
+            .collect();
+        self.recent_at
+            .borrow_mut()
+            .retain(|pk, _| recent.contains(pk));
+    }
+
+    pub fn recent_at(&self, pk: &XOnlyPublicKey) -> Option<Timestamp> {
+        self.recent_at.borrow().get(pk).map(|t| Timestamp::from(*t))
+    }
+
+    // drop the removed contacts from the groups
+    fn remove_from_groups(&self, pks: &HashSet<XOnlyPublicKey>) {
+        let changed = self
+            .groups
+            .borrow_mut()
+            .iter_mut()
+            .filter(|(_, g)| g.members.iter().any(|pk| pks.contains(pk)))
+            .map(|(name, g)| {
+                g.members.retain(|pk| !pks.contains(pk));
+                name.clone()
+            })
+            .collect::<Vec<_>>();
+        for name in changed {
+            self.touch_group(&name);
+        }
+    }
+
+    fn preview(&self, remote: Vec<UserContactInfo>) -> ContactSyncPreview {
+        let data = self.data.borrow();
+        let mut preview = ContactSyncPreview::default();
+        for info in &remote {
+            let pk = info.pubkey.as_ref().unwrap();
+            match data.get(pk) {
+                Some(local) if local.is_conflict(info) => {
+                    preview.conflicts.push((local.clone(), info.clone()))
+                }
+                Some(_) => {}
+                None => preview.remote_only.push(info.clone()),
+            }
+        }
+        preview.local_only = data
+            .values()
+            .filter(|local| !remote.iter().any(|info| info.pubkey == local.pubkey))
+            .cloned()
+            .collect();
+        preview.remote = remote;
+        preview
+    }
+
+    fn resolve(&self, preview: &ContactSyncPreview, resolution: ContactSyncResolution) {
+        use ContactSyncResolution::*;
+        let mut data = self.data.borrow_mut();
+        match resolution {
+            KeepLocal => {}
+            UseRemote => {
+                *data = preview
+                    .remote
+                    .iter()
+                    .map(|info| {
+                        let pk = info.pubkey.unwrap();
+                        // keep the local profile data of the remaining contacts
+                        let info = match data.get(&pk) {
+                            Some(local) => info.merge(local),
+                            None => info.clone(),
+                        };
+                        (pk, info)
+                    })
+                    .collect();
+            }
+            MergeKeepRemote | MergeKeepLocal => {
+                for info in &preview.remote {
+                    let pk = info.pubkey.unwrap();
+                    let merged = match data.get(&pk) {
+                        Some(local) if resolution == MergeKeepLocal => local.merge(info),
+                        Some(local) => info.merge(local),
+                        None => info.clone(),
+                    };
+                    data.insert(pk, merged);
+                }
+            }
+        }
+    }
+
     // pub fn load() -> Self {
     //     let ret = if let Ok(user) = LocalStorage::get::<UserContact>(Self::USER_CONTACT_KEY) {
     //         user
@@ -99,10 +377,39 @@ impl UserContact {
 pub enum UserContactAction {
     Remove(XOnlyPublicKey),
     RemoveBatch(HashSet<XOnlyPublicKey>),
-    Recent(XOnlyPublicKey),
+    // the contacts opened, messaged or mentioned, the first one is the latest
+    Recent(Vec<XOnlyPublicKey>),
 
     Add(UserContactInfo),
     Update(UserContactInfo),
+
+    // the latest kind 3 from relays, empty if the relays answered without one
+    Remote(Vec<Contact>),
+    // the kind 3 couldn't be fetched, it isn't the same as an empty one
+    RemoteFailed,
+    ResolveSync(ContactSyncResolution),
+    ResetSync,
+    RetrySync,
+
+    AddGroup(String),
+    RemoveGroup(String),
+    ToggleGroupMember(String, XOnlyPublicKey),
+    // a follow set from relays: the name, members and created_at
+    RemoteGroup(String, Vec<XOnlyPublicKey>, u64),
+
+    // copy the follows of the user
+    FetchFollows(XOnlyPublicKey),
+    // the contacts read from a file, and the file name
+    ImportPreview(String, Vec<UserContactInfo>),
+    // the follows fetched, dropped if the fetching is cancelled
+    FollowsFetched(XOnlyPublicKey, Vec<UserContactInfo>),
+    FollowsFailed(XOnlyPublicKey, String),
+    // import the chosen ones of the preview
+    ApplyImport(HashSet<XOnlyPublicKey>),
+    CancelImport,
+
+    // restore from a backup, replace or merge
+    Restore(Box<UserContact>, bool),
 }
 
 impl Reducible for UserContact {
@@ -111,34 +418,237 @@ impl Reducible for UserContact {
     fn reduce(self: std::rc::Rc<Self>, action: Self::Action) -> std::rc::Rc<Self> {
         use UserContactAction::*;
         let mut count = self.count;
+        let mut sync = self.sync.clone();
+        let mut import = self.import.clone();
         match action {
             Remove(pk) => {
                 self.data.borrow_mut().remove(&pk);
+                self.remove_from_groups(&HashSet::from([pk]));
+                self.prune_recent();
             }
             Add(info) | Update(info) => {
                 self.data
                     .borrow_mut()
                     .insert(info.pubkey.as_ref().unwrap().clone(), info);
             }
-            Recent(pk) => {
+            Recent(pks) => {
+                let pks = pks
+                    .into_iter()
+                    .filter(|pk| self.data.borrow().contains_key(pk))
+                    .collect::<Vec<_>>();
+                if pks.is_empty() {
+                    return self;
+                }
                 let mut recent = self.recent.borrow_mut();
-                if recent.len() == MAX_RECENTS {
-                    // gurantee MAX_RECENTS item
-                    recent.pop_back();
+                for pk in pks.into_iter().rev() {
+                    // move the existing one to the front
+                    *recent = recent.iter().filter(|r| **r != pk).copied().collect();
+                    if recent.len() == MAX_RECENTS {
+                        // gurantee MAX_RECENTS item
+                        if let Some(old) = recent.pop_back() {
+                            self.recent_at.borrow_mut().remove(&old);
+                        }
+                    }
+                    // place the latest contact in the front of others
+                    recent.push_front(pk);
+                    self.recent_at
+                        .borrow_mut()
+                        .insert(pk, Timestamp::now().as_u64());
                 }
-                // place the latest contact in the front of others
-                recent.push_front(pk);
             }
             RemoveBatch(pks) => {
-                for pk in pks {
-                    self.data.borrow_mut().remove(&pk);
+                for pk in &pks {
+                    self.data.borrow_mut().remove(pk);
+                }
+                self.remove_from_groups(&pks);
+                self.prune_recent();
+            }
+            Remote(remote) => {
+                let remote = remote.iter().map(UserContactInfo::from).collect::<Vec<_>>();
+                let preview = self.preview(remote);
+                sync = if preview.remote_only.is_empty()
+                    && preview.local_only.is_empty()
+                    && preview.conflicts.is_empty()
+                {
+                    ContactSync::Synced
+                } else if preview.remote.is_empty() {
+                    // nothing on relays, the local contacts are published
+                    ContactSync::Synced
+                } else {
+                    ContactSync::Preview(Rc::new(preview))
+                };
+            }
+            ResolveSync(resolution) => {
+                if let ContactSync::Preview(preview) = &self.sync {
+                    self.resolve(preview, resolution);
+                    self.prune_recent();
+                }
+                sync = ContactSync::Synced;
+            }
+            RemoteFailed => {
+                sync = ContactSync::Failed;
+            }
+            ResetSync => {
+                sync = ContactSync::Pending;
+            }
+            RetrySync => {
+                if self.sync != ContactSync::Failed {
+                    return self;
+                }
+                sync = ContactSync::Pending;
+            }
+            AddGroup(name) => {
+                let name = name.trim().to_owned();
+                if name.is_empty() || self.groups.borrow().contains_key(&name) {
+                    return self;
+                }
+                self.groups
+                    .borrow_mut()
+                    .insert(name.clone(), ContactGroup::default());
+                self.touch_group(&name);
+            }
+            RemoveGroup(name) => {
+                // the follow set is deleted from relays
+                if self.groups.borrow_mut().remove(&name).is_none() {
+                    return self;
+                }
+                self.dirty_groups.borrow_mut().insert(name);
+            }
+            ToggleGroupMember(name, pk) => {
+                let toggled = match self.groups.borrow_mut().get_mut(&name) {
+                    Some(g) => {
+                        if let Some(idx) = g.members.iter().position(|m| *m == pk) {
+                            g.members.remove(idx);
+                        } else {
+                            g.members.push(pk);
+                        }
+                        true
+                    }
+                    None => false,
+                };
+                if !toggled {
+                    // a deleted group, nothing to publish
+                    return self;
+                }
+                self.touch_group(&name);
+            }
+            RemoteGroup(name, members, created_at) => {
+                let mut groups = self.groups.borrow_mut();
+                let updated_at = groups.get(&name).map(|g| g.updated_at);
+                match updated_at {
+                    Some(updated_at) if updated_at > created_at => {
+                        // edited on this device after the one on relays
+                        self.dirty_groups.borrow_mut().insert(name);
+                    }
+                    Some(_) if members.is_empty() => {
+                        groups.remove(&name);
+                    }
+                    None if members.is_empty() => {}
+                    _ => {
+                        groups.insert(
+                            name,
+                            ContactGroup {
+                                members,
+                                updated_at: created_at,
+                            },
+                        );
+                    }
+                }
+            }
+            FetchFollows(pk) => {
+                import = Some(ContactImport::Fetching(pk));
+            }
+            ImportPreview(source, infos) => {
+                import = Some(self.import_preview(source, infos));
+            }
+            FollowsFetched(pk, infos) => {
+                if self.import != Some(ContactImport::Fetching(pk)) {
+                    return self;
+                }
+                let source = pk.to_bech32().unwrap_or(pk.to_string());
+                import = Some(self.import_preview(source, infos));
+            }
+            FollowsFailed(pk, error) => {
+                if self.import != Some(ContactImport::Fetching(pk)) {
+                    return self;
+                }
+                import = Some(ContactImport::Failed(pk, error));
+            }
+            ApplyImport(pks) => {
+                if let Some(ContactImport::Preview(preview)) = &self.import {
+                    let mut data = self.data.borrow_mut();
+                    for candidate in &preview.candidates {
+                        let pk = candidate.info.pubkey.unwrap();
+                        if !pks.contains(&pk) {
+                            continue;
+                        }
+                        // the existing values are kept
+                        let info = match data.get(&pk) {
+                            Some(local) => local.merge(&candidate.info),
+                            None => candidate.info.clone(),
+                        };
+                        data.insert(pk, info);
+                    }
+                }
+                import = None;
+            }
+            CancelImport => {
+                import = None;
+            }
+            Restore(backup, merge) => {
+                let mut data = self.data.borrow_mut();
+                let mut groups = self.groups.borrow_mut();
+                if merge {
+                    for (pk, info) in backup.data.take() {
+                        let info = match data.get(&pk) {
+                            Some(local) => local.merge(&info),
+                            None => info,
+                        };
+                        data.insert(pk, info);
+                    }
+                    for (name, group) in backup.groups.take() {
+                        let merged = match groups.get(&name) {
+                            Some(local) => {
+                                let mut members = local.members.clone();
+                                for pk in group.members {
+                                    if !members.contains(&pk) {
+                                        members.push(pk);
+                                    }
+                                }
+                                ContactGroup {
+                                    members,
+                                    updated_at: Timestamp::now().as_u64(),
+                                }
+                            }
+                            None => group,
+                        };
+                        groups.insert(name.clone(), merged);
+                        self.dirty_groups.borrow_mut().insert(name);
+                    }
+                } else {
+                    *data = backup.data.take();
+                    // the groups not in the backup are published empty
+                    let mut dirty = self.dirty_groups.borrow_mut();
+                    dirty.extend(groups.keys().cloned());
+                    *groups = backup.groups.take();
+                    dirty.extend(groups.keys().cloned());
+                    *self.recent.borrow_mut() = backup.recent.take();
+                    *self.recent_at.borrow_mut() = backup.recent_at.take();
                 }
+                drop(data);
+                drop(groups);
+                self.prune_recent();
             }
         };
         count = count.checked_add(1).or(Some(0)).unwrap();
         let ret = Rc::new(Self {
             data: self.data.clone(),
             recent: self.recent.clone(),
+            recent_at: self.recent_at.clone(),
+            groups: self.groups.clone(),
+            dirty_groups: self.dirty_groups.clone(),
+            import,
+            sync,
             count,
         });
 
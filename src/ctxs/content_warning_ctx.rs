@@ -0,0 +1,93 @@
+// NIP-36 content warning: 折叠, 总是显示, 或者总是隐藏
+use crate::ctxs::*;
+
+use nostr_sdk::nostr::{Event, EventId, Tag};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+use yew::Reducible;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ContentWarningMode {
+    // behind a banner, shown by a click
+    #[default]
+    Collapse,
+    AlwaysShow,
+    // left out of the timeline and the search
+    AlwaysHide,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContentWarnings {
+    pub mode: ContentWarningMode,
+    // clicked to show, until the page is closed
+    #[serde(skip)]
+    pub revealed: Rc<RefCell<HashSet<EventId>>>,
+    #[serde(skip)]
+    count: u64,
+}
+
+impl PartialEq for ContentWarnings {
+    fn eq(&self, other: &Self) -> bool {
+        self.count == other.count
+    }
+}
+
+impl LoadStoreKey for ContentWarnings {
+    fn load_store_key() -> &'static str {
+        "content_warning"
+    }
+}
+
+pub enum ContentWarningAction {
+    SetMode(ContentWarningMode),
+    Reveal(EventId),
+}
+
+// the reason of the `content-warning` tag, empty when it has none
+pub fn content_warning(event: &Event) -> Option<String> {
+    event.tags.iter().find_map(|tag| match tag {
+        Tag::ContentWarning { reason } => Some(reason.clone().unwrap_or_default()),
+        _ => None,
+    })
+}
+
+impl ContentWarnings {
+    // left out of the feeds
+    pub fn is_hidden(&self, event: &Event) -> bool {
+        self.mode == ContentWarningMode::AlwaysHide && content_warning(event).is_some()
+    }
+
+    pub fn is_collapsed(&self, event: &Event) -> bool {
+        self.mode != ContentWarningMode::AlwaysShow
+            && !self.revealed.borrow().contains(&event.id)
+            && content_warning(event).is_some()
+    }
+}
+
+impl Reducible for ContentWarnings {
+    type Action = ContentWarningAction;
+
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        let count = self.count.checked_add(1).or(Some(0)).unwrap();
+        let mut mode = self.mode;
+        match action {
+            ContentWarningAction::SetMode(v) => mode = v,
+            ContentWarningAction::Reveal(id) => {
+                self.revealed.borrow_mut().insert(id);
+            }
+        }
+        let ret = Rc::new(Self {
+            mode,
+            revealed: self.revealed.clone(),
+            count,
+        });
+        if ret.mode != self.mode {
+            ret.store();
+        }
+        ret
+    }
+}
+
+pub type ContentWarningContext = yew::UseReducerHandle<ContentWarnings>;
@@ -0,0 +1,71 @@
+// 每个账户未发送的草稿, 保存在本地
+use crate::ctxs::*;
+
+use nostr_sdk::nostr::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use yew::Reducible;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Drafts {
+    pub drafts: Rc<RefCell<HashMap<XOnlyPublicKey, String>>>,
+    #[serde(skip)]
+    count: u64,
+}
+
+impl PartialEq for Drafts {
+    fn eq(&self, other: &Self) -> bool {
+        self.count == other.count
+    }
+}
+
+impl LoadStoreKey for Drafts {
+    fn load_store_key() -> &'static str {
+        "drafts"
+    }
+}
+
+pub enum DraftAction {
+    // typed, the compose box has the text already, not rendered again
+    Save(XOnlyPublicKey, String),
+    // sent
+    Clear(XOnlyPublicKey),
+}
+
+impl Drafts {
+    pub fn get(&self, pk: &XOnlyPublicKey) -> String {
+        self.drafts.borrow().get(pk).cloned().unwrap_or_default()
+    }
+}
+
+impl Reducible for Drafts {
+    type Action = DraftAction;
+
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        let mut count = self.count;
+        match action {
+            DraftAction::Save(pk, content) => {
+                let mut drafts = self.drafts.borrow_mut();
+                if content.is_empty() {
+                    drafts.remove(&pk);
+                } else {
+                    drafts.insert(pk, content);
+                }
+            }
+            DraftAction::Clear(pk) => {
+                self.drafts.borrow_mut().remove(&pk);
+                count = count.checked_add(1).or(Some(0)).unwrap();
+            }
+        }
+        let ret = Rc::new(Self {
+            drafts: self.drafts.clone(),
+            count,
+        });
+        ret.store();
+        ret
+    }
+}
+
+pub type DraftContext = yew::UseReducerHandle<Drafts>;
@@ -1,6 +1,7 @@
 use indexmap::IndexMap;
 use nostr_sdk::nostr::prelude::*;
 use std::cell::RefCell;
+use std::collections::HashSet;
 use std::rc::Rc;
 use yew::Reducible;
 
@@ -15,6 +16,18 @@ pub struct UserEvent {
     count: u64,
 }
 
+// NIP-40, the time after which the event should be dropped
+pub fn expiration(event: &Event) -> Option<Timestamp> {
+    event.tags.iter().find_map(|tag| match tag {
+        Tag::Expiration(t) => Some(*t),
+        _ => None,
+    })
+}
+
+pub fn is_expired(event: &Event, now: Timestamp) -> bool {
+    expiration(event).map_or(false, |t| t <= now)
+}
+
 impl PartialEq for UserEvent {
     fn eq(&self, other: &Self) -> bool {
         self.count == other.count
@@ -23,7 +36,9 @@ impl PartialEq for UserEvent {
 pub enum UserEventAction {
     Add(Event),
     AddVisible(Event),
+    AddVisibleBatch(Vec<Event>),
     Remove(EventId),
+    RemoveBatch(HashSet<EventId>),
     Visible(EventId, bool),
     Update(UserEventMsg),
 }
@@ -35,6 +50,10 @@ impl Reducible for UserEvent {
         let mut count = self.count;
         let evs = self.events.clone();
         match action {
+            // an expired event may still be served by a relay, or restored from a backup
+            Add(event) | AddVisible(event) if is_expired(&event, Timestamp::now()) => {
+                return self;
+            }
             Add(event) => {
                 evs.borrow_mut().insert(
                     event.id,
@@ -50,11 +69,27 @@ impl Reducible for UserEvent {
             Remove(eid) => {
                 evs.borrow_mut().remove(&eid);
             }
+            RemoveBatch(eids) => {
+                evs.borrow_mut().retain(|eid, _| !eids.contains(eid));
+            }
             Visible(eid, visible) => {
                 if let Some(e) = evs.borrow_mut().get_mut(&eid) {
                     e.visible = visible;
                 }
             }
+            AddVisibleBatch(events) => {
+                let now = Timestamp::now();
+                let mut evs = evs.borrow_mut();
+                for event in events.into_iter().filter(|e| !is_expired(e, now)) {
+                    evs.insert(
+                        event.id,
+                        UserEventMsg {
+                            event,
+                            visible: true,
+                        },
+                    );
+                }
+            }
             AddVisible(event) => {
                 evs.borrow_mut().insert(
                     event.id,
@@ -1,33 +1,199 @@
+use crate::ctxs::*;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
 use std::fmt::Display;
 use std::rc::Rc;
 use yew::prelude::*;
 
-#[derive(Clone, Debug, PartialEq, Default, Copy)]
-pub enum I18nLocale {
-    #[default]
-    Zhcn,
-    En,
+// the same as `default-locale` of Cargo.toml, the macro doesn't read it
+pub const DEFAULT_LOCALE: &str = "zh-CN";
+
+// one of the files in `locales/`, adding a language is adding a file
+#[derive(Clone, Debug, PartialEq, Eq, Copy)]
+pub struct I18nLocale(&'static str);
+
+impl Default for I18nLocale {
+    fn default() -> Self {
+        Self::from_code(DEFAULT_LOCALE).unwrap_or(Self(DEFAULT_LOCALE))
+    }
+}
+
+impl Serialize for I18nLocale {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0)
+    }
+}
+
+// a locale removed since it was chosen falls back to the default
+impl<'de> Deserialize<'de> for I18nLocale {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        Ok(Self::from_code(&code).unwrap_or_default())
+    }
 }
 
 impl Display for I18nLocale {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                I18nLocale::En => "en",
-                I18nLocale::Zhcn => "zh-cn",
-            }
-        )
+        write!(f, "{}", self.0)
     }
 }
 
+impl LoadStoreKey for I18nLocale {
+    fn load_store_key() -> &'static str {
+        "locale"
+    }
+
+    // 2: the code of the locale file, it was the name of the enum variant
+    fn schema_version() -> u32 {
+        2
+    }
+
+    fn migrate(version: u32, data: Value) -> anyhow::Result<Value> {
+        if version > 1 {
+            return Ok(data);
+        }
+        Ok(match data.as_str() {
+            Some("Zhcn") => "zh-CN".into(),
+            Some("En") => "en".into(),
+            _ => data,
+        })
+    }
+}
+
+impl I18nLocale {
+    // all the locales, from the locale files
+    pub fn all() -> Vec<Self> {
+        crate::available_locales()
+            .iter()
+            .map(|code| Self(code))
+            .collect()
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        crate::available_locales()
+            .iter()
+            .find(|l| l.eq_ignore_ascii_case(code))
+            .map(|code| Self(code))
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.0
+    }
+
+    // the name of the language in itself, `lang` of the locale file
+    pub fn name(&self) -> String {
+        rust_i18n::t!("lang", locale = self.0)
+    }
+
+    // the chosen one, or the first of the browser languages we have
+    pub fn init() -> Self {
+        let stored = storage().get(Self::load_store_key()).is_some();
+        let locale = if stored { Self::load() } else { Self::detect() };
+        rust_i18n::set_locale(&locale.to_string());
+        locale
+    }
+
+    fn detect() -> Self {
+        let languages = web_sys::window()
+            .map(|w| js_sys::Array::from(&w.navigator().languages()))
+            .map(|langs| {
+                langs
+                    .iter()
+                    .filter_map(|l| l.as_string())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        languages
+            .iter()
+            .find_map(|lang| match_locale(crate::available_locales(), lang))
+            .and_then(Self::from_code)
+            .unwrap_or_default()
+    }
+}
+
+// `zh-TW` matches `zh-tw`, and then `zh-HK` matches the first `zh-*`
+pub fn match_locale<'a>(available: &[&'a str], lang: &str) -> Option<&'a str> {
+    let primary = |l: &str| {
+        l.split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase()
+    };
+    available
+        .iter()
+        .find(|l| l.eq_ignore_ascii_case(&lang.replace('_', "-")))
+        .or_else(|| available.iter().find(|l| primary(l) == primary(lang)))
+        .copied()
+}
+
 impl Reducible for I18nLocale {
     type Action = I18nLocale;
 
     fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
         rust_i18n::set_locale(action.to_string().as_str());
+        action.store();
         Rc::new(action)
     }
 }
 pub type I18nLocaleContext = UseReducerHandle<I18nLocale>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locale_files() {
+        let codes = I18nLocale::all()
+            .iter()
+            .map(|l| l.code())
+            .collect::<Vec<_>>();
+        for code in ["en", "zh-CN", "zh-TW"] {
+            assert!(codes.contains(&code), "{code} in {codes:?}");
+        }
+        assert_eq!(I18nLocale::from_code("zh-tw").unwrap().name(), "繁體中文");
+    }
+
+    #[test]
+    fn match_browser_languages() {
+        let available = ["en", "zh-CN", "zh-TW"];
+        assert_eq!(match_locale(&available, "zh-TW"), Some("zh-TW"));
+        assert_eq!(match_locale(&available, "zh_tw"), Some("zh-TW"));
+        assert_eq!(match_locale(&available, "zh-HK"), Some("zh-CN"));
+        assert_eq!(match_locale(&available, "en-US"), Some("en"));
+        assert_eq!(match_locale(&available, "ja"), None);
+    }
+
+    #[test]
+    fn migrate_variant_names() {
+        let zh = serde_json::from_value::<I18nLocale>(
+            I18nLocale::migrate(1, Value::from("Zhcn")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(zh.code(), "zh-CN");
+        let en = serde_json::from_value::<I18nLocale>(
+            I18nLocale::migrate(1, Value::from("En")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(en.code(), "en");
+        assert_eq!(
+            I18nLocale::migrate(2, Value::from("Zhcn")).unwrap(),
+            Value::from("Zhcn")
+        );
+    }
+
+    // stored as the enum variant before the locales became the codes of the locale files
+    #[test]
+    fn decode_stored_variant_names() {
+        let decode = |value| decode_store::<I18nLocale>(value).unwrap().code();
+        assert_eq!(decode(Value::from("En")), "en");
+        assert_eq!(
+            decode(serde_json::json!({"version": 1, "data": "Zhcn"})),
+            "zh-CN"
+        );
+        assert_eq!(
+            decode(serde_json::json!({"version": 2, "data": "zh-TW"})),
+            "zh-TW"
+        );
+    }
+}
@@ -1,22 +1,230 @@
-use gloo_storage::LocalStorage;
-use gloo_storage::Storage;
+use super::storage::{storage, StorageBackend};
+use gloo_timers::callback::Timeout;
+use indexmap::IndexMap;
+use nostr_sdk::nostr::Timestamp;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use wasm_bindgen::JsValue;
+use yew::Callback;
+
+// 存储的值带有schema版本号: {"version": 1, "data": ...}, 没有版本号的旧数据是版本0
+const VERSION_FIELD: &str = "version";
+const DATA_FIELD: &str = "data";
+// 合并短时间内的多次写入, 避免每个action都序列化整个map
+const STORE_DEBOUNCE_MS: u32 = 300;
+
+// the stored value can't be read, it's kept in `backup_key` and the default is used
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadError {
+    pub key: &'static str,
+    pub backup_key: Option<String>,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StoreError {
+    // the storage is full, the data is only kept in memory
+    QuotaExceeded(String),
+    Serialize(String, String),
+    Storage(String, String),
+}
+
+impl StoreError {
+    pub fn key(&self) -> &str {
+        match self {
+            Self::QuotaExceeded(key) | Self::Serialize(key, _) | Self::Storage(key, _) => key,
+        }
+    }
+
+    // a DOMException, or the error string of a tauri command
+    pub fn from_js(key: &str, e: JsValue) -> Self {
+        let key = key.to_owned();
+        let name = e
+            .as_string()
+            .or_else(|| {
+                js_sys::Reflect::get(&e, &JsValue::from_str("name"))
+                    .ok()
+                    .and_then(|name| name.as_string())
+            })
+            .unwrap_or_default();
+        match name.as_str() {
+            // firefox uses its own name
+            "QuotaExceededError" | "NS_ERROR_DOM_QUOTA_REACHED" => Self::QuotaExceeded(key),
+            _ => Self::Storage(key, format!("{e:?}")),
+        }
+    }
+}
+
+impl std::fmt::Display for StoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::QuotaExceeded(key) => write!(f, "storage quota exceeded when storing {key}"),
+            Self::Serialize(key, e) => write!(f, "serialize {key} error: {e}"),
+            Self::Storage(key, e) => write!(f, "store {key} error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+type PendingStore = Box<dyn FnOnce() -> Result<(), StoreError>>;
+
+thread_local! {
+    static LOAD_ERRORS: RefCell<Vec<LoadError>> = RefCell::new(vec![]);
+    // the latest value of each key waiting to be written
+    static PENDING_STORES: RefCell<IndexMap<&'static str, PendingStore>> = RefCell::new(IndexMap::new());
+    static STORE_SCHEDULED: RefCell<bool> = RefCell::new(false);
+    static STORE_ERROR_CB: RefCell<Option<Callback<StoreError>>> = RefCell::new(None);
+}
+
+// notified when a debounced write fails
+pub fn set_store_error_cb(cb: Option<Callback<StoreError>>) {
+    STORE_ERROR_CB.with(|store_cb| *store_cb.borrow_mut() = cb);
+}
+
+// write all the pending values now, e.g. before the page is closed
+pub fn flush_stores() {
+    STORE_SCHEDULED.with(|scheduled| *scheduled.borrow_mut() = false);
+    let pending = PENDING_STORES.with(|pending| std::mem::take(&mut *pending.borrow_mut()));
+    for (_, store) in pending {
+        if let Err(e) = store() {
+            report_store_error(e);
+        }
+    }
+    // the async backends write in the background, it may not run after the page is hidden
+    storage().flush();
+}
+
+pub fn report_store_error(e: StoreError) {
+    log::error!("{e}");
+    let cb = STORE_ERROR_CB.with(|cb| cb.borrow().clone());
+    if let Some(cb) = cb {
+        cb.emit(e);
+    }
+}
+
+pub fn load_errors() -> Vec<LoadError> {
+    LOAD_ERRORS.with(|errors| errors.borrow().clone())
+}
+
+pub fn dismiss_load_errors() {
+    LOAD_ERRORS.with(|errors| errors.borrow_mut().clear());
+}
 
 pub trait LoadStoreKey: Sized + 'static {
     #[inline]
     fn load_store_key() -> &'static str;
+
+    // bump it when the serde layout changes, and migrate the old data in `migrate`
+    fn schema_version() -> u32 {
+        1
+    }
+
+    // migrate the data of `version` to `version + 1`
+    fn migrate(version: u32, data: Value) -> anyhow::Result<Value> {
+        let _ = version;
+        Ok(data)
+    }
 }
 pub trait LoadStore: Default + LoadStoreKey {
     fn load() -> Self
     where
         for<'de> Self: Deserialize<'de>;
 
+    // debounced, the errors are reported to the callback of `set_store_error_cb`
     fn store(&self)
+    where
+        Self: Serialize + Clone;
+
+    fn store_now(&self) -> Result<(), StoreError>
     where
         Self: Serialize;
 }
 
+// the stored form with the schema version, also kept in the backups
+pub fn encode_store<T>(store: &T) -> serde_json::Result<Value>
+where
+    T: LoadStoreKey + Serialize,
+{
+    Ok(json!({
+        VERSION_FIELD: T::schema_version(),
+        DATA_FIELD: serde_json::to_value(store)?,
+    }))
+}
+
+fn decode<T>(raw: &str) -> anyhow::Result<T>
+where
+    T: LoadStoreKey,
+    for<'de> T: Deserialize<'de>,
+{
+    decode_store(serde_json::from_str::<Value>(raw)?)
+}
+
+// migrated from the version it was stored with
+pub fn decode_store<T>(value: Value) -> anyhow::Result<T>
+where
+    T: LoadStoreKey,
+    for<'de> T: Deserialize<'de>,
+{
+    let (mut version, mut data) = match value {
+        Value::Object(mut obj)
+            if obj.len() == 2
+                && obj.contains_key(DATA_FIELD)
+                && obj.contains_key(VERSION_FIELD) =>
+        {
+            let version = obj
+                .get(VERSION_FIELD)
+                .and_then(|v| v.as_u64())
+                .ok_or_else(|| anyhow::anyhow!("invalid schema version"))?;
+            (version as u32, obj.remove(DATA_FIELD).unwrap())
+        }
+        // stored before the versioning
+        value => (0, value),
+    };
+    if version > T::schema_version() {
+        anyhow::bail!(
+            "stored by a newer version: {version} > {}",
+            T::schema_version()
+        );
+    }
+    while version < T::schema_version() {
+        data = T::migrate(version, data)?;
+        version += 1;
+    }
+    Ok(serde_json::from_value(data)?)
+}
+
+fn load_from<T>(storage: &dyn StorageBackend) -> T
+where
+    T: LoadStoreKey + Default,
+    for<'de> T: Deserialize<'de>,
+{
+    let key = T::load_store_key();
+    log::info!("load:{}", key);
+    let raw = match storage.get(key) {
+        Some(raw) => raw,
+        None => return T::default(),
+    };
+    match decode::<T>(&raw) {
+        Ok(data) => data,
+        Err(e) => {
+            // keep a copy of the unreadable data, instead of overwriting it silently
+            let backup_key = format!("{key}.unreadable.{}", Timestamp::now());
+            let backup_key = storage.set(&backup_key, &raw).ok().map(|_| backup_key);
+            log::error!("load {key} error:{e:?}, the data is kept in {backup_key:?}");
+            LOAD_ERRORS.with(|errors| {
+                errors.borrow_mut().push(LoadError {
+                    key,
+                    backup_key,
+                    error: e.to_string(),
+                })
+            });
+            T::default()
+        }
+    }
+}
+
 impl<T> LoadStore for T
 where
     T: LoadStoreKey + Default,
@@ -25,24 +233,156 @@ where
     where
         for<'de> Self: Deserialize<'de>,
     {
-        log::info!("load:{}", Self::load_store_key());
-        let ret = if let Ok(data) = LocalStorage::get::<Self>(Self::load_store_key()) {
-            data
-        } else {
-            Self::default()
-        };
-        ret
+        load_from(&*storage())
     }
 
     fn store(&self)
+    where
+        Self: Serialize + Clone,
+    {
+        let data = self.clone();
+        PENDING_STORES.with(|pending| {
+            pending
+                .borrow_mut()
+                .insert(Self::load_store_key(), Box::new(move || data.store_now()))
+        });
+        let scheduled = STORE_SCHEDULED.with(|scheduled| scheduled.replace(true));
+        if !scheduled {
+            Timeout::new(STORE_DEBOUNCE_MS, flush_stores).forget();
+        }
+    }
+
+    fn store_now(&self) -> Result<(), StoreError>
     where
         Self: Serialize,
     {
-        log::info!(
-            "store:{}-{}",
-            Self::load_store_key(),
-            json!(self).to_string()
+        let key = Self::load_store_key();
+        let data = encode_store(self)
+            .and_then(|value| serde_json::to_string(&value))
+            .map_err(|e| StoreError::Serialize(key.to_owned(), e.to_string()))?;
+        // the value may contain the secret key, only log its size
+        log::info!("store:{}-{} bytes", key, data.len());
+        storage().set(key, &data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ctxs::MemoryBackend;
+
+    // version 1 had `name`, version 2 renamed it to `title`
+    #[derive(Debug, Default, PartialEq, Deserialize)]
+    struct Note {
+        title: String,
+    }
+
+    impl LoadStoreKey for Note {
+        fn load_store_key() -> &'static str {
+            "note"
+        }
+
+        fn schema_version() -> u32 {
+            2
+        }
+
+        fn migrate(version: u32, mut data: Value) -> anyhow::Result<Value> {
+            if version == 1 {
+                let name = data
+                    .as_object_mut()
+                    .and_then(|obj| obj.remove("name"))
+                    .ok_or_else(|| anyhow::anyhow!("no name"))?;
+                data = json!({ "title": name });
+            }
+            Ok(data)
+        }
+    }
+
+    #[test]
+    fn decode_current_version() {
+        let note = decode::<Note>(r#"{"version":2,"data":{"title":"a"}}"#).unwrap();
+        assert_eq!(note.title, "a");
+    }
+
+    #[test]
+    fn decode_migrates_old_versions() {
+        let note = decode::<Note>(r#"{"version":1,"data":{"name":"a"}}"#).unwrap();
+        assert_eq!(note.title, "a");
+        // unversioned, stored before the versioning: 0 -> 1 -> 2
+        let note = decode::<Note>(r#"{"name":"a"}"#).unwrap();
+        assert_eq!(note.title, "a");
+        assert!(decode::<Note>(r#"{"version":1,"data":{"title":"a"}}"#).is_err());
+    }
+
+    #[test]
+    fn decode_rejects_newer_version() {
+        let err = decode::<Note>(r#"{"version":3,"data":{"title":"a"}}"#).unwrap_err();
+        assert!(err.to_string().contains("newer version"));
+        assert!(decode::<Note>(r#"{"version":"x","data":{}}"#).is_err());
+    }
+
+    // the stores before the versioning, in the layouts they had then
+    #[test]
+    fn decode_unversioned_stores() {
+        use crate::ctxs::{Relay, User, UserContact};
+        use nostr_sdk::nostr::{prelude::FromSkStr, Keys};
+
+        let sk = "0101010101010101010101010101010101010101010101010101010101010101";
+        let pk = Keys::from_sk_str(sk).unwrap().public_key().to_string();
+        let raw = format!(r#"{{"keys":"{sk}","nick_name":"test","avatar_url":""}}"#);
+        let user = decode::<User>(&raw).unwrap();
+        assert_eq!(
+            user.keys.map(|keys| keys.public_key().to_string()),
+            Some(pk.clone())
+        );
+        assert_eq!(user.nick_name, "test");
+        let raw = r#"{"keys":"","nick_name":"","avatar_url":""}"#;
+        assert!(decode::<User>(raw).unwrap().keys.is_none());
+
+        let raw = format!(
+            r#"{{"data":{{"{pk}":{{"pubkey":"{pk}","avatar_url":null,"relay":"wss://relay.damus.io/","nickname":"lhy1"}}}},"recent":["{pk}"]}}"#
         );
-        LocalStorage::set(Self::load_store_key(), self).expect("store error ")
+        let contacts = decode::<UserContact>(&raw).unwrap();
+        let data = contacts.data.borrow();
+        let info = data.values().next().unwrap();
+        assert_eq!(info.nickname.as_deref(), Some("lhy1"));
+        assert!(info.nip05.is_none());
+        assert_eq!(contacts.recent.borrow().len(), 1);
+        assert!(contacts.groups.borrow().is_empty());
+
+        let raw =
+            r#"{"list":{"0":{"id":0,"uri":"wss://relay.damus.io/"},"1":{"id":1,"uri":null}}}"#;
+        let relays = decode::<Relay>(raw).unwrap();
+        let list = relays.list.borrow();
+        assert_eq!(list.len(), 2);
+        assert_eq!(
+            list[&0].uri.as_ref().map(|uri| uri.as_str()),
+            Some("wss://relay.damus.io/")
+        );
+        assert!(!list[&0].auth);
+        // the same data with the version 1
+        let versioned = format!(r#"{{"version":1,"data":{raw}}}"#);
+        assert_eq!(decode::<Relay>(&versioned).unwrap().list.borrow().len(), 2);
+    }
+
+    #[test]
+    fn load_keeps_unreadable_data() {
+        dismiss_load_errors();
+        let storage = MemoryBackend::default();
+        assert_eq!(load_from::<Note>(&storage), Note::default());
+        assert!(load_errors().is_empty());
+
+        let raw = r#"{"version":3,"data":{"title":"a"}}"#;
+        storage.set("note", raw).unwrap();
+        assert_eq!(load_from::<Note>(&storage), Note::default());
+        let errors = load_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].key, "note");
+        let backup_key = errors[0].backup_key.clone().unwrap();
+        assert!(backup_key.starts_with("note.unreadable."));
+        assert_eq!(storage.get(&backup_key).as_deref(), Some(raw));
+        // the original is left for the next store
+        assert_eq!(storage.get("note").as_deref(), Some(raw));
+        dismiss_load_errors();
     }
 }
@@ -0,0 +1,61 @@
+// 上传图片和视频的服务器: NIP-96 或 Blossom
+use crate::ctxs::*;
+
+use nostr_sdk::nostr::Url;
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+use yew::Reducible;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MediaServerKind {
+    // the api url is in `/.well-known/nostr/nip96.json`
+    #[default]
+    Nip96,
+    // `PUT /upload` of BUD-02
+    Blossom,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MediaServer {
+    pub kind: MediaServerKind,
+    // None disables the upload
+    pub url: Option<Url>,
+    #[serde(skip)]
+    count: u64,
+}
+
+impl PartialEq for MediaServer {
+    fn eq(&self, other: &Self) -> bool {
+        self.count == other.count
+    }
+}
+
+impl LoadStoreKey for MediaServer {
+    fn load_store_key() -> &'static str {
+        "media_server"
+    }
+}
+
+pub enum MediaServerAction {
+    SetKind(MediaServerKind),
+    SetUrl(Option<Url>),
+}
+
+impl Reducible for MediaServer {
+    type Action = MediaServerAction;
+
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        let count = self.count.checked_add(1).or(Some(0)).unwrap();
+        let mut kind = self.kind;
+        let mut url = self.url.clone();
+        match action {
+            MediaServerAction::SetKind(v) => kind = v,
+            MediaServerAction::SetUrl(v) => url = v,
+        }
+        let ret = Rc::new(Self { kind, url, count });
+        ret.store();
+        ret
+    }
+}
+
+pub type MediaServerContext = yew::UseReducerHandle<MediaServer>;
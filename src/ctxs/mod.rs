@@ -1,16 +1,40 @@
+mod clock_ctx;
 mod contact_ctx;
+mod content_warning_ctx;
+mod draft_ctx;
 mod event_ctx;
 mod i18n_ctx;
 mod load_store;
+mod media_ctx;
+mod mute_ctx;
+mod notification_ctx;
+mod pow_ctx;
 mod relay_ctx;
+mod relay_status_ctx;
+mod schedule_ctx;
+mod search_ctx;
 mod send_msg_ctx;
+mod storage;
 mod subscription_ctx;
+mod theme_ctx;
 mod user_ctx;
+pub use clock_ctx::*;
 pub use contact_ctx::*;
+pub use content_warning_ctx::*;
+pub use draft_ctx::*;
 pub use event_ctx::*;
 pub use i18n_ctx::*;
 pub use load_store::*;
+pub use media_ctx::*;
+pub use mute_ctx::*;
+pub use notification_ctx::*;
+pub use pow_ctx::*;
 pub use relay_ctx::*;
+pub use relay_status_ctx::*;
+pub use schedule_ctx::*;
+pub use search_ctx::*;
 pub use send_msg_ctx::*;
+pub use storage::*;
 pub use subscription_ctx::*;
+pub use theme_ctx::*;
 pub use user_ctx::*;
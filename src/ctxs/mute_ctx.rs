@@ -0,0 +1,343 @@
+use crate::ctxs::*;
+
+use nostr_sdk::nostr::nips::nip04;
+use nostr_sdk::nostr::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::str::FromStr;
+use yew::Reducible;
+
+// NIP-51 mute list
+pub const MUTE_LIST_KIND: Kind = Kind::Replaceable(10000);
+const WORD_TAG: &str = "word";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MuteItem {
+    PubKey(XOnlyPublicKey),
+    Event(EventId),
+    Hashtag(String),
+    Word(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MuteItemKind {
+    PubKey,
+    Event,
+    Hashtag,
+    Word,
+}
+
+impl MuteItem {
+    // parse the user input, public keys and event ids are in bech32 or hex
+    pub fn parse(kind: MuteItemKind, input: &str) -> Option<Self> {
+        let input = input.trim();
+        match kind {
+            MuteItemKind::PubKey => XOnlyPublicKey::from_bech32(input)
+                .ok()
+                .or_else(|| XOnlyPublicKey::from_str(input).ok())
+                .map(Self::PubKey),
+            MuteItemKind::Event => EventId::from_bech32(input)
+                .ok()
+                .or_else(|| EventId::from_hex(input).ok())
+                .map(Self::Event),
+            MuteItemKind::Hashtag => {
+                let tag = input.trim_start_matches('#').to_lowercase();
+                (!tag.is_empty()).then_some(Self::Hashtag(tag))
+            }
+            MuteItemKind::Word => (!input.is_empty()).then_some(Self::Word(input.to_lowercase())),
+        }
+    }
+
+    pub fn kind(&self) -> MuteItemKind {
+        match self {
+            Self::PubKey(_) => MuteItemKind::PubKey,
+            Self::Event(_) => MuteItemKind::Event,
+            Self::Hashtag(_) => MuteItemKind::Hashtag,
+            Self::Word(_) => MuteItemKind::Word,
+        }
+    }
+
+    fn from_tag(tag: Tag) -> Option<Self> {
+        match tag {
+            Tag::PubKey(pk, _) => Some(Self::PubKey(pk)),
+            Tag::Event(id, _, _) => Some(Self::Event(id)),
+            Tag::Hashtag(tag) => Some(Self::Hashtag(tag.to_lowercase())),
+            Tag::Generic(TagKind::Custom(kind), values) if kind == WORD_TAG => values
+                .into_iter()
+                .next()
+                .map(|word| Self::Word(word.to_lowercase())),
+            _ => None,
+        }
+    }
+
+    fn to_tag(&self) -> Tag {
+        match self {
+            Self::PubKey(pk) => Tag::PubKey(*pk, None),
+            Self::Event(id) => Tag::Event(*id, None, None),
+            Self::Hashtag(tag) => Tag::Hashtag(tag.clone()),
+            Self::Word(word) => {
+                Tag::Generic(TagKind::Custom(WORD_TAG.to_owned()), vec![word.clone()])
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for MuteItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PubKey(pk) => match pk.to_bech32() {
+                Ok(npub) => write!(f, "{npub}"),
+                Err(_) => write!(f, "{pk}"),
+            },
+            Self::Event(id) => match id.to_bech32() {
+                Ok(note) => write!(f, "{note}"),
+                Err(_) => write!(f, "{id}"),
+            },
+            Self::Hashtag(tag) => write!(f, "#{tag}"),
+            Self::Word(word) => write!(f, "{word}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MuteEntry {
+    pub item: MuteItem,
+    pub private: bool, // 私有的条目加密给自己, 只有自己可见
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserMute {
+    pub entries: Rc<RefCell<Vec<MuteEntry>>>,
+    pub updated_at: u64, // created_at of the list on relays, or the last local edit
+    #[serde(default)]
+    pub owner: Option<XOnlyPublicKey>, // 列表所属的账号, 切换账号时清空
+    #[serde(skip)]
+    pub publish: bool, // the local list is newer than the one on relays
+    #[serde(skip)]
+    count: u64,
+}
+
+impl PartialEq for UserMute {
+    fn eq(&self, other: &Self) -> bool {
+        self.count == other.count
+    }
+}
+
+impl LoadStoreKey for UserMute {
+    fn load_store_key() -> &'static str {
+        "mute-list"
+    }
+}
+
+impl UserMute {
+    pub fn is_muted(&self, event: &Event) -> bool {
+        let entries = self.entries.borrow();
+        if entries.is_empty() {
+            return false;
+        }
+        let content = event.content.to_lowercase();
+        entries.iter().any(|entry| match &entry.item {
+            MuteItem::PubKey(pk) => event.pubkey == *pk,
+            // the event itself, and the replies, reactions and reposts of it
+            MuteItem::Event(id) => {
+                event.id == *id
+                    || event
+                        .tags
+                        .iter()
+                        .any(|t| matches!(t, Tag::Event(e, _, _) if e == id))
+            }
+            MuteItem::Hashtag(tag) => {
+                event
+                    .tags
+                    .iter()
+                    .any(|t| matches!(t, Tag::Hashtag(t) if t.to_lowercase() == *tag))
+                    || content
+                        .split(|c: char| c.is_whitespace())
+                        .any(|w| w.strip_prefix('#') == Some(tag.as_str()))
+            }
+            MuteItem::Word(word) => content.contains(word.as_str()),
+        })
+    }
+
+    // kind 10000: the public entries in tags, the private ones in the nip04 encrypted content
+    pub fn to_event(&self, keys: &Keys) -> anyhow::Result<Event> {
+        let entries = self.entries.borrow();
+        let tags = entries
+            .iter()
+            .filter(|entry| !entry.private)
+            .map(|entry| entry.item.to_tag())
+            .collect::<Vec<_>>();
+        let private = entries
+            .iter()
+            .filter(|entry| entry.private)
+            .map(|entry| entry.item.to_tag().as_vec())
+            .collect::<Vec<_>>();
+        let content = if private.is_empty() {
+            String::new()
+        } else {
+            nip04::encrypt(
+                &keys.secret_key()?,
+                &keys.public_key(),
+                serde_json::to_string(&private)?,
+            )?
+        };
+        Ok(EventBuilder::new(MUTE_LIST_KIND, content, &tags).to_event(keys)?)
+    }
+
+    // the entries, and whether the private ones could be decrypted
+    pub fn entries_of_event(event: &Event, keys: &Keys) -> (Vec<MuteEntry>, bool) {
+        let mut entries = event
+            .tags
+            .iter()
+            .cloned()
+            .filter_map(MuteItem::from_tag)
+            .map(|item| MuteEntry {
+                item,
+                private: false,
+            })
+            .collect::<Vec<_>>();
+        let mut decrypted = true;
+        if !event.content.is_empty() {
+            let private = keys
+                .secret_key()
+                .map_err(anyhow::Error::from)
+                .and_then(|sk| Ok(nip04::decrypt(&sk, &keys.public_key(), &event.content)?))
+                .and_then(|json| Ok(serde_json::from_str::<Vec<Vec<String>>>(&json)?));
+            match private {
+                Ok(tags) => entries.extend(
+                    tags.into_iter()
+                        .filter_map(|tag| Tag::parse(tag).ok())
+                        .filter_map(MuteItem::from_tag)
+                        .map(|item| MuteEntry {
+                            item,
+                            private: true,
+                        }),
+                ),
+                Err(e) => {
+                    log::warn!("decrypt private mute list error:{e:?}");
+                    decrypted = false;
+                }
+            }
+        }
+        (entries, decrypted)
+    }
+}
+
+pub enum UserMuteAction {
+    Add(MuteItem, bool),
+    Remove(MuteItem),
+    TogglePrivate(MuteItem),
+    // the latest list from relays: its author, entries, created_at,
+    // and whether its private entries were decrypted
+    Remote(XOnlyPublicKey, Vec<MuteEntry>, u64, bool),
+    // the account logged in, the list of another one is dropped
+    Owner(XOnlyPublicKey),
+    // restore from a backup, replace or merge
+    Restore(Vec<MuteEntry>, bool),
+}
+
+impl Reducible for UserMute {
+    type Action = UserMuteAction;
+
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        use UserMuteAction::*;
+        let count = self.count.checked_add(1).or(Some(0)).unwrap();
+        let mut updated_at = Timestamp::now().as_u64().max(self.updated_at + 1);
+        let publish;
+        let mut owner = self.owner;
+        match action {
+            Add(item, private) => {
+                let mut entries = self.entries.borrow_mut();
+                if let Some(entry) = entries.iter_mut().find(|entry| entry.item == item) {
+                    entry.private = private;
+                } else {
+                    entries.push(MuteEntry { item, private });
+                }
+                publish = true;
+            }
+            Remove(item) => {
+                self.entries.borrow_mut().retain(|entry| entry.item != item);
+                publish = true;
+            }
+            TogglePrivate(item) => {
+                if let Some(entry) = self
+                    .entries
+                    .borrow_mut()
+                    .iter_mut()
+                    .find(|entry| entry.item == item)
+                {
+                    entry.private = !entry.private;
+                }
+                publish = true;
+            }
+            Owner(pk) => {
+                if self.owner == Some(pk) {
+                    return self;
+                }
+                if self.owner.is_some() {
+                    self.entries.borrow_mut().clear();
+                    updated_at = 0;
+                } else {
+                    // stored before the owner was, by the account logged in
+                    updated_at = self.updated_at;
+                }
+                owner = Some(pk);
+                publish = false;
+            }
+            Restore(restored, merge) => {
+                let mut entries = self.entries.borrow_mut();
+                if !merge {
+                    entries.clear();
+                }
+                for entry in restored {
+                    if !entries.iter().any(|e| e.item == entry.item) {
+                        entries.push(entry);
+                    }
+                }
+                publish = true;
+            }
+            Remote(pk, entries, created_at, decrypted) => {
+                if self.owner != Some(pk) {
+                    // never compared with the list of another account
+                    *self.entries.borrow_mut() = entries;
+                    owner = Some(pk);
+                    updated_at = created_at;
+                    publish = false;
+                } else if !decrypted {
+                    // not the whole list, the public entries are taken and the private ones kept
+                    let mut local = self.entries.borrow_mut();
+                    if created_at >= self.updated_at {
+                        local.retain(|entry| entry.private);
+                        for entry in entries {
+                            if !local.iter().any(|e| e.item == entry.item) {
+                                local.push(entry);
+                            }
+                        }
+                    }
+                    updated_at = self.updated_at;
+                    publish = false;
+                } else if created_at >= self.updated_at {
+                    *self.entries.borrow_mut() = entries;
+                    updated_at = created_at;
+                    publish = false;
+                } else {
+                    // edited on this device after the one on relays
+                    updated_at = self.updated_at;
+                    publish = true;
+                }
+            }
+        }
+        let ret = Rc::new(Self {
+            entries: self.entries.clone(),
+            updated_at,
+            owner,
+            publish,
+            count,
+        });
+        ret.store();
+        ret
+    }
+}
+
+pub type UserMuteContext = yew::UseReducerHandle<UserMute>;
@@ -0,0 +1,276 @@
+use crate::ctxs::*;
+
+use indexmap::IndexMap;
+use nostr_sdk::nostr::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use yew::Reducible;
+
+// 最多保留的通知分组个数
+const MAX_NOTIFICATION_GROUPS: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NotificationKind {
+    Mention,
+    Reply,
+    Reaction,
+    Repost,
+}
+
+impl NotificationKind {
+    // classify an event which tags the user, return the kind and the target note
+    pub fn classify(event: &Event) -> Option<(Self, Option<EventId>)> {
+        let e_tags = event
+            .tags
+            .iter()
+            .filter_map(|t| match t {
+                Tag::Event(id, _, marker) => Some((*id, marker.clone())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        match event.kind {
+            Kind::Reaction => Some((Self::Reaction, e_tags.last().map(|(id, _)| *id))),
+            Kind::Repost => Some((Self::Repost, e_tags.first().map(|(id, _)| *id))),
+            Kind::TextNote => {
+                if e_tags.is_empty() {
+                    Some((Self::Mention, None))
+                } else {
+                    // NIP-10: the `reply` marker first, or the last `e` tag
+                    let target = e_tags
+                        .iter()
+                        .find(|(_, marker)| *marker == Some(Marker::Reply))
+                        .or(e_tags.last())
+                        .map(|(id, _)| *id);
+                    Some((Self::Reply, target))
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+pub type NotificationGroupKey = (NotificationKind, EventId);
+
+#[derive(Debug, Clone)]
+pub struct NotificationGroup {
+    pub kind: NotificationKind,
+    pub target: Option<EventId>,
+    pub events: Vec<Event>, // the latest is in the front
+}
+
+impl NotificationGroup {
+    pub fn latest(&self) -> Timestamp {
+        self.events
+            .first()
+            .map(|e| e.created_at)
+            .unwrap_or(Timestamp::from(0))
+    }
+
+    // distinct authors of the group, the latest is in the front
+    pub fn authors(&self) -> Vec<XOnlyPublicKey> {
+        let mut authors = Vec::with_capacity(self.events.len());
+        for e in &self.events {
+            if !authors.contains(&e.pubkey) {
+                authors.push(e.pubkey);
+            }
+        }
+        authors
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserNotification {
+    // all notifications created before are read
+    pub read_until: u64,
+    // the notifications read one by one after `read_until`, with their created_at,
+    // dropped once `read_until` passes them
+    pub read_ids: Rc<RefCell<HashMap<EventId, u64>>>,
+    #[serde(skip)]
+    pub groups: Rc<RefCell<IndexMap<NotificationGroupKey, NotificationGroup>>>,
+    #[serde(skip)]
+    count: u64,
+}
+
+impl PartialEq for UserNotification {
+    fn eq(&self, other: &Self) -> bool {
+        self.count == other.count
+    }
+}
+
+impl LoadStoreKey for UserNotification {
+    fn load_store_key() -> &'static str {
+        "notifications"
+    }
+
+    fn schema_version() -> u32 {
+        2
+    }
+
+    // `read_ids` was a list, the time of the old ones is unknown and they're kept until all are read
+    fn migrate(version: u32, mut data: Value) -> anyhow::Result<Value> {
+        if version <= 1 {
+            if let Some(ids) = data.get_mut("read_ids") {
+                if let Value::Array(list) = ids.take() {
+                    *ids = Value::Object(
+                        list.into_iter()
+                            .filter_map(|id| id.as_str().map(|id| (id.to_owned(), u64::MAX.into())))
+                            .collect(),
+                    );
+                }
+            }
+        }
+        Ok(data)
+    }
+}
+
+impl UserNotification {
+    pub fn is_read(&self, event: &Event) -> bool {
+        event.created_at.as_u64() <= self.read_until
+            || self.read_ids.borrow().contains_key(&event.id)
+    }
+
+    pub fn is_group_read(&self, group: &NotificationGroup) -> bool {
+        group.events.iter().all(|e| self.is_read(e))
+    }
+
+    pub fn unread(&self) -> usize {
+        self.groups
+            .borrow()
+            .values()
+            .flat_map(|g| g.events.iter())
+            .filter(|e| !self.is_read(e))
+            .count()
+    }
+}
+
+pub enum UserNotificationAction {
+    Add(Event),
+    ReadGroup(NotificationGroupKey),
+    ReadAll,
+    RemoveEvents(HashSet<EventId>),
+    // restore from a backup, the notifications before it are read
+    Restore(u64),
+    // read in another tab
+    Synced(u64, HashMap<EventId, u64>),
+    Clear,
+}
+
+impl Reducible for UserNotification {
+    type Action = UserNotificationAction;
+
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        use UserNotificationAction::*;
+        let count = self.count.checked_add(1).or(Some(0)).unwrap();
+        let mut read_until = self.read_until;
+        let mut store = false;
+        match action {
+            Add(event) => {
+                let (kind, target) = if let Some(v) = NotificationKind::classify(&event) {
+                    v
+                } else {
+                    return self;
+                };
+                let key = (kind, target.unwrap_or(event.id));
+                let mut groups = self.groups.borrow_mut();
+                if let Some(group) = groups.get_mut(&key) {
+                    if group.events.iter().any(|e| e.id == event.id) {
+                        return self.clone();
+                    }
+                    group.events.push(event);
+                    group
+                        .events
+                        .sort_by(|e1, e2| e2.created_at.as_u64().cmp(&e1.created_at.as_u64()));
+                } else {
+                    groups.insert(
+                        key,
+                        NotificationGroup {
+                            kind,
+                            target,
+                            events: vec![event],
+                        },
+                    );
+                }
+                groups.sort_by(|_, v1, _, v2| v2.latest().as_u64().cmp(&v1.latest().as_u64()));
+                groups.truncate(MAX_NOTIFICATION_GROUPS);
+            }
+            ReadGroup(key) => {
+                if let Some(group) = self.groups.borrow().get(&key) {
+                    let mut read_ids = self.read_ids.borrow_mut();
+                    for e in &group.events {
+                        if e.created_at.as_u64() > read_until {
+                            read_ids.insert(e.id, e.created_at.as_u64());
+                        }
+                    }
+                }
+                store = true;
+            }
+            ReadAll => {
+                read_until = self
+                    .groups
+                    .borrow()
+                    .values()
+                    .map(|g| g.latest().as_u64())
+                    .max()
+                    .unwrap_or(read_until)
+                    .max(read_until);
+                self.read_ids.borrow_mut().clear();
+                store = true;
+            }
+            Restore(restored) => {
+                read_until = read_until.max(restored);
+                store = true;
+            }
+            Synced(until, ids) => {
+                read_until = read_until.max(until);
+                self.read_ids.borrow_mut().extend(ids);
+                store = true;
+            }
+            RemoveEvents(ids) => {
+                let mut groups = self.groups.borrow_mut();
+                for group in groups.values_mut() {
+                    group.events.retain(|e| !ids.contains(&e.id));
+                }
+                groups.retain(|_, group| !group.events.is_empty());
+            }
+            Clear => {
+                self.groups.borrow_mut().clear();
+            }
+        }
+        if store {
+            self.read_ids
+                .borrow_mut()
+                .retain(|_, created_at| *created_at > read_until);
+        }
+        let ret = Rc::new(Self {
+            read_until,
+            read_ids: self.read_ids.clone(),
+            groups: self.groups.clone(),
+            count,
+        });
+        if store {
+            ret.store();
+        }
+        ret
+    }
+}
+
+pub type UserNotificationContext = yew::UseReducerHandle<UserNotification>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn migrate_read_ids_list() {
+        let id = EventId::from_slice(&[1; 32]).unwrap();
+        let data = json!({ "read_until": 5, "read_ids": [id.to_hex()] });
+        let migrated = UserNotification::migrate(1, data).unwrap();
+        let notification = serde_json::from_value::<UserNotification>(migrated).unwrap();
+        assert_eq!(notification.read_until, 5);
+        assert_eq!(notification.read_ids.borrow().get(&id), Some(&u64::MAX));
+    }
+}
@@ -0,0 +1,127 @@
+// NIP-13 工作量证明: 发送的目标难度(全局和每个relay), 以及接收事件的最小难度
+use crate::ctxs::*;
+
+use nostr_sdk::nostr::nips::nip13::get_leading_zero_bits;
+use nostr_sdk::nostr::{Event, Tag, Url};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use yew::Reducible;
+
+// more takes too long in the browser
+pub const MAX_POW_DIFFICULTY: u8 = 32;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Pow {
+    // the target of all the outgoing events
+    pub difficulty: u8,
+    // the events with less are dropped, 0 keeps all
+    pub min_difficulty: u8,
+    // set by the user, overrides the learned one
+    pub relays: Rc<RefCell<HashMap<Url, u8>>>,
+    // `limitation.min_pow_difficulty` of NIP-11
+    #[serde(skip)]
+    pub learned: Rc<RefCell<HashMap<Url, u8>>>,
+    #[serde(skip)]
+    count: u64,
+}
+
+impl PartialEq for Pow {
+    fn eq(&self, other: &Self) -> bool {
+        self.count == other.count
+    }
+}
+
+impl LoadStoreKey for Pow {
+    fn load_store_key() -> &'static str {
+        "pow"
+    }
+}
+
+pub enum PowAction {
+    SetDifficulty(u8),
+    SetMinDifficulty(u8),
+    // None uses the learned one
+    SetRelay(Url, Option<u8>),
+    Learned(Url, u8),
+}
+
+impl Pow {
+    pub fn relay_difficulty(&self, uri: &Url) -> u8 {
+        let custom = self.relays.borrow().get(uri).copied();
+        custom
+            .or_else(|| self.learned.borrow().get(uri).copied())
+            .unwrap_or(0)
+    }
+
+    // enough for all the relays the event is sent to
+    pub fn target<'a>(&self, relays: impl Iterator<Item = &'a Url>) -> u8 {
+        relays
+            .map(|uri| self.relay_difficulty(uri))
+            .fold(self.difficulty, u8::max)
+            .min(MAX_POW_DIFFICULTY)
+    }
+
+    pub fn is_enough(&self, event: &Event) -> bool {
+        self.min_difficulty == 0 || pow_difficulty(event) >= self.min_difficulty
+    }
+}
+
+// the leading zero bits of the id, but no more than the committed target of the nonce tag,
+// so a lucky low target event doesn't pass
+pub fn pow_difficulty(event: &Event) -> u8 {
+    let bits = get_leading_zero_bits(event.id.inner());
+    let committed = event.tags.iter().find_map(|tag| match tag {
+        Tag::POW { difficulty, .. } => Some(*difficulty),
+        _ => None,
+    });
+    match committed {
+        Some(committed) => bits.min(committed),
+        None => 0,
+    }
+}
+
+impl Reducible for Pow {
+    type Action = PowAction;
+
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        use PowAction::*;
+        let count = self.count.checked_add(1).or(Some(0)).unwrap();
+        let mut difficulty = self.difficulty;
+        let mut min_difficulty = self.min_difficulty;
+        let mut store = true;
+        match action {
+            SetDifficulty(v) => difficulty = v.min(MAX_POW_DIFFICULTY),
+            SetMinDifficulty(v) => min_difficulty = v,
+            SetRelay(uri, Some(v)) => {
+                self.relays
+                    .borrow_mut()
+                    .insert(uri, v.min(MAX_POW_DIFFICULTY));
+            }
+            SetRelay(uri, None) => {
+                self.relays.borrow_mut().remove(&uri);
+            }
+            Learned(uri, v) => {
+                if self.learned.borrow().get(&uri) == Some(&v) {
+                    return self;
+                }
+                self.learned.borrow_mut().insert(uri, v);
+                store = false;
+            }
+        }
+        let ret = Rc::new(Self {
+            difficulty,
+            min_difficulty,
+            relays: self.relays.clone(),
+            learned: self.learned.clone(),
+            count,
+        });
+        if store {
+            ret.store();
+        }
+        ret
+    }
+}
+
+pub type PowContext = yew::UseReducerHandle<Pow>;
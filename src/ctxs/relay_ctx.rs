@@ -25,6 +25,9 @@ impl PartialEq for Relay {
 pub struct RelayInfo {
     pub id: usize,
     pub uri: Option<Url>,
+    // NIP-42, answer the AUTH challenges of the relay
+    #[serde(default)]
+    pub auth: bool,
 }
 
 pub enum RelayAction {
@@ -32,6 +35,9 @@ pub enum RelayAction {
     RemoveBatch(HashSet<usize>),
     Add(Url),
     Update(usize, Url),
+    SetAuth(usize, bool),
+    // restore from a backup, replace or merge
+    Restore(Vec<Url>, bool),
 }
 impl LoadStoreKey for Relay {
     fn load_store_key() -> &'static str {
@@ -76,16 +82,27 @@ impl RelayInfo {
         Self {
             id: Self::generate_id(),
             uri,
+            auth: false,
         }
     }
     pub fn empty() -> Self {
         Self {
             id: Self::generate_id(),
             uri: None,
+            auth: false,
         }
     }
 }
 
+impl Relay {
+    pub fn auth_enabled(&self, uri: &Url) -> bool {
+        self.list
+            .borrow()
+            .values()
+            .any(|info| info.auth && info.uri.as_ref() == Some(uri))
+    }
+}
+
 // 最大relay个数
 const MAX_RELAY_SIZE: usize = 50;
 
@@ -117,15 +134,41 @@ impl Reducible for Relay {
                 list.borrow_mut().insert(relay.id, relay);
                 list
             }
+            Restore(relays, merge) => {
+                let list = self.list.clone();
+                if !merge {
+                    list.borrow_mut().clear();
+                }
+                for relay in relays {
+                    let exists = list
+                        .borrow()
+                        .values()
+                        .any(|info| info.uri.as_ref() == Some(&relay));
+                    if !exists && list.borrow().len() < MAX_RELAY_SIZE {
+                        let relay = RelayInfo::new2(Some(relay));
+                        list.borrow_mut().insert(relay.id, relay);
+                    }
+                }
+                list
+            }
             Update(i, relay) => {
                 let list = self.list.clone();
+                let auth = list.borrow().get(&i).map(|info| info.auth).unwrap_or(false);
                 let relay_info = RelayInfo {
                     id: i,
                     uri: Some(relay),
+                    auth,
                 };
                 list.borrow_mut().insert(i, relay_info);
                 list
             }
+            SetAuth(i, auth) => {
+                let list = self.list.clone();
+                if let Some(info) = list.borrow_mut().get_mut(&i) {
+                    info.auth = auth;
+                }
+                list
+            }
         };
         count = count.checked_add(1).or(Some(0)).unwrap();
         list.borrow_mut().sort_by(|_, v1, _, v2| v2.id.cmp(&v1.id));
@@ -0,0 +1,104 @@
+// relay的连接状态, 只在内存里: NIP-42 认证, NOTICE, 订阅是否已经收到EOSE
+use nostr_sdk::nostr::{Timestamp, Url};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+use yew::Reducible;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum AuthState {
+    #[default]
+    None,
+    // challenged, but the auth isn't enabled for the relay
+    Required,
+    Authenticating,
+    Authenticated,
+    // the reason from the relay
+    Failed(String),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RelayState {
+    pub auth: AuthState,
+    // the latest is in the front
+    pub notices: VecDeque<(Timestamp, String)>,
+    // the stored events are received, the new ones come from now on
+    pub live: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RelayStatus {
+    pub relays: Rc<RefCell<HashMap<Url, RelayState>>>,
+    count: u64,
+}
+
+impl PartialEq for RelayStatus {
+    fn eq(&self, other: &Self) -> bool {
+        self.count == other.count
+    }
+}
+
+pub enum RelayStatusAction {
+    Auth(Url, AuthState),
+    Notice(Url, String),
+    Live(Url),
+    // subscribed again, loading the stored events
+    Loading(Url),
+    Remove(Url),
+    Clear,
+}
+
+impl RelayStatus {
+    const MAX_NOTICES: usize = 20;
+
+    // any relay sent all the stored events
+    pub fn is_live(&self) -> bool {
+        self.relays.borrow().values().any(|state| state.live)
+    }
+
+    pub fn auth(&self, uri: &Url) -> AuthState {
+        self.relays
+            .borrow()
+            .get(uri)
+            .map(|state| state.auth.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Reducible for RelayStatus {
+    type Action = RelayStatusAction;
+
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        use RelayStatusAction::*;
+        let count = self.count.checked_add(1).or(Some(0)).unwrap();
+        match action {
+            Auth(uri, auth) => {
+                self.relays.borrow_mut().entry(uri).or_default().auth = auth;
+            }
+            Notice(uri, message) => {
+                let mut relays = self.relays.borrow_mut();
+                let notices = &mut relays.entry(uri).or_default().notices;
+                notices.push_front((Timestamp::now(), message));
+                notices.truncate(Self::MAX_NOTICES);
+            }
+            Live(uri) => {
+                self.relays.borrow_mut().entry(uri).or_default().live = true;
+            }
+            Loading(uri) => {
+                self.relays.borrow_mut().entry(uri).or_default().live = false;
+            }
+            Remove(uri) => {
+                self.relays.borrow_mut().remove(&uri);
+            }
+            Clear => {
+                self.relays.borrow_mut().clear();
+            }
+        }
+        Rc::new(Self {
+            relays: self.relays.clone(),
+            count,
+        })
+    }
+}
+
+pub type RelayStatusContext = yew::UseReducerHandle<RelayStatus>;
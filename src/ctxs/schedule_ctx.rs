@@ -0,0 +1,186 @@
+// 定时发送的消息, 保存在本地, 到时间后由AppClient发送
+use crate::ctxs::*;
+
+use nostr_sdk::nostr::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+use yew::Reducible;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledNote {
+    // local id, the time in milliseconds when it's queued
+    pub id: u64,
+    pub pubkey: XOnlyPublicKey,
+    pub content: String,
+    pub tags: Vec<Tag>,
+    pub publish_at: Timestamp,
+    // signed when it's queued, otherwise when it's published by the account of `pubkey`
+    pub signed: Option<Event>,
+    // passed while the app wasn't running, or the publish failed, waiting for the user
+    #[serde(default)]
+    pub missed: bool,
+    #[serde(default)]
+    pub error: Option<String>,
+    // sent to the relays and waiting for the result, it's kept in the queue until then
+    #[serde(default)]
+    pub sending: bool,
+}
+
+impl ScheduledNote {
+    // created at the scheduled time, so the id is the same whenever and wherever it's signed,
+    // and relays drop the copy published by another tab
+    pub fn sign(&self, keys: &Keys) -> Option<Event> {
+        if keys.public_key() != self.pubkey {
+            return None;
+        }
+        let kind = Kind::TextNote;
+        let unsigned = UnsignedEvent {
+            id: EventId::new(
+                &self.pubkey,
+                self.publish_at,
+                &kind,
+                &self.tags,
+                &self.content,
+            ),
+            pubkey: self.pubkey,
+            created_at: self.publish_at,
+            kind,
+            tags: self.tags.clone(),
+            content: self.content.clone(),
+        };
+        match unsigned.sign(keys) {
+            Ok(event) => Some(event),
+            Err(e) => {
+                log::warn!("sign scheduled note error:{e:?}");
+                None
+            }
+        }
+    }
+
+    // the signed one, or sign it now with the keys of its account
+    pub fn event(&self, keys: &Keys) -> Option<Event> {
+        self.signed.clone().or_else(|| self.sign(keys))
+    }
+
+    pub fn is_due(&self, now: Timestamp) -> bool {
+        !self.missed && !self.sending && self.publish_at <= now
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Schedule {
+    // sorted by the publish time
+    pub notes: Rc<RefCell<Vec<ScheduledNote>>>,
+    // the missed ones wait for "publish now" instead of being published at the start
+    #[serde(default)]
+    pub review_missed: bool,
+    #[serde(skip)]
+    count: u64,
+}
+
+impl PartialEq for Schedule {
+    fn eq(&self, other: &Self) -> bool {
+        self.count == other.count
+    }
+}
+
+impl LoadStoreKey for Schedule {
+    fn load_store_key() -> &'static str {
+        "schedule"
+    }
+}
+
+pub enum ScheduleAction {
+    Add(ScheduledNote),
+    // edited, replaces the one with the same id
+    Update(ScheduledNote),
+    Cancel(u64),
+    // kept in the queue while it's sent, taken out when it's published,
+    // put back with the error if the sending failed
+    Sending(u64),
+    Published(u64),
+    Failed(ScheduledNote, String),
+    // the ones being sent when the app was closed, sent again
+    Interrupted,
+    Missed(HashSet<u64>),
+    PublishNow(u64),
+    ReviewMissed(bool),
+    // the queue from another tab
+    Synced(Vec<ScheduledNote>),
+}
+
+impl Schedule {
+    pub fn of(&self, pk: &XOnlyPublicKey) -> Vec<ScheduledNote> {
+        self.notes
+            .borrow()
+            .iter()
+            .filter(|note| note.pubkey == *pk)
+            .cloned()
+            .collect()
+    }
+}
+
+impl Reducible for Schedule {
+    type Action = ScheduleAction;
+
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        use ScheduleAction::*;
+        let count = self.count.checked_add(1).or(Some(0)).unwrap();
+        let mut review_missed = self.review_missed;
+        {
+            let mut notes = self.notes.borrow_mut();
+            match action {
+                Add(note) => notes.push(note),
+                Update(note) => {
+                    if let Some(old) = notes.iter_mut().find(|n| n.id == note.id) {
+                        *old = note;
+                    }
+                }
+                Cancel(id) | Published(id) => notes.retain(|n| n.id != id),
+                Sending(id) => {
+                    if let Some(note) = notes.iter_mut().find(|n| n.id == id) {
+                        note.sending = true;
+                    }
+                }
+                Interrupted => {
+                    for note in notes.iter_mut() {
+                        note.sending = false;
+                    }
+                }
+                Failed(mut note, error) => {
+                    note.sending = false;
+                    note.missed = true;
+                    note.error = Some(error);
+                    notes.retain(|n| n.id != note.id);
+                    notes.push(note);
+                }
+                Missed(ids) => {
+                    for note in notes.iter_mut().filter(|n| ids.contains(&n.id)) {
+                        note.missed = true;
+                    }
+                }
+                // at its scheduled time, the nonce and the expiration are for it
+                PublishNow(id) => {
+                    if let Some(note) = notes.iter_mut().find(|n| n.id == id) {
+                        note.missed = false;
+                        note.error = None;
+                    }
+                }
+                ReviewMissed(review) => review_missed = review,
+                Synced(synced) => *notes = synced,
+            }
+            notes.sort_by_key(|n| n.publish_at.as_u64());
+        }
+        let ret = Rc::new(Self {
+            notes: self.notes.clone(),
+            review_missed,
+            count,
+        });
+        ret.store();
+        ret
+    }
+}
+
+pub type ScheduleContext = yew::UseReducerHandle<Schedule>;
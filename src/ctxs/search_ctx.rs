@@ -0,0 +1,126 @@
+use indexmap::IndexMap;
+use nostr_sdk::nostr::prelude::*;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::rc::Rc;
+use yew::Reducible;
+
+// 每次搜索最多保留的结果数
+const MAX_SEARCH_HITS: usize = 200;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchSource {
+    Local,
+    Relay(Url),
+}
+
+impl Display for SearchSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SearchSource::Local => write!(f, "local"),
+            SearchSource::Relay(url) => write!(f, "{}", url.host_str().unwrap_or(url.as_str())),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub event: Event,
+    pub sources: Vec<SearchSource>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct UserSearch {
+    pub query: String,
+    // the subscription id of the NIP-50 request sent to relays for the current query
+    pub sub_id: Option<SubscriptionId>,
+    pub hits: Rc<RefCell<IndexMap<EventId, SearchHit>>>,
+    count: u64,
+}
+
+impl PartialEq for UserSearch {
+    fn eq(&self, other: &Self) -> bool {
+        self.count == other.count
+    }
+}
+
+pub enum UserSearchAction {
+    Query(String),
+    Hits(SearchSource, Vec<Event>),
+    Remove(HashSet<EventId>),
+    Clear,
+}
+
+impl Reducible for UserSearch {
+    type Action = UserSearchAction;
+
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        use UserSearchAction::*;
+        let count = self.count.checked_add(1).or(Some(0)).unwrap();
+        match action {
+            Query(query) => {
+                let query = query.trim().to_owned();
+                let sub_id = if query.is_empty() {
+                    None
+                } else {
+                    Some(SubscriptionId::generate())
+                };
+                Rc::new(Self {
+                    query,
+                    sub_id,
+                    hits: Rc::new(RefCell::new(IndexMap::new())),
+                    count,
+                })
+            }
+            Hits(source, events) => {
+                {
+                    let mut hits = self.hits.borrow_mut();
+                    for event in events {
+                        if let Some(hit) = hits.get_mut(&event.id) {
+                            // the same event from another source, only record the source
+                            if !hit.sources.contains(&source) {
+                                hit.sources.push(source.clone());
+                            }
+                        } else if hits.len() < MAX_SEARCH_HITS {
+                            hits.insert(
+                                event.id,
+                                SearchHit {
+                                    event,
+                                    sources: vec![source.clone()],
+                                },
+                            );
+                        }
+                    }
+                    hits.sort_by(|_, v1, _, v2| {
+                        v2.event
+                            .created_at
+                            .as_u64()
+                            .cmp(&v1.event.created_at.as_u64())
+                    });
+                }
+                Rc::new(Self {
+                    query: self.query.clone(),
+                    sub_id: self.sub_id.clone(),
+                    hits: self.hits.clone(),
+                    count,
+                })
+            }
+            Remove(ids) => {
+                self.hits.borrow_mut().retain(|id, _| !ids.contains(id));
+                Rc::new(Self {
+                    query: self.query.clone(),
+                    sub_id: self.sub_id.clone(),
+                    hits: self.hits.clone(),
+                    count,
+                })
+            }
+            Clear => Rc::new(Self {
+                count,
+                ..Default::default()
+            }),
+        }
+    }
+}
+
+pub type UserSearchContext = yew::UseReducerHandle<UserSearch>;
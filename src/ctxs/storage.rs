@@ -0,0 +1,489 @@
+// 持久化存储的后端: 浏览器的LocalStorage/IndexedDB, 内存, 以及tauri桌面端的文件
+use crate::ctxs::StoreError;
+use crate::tauri;
+
+use indexmap::IndexMap;
+use js_sys::{Array, Object, Promise, Reflect};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{IdbDatabase, IdbRequest, IdbTransactionMode, Storage};
+
+const IDB_NAME: &str = "dapp";
+const IDB_STORE: &str = "kv";
+
+// `LoadStore` reads synchronously when the contexts are created, so the async backends
+// load everything into memory when the app starts, and write back in the background
+pub trait StorageBackend {
+    fn name(&self) -> &'static str;
+
+    fn get(&self, key: &str) -> Option<String>;
+
+    fn set(&self, key: &str, value: &str) -> Result<(), StoreError>;
+
+    fn remove(&self, key: &str);
+
+    fn keys(&self) -> Vec<String>;
+
+    // start the writes waiting in the background now, e.g. when the page is being closed
+    fn flush(&self) {}
+}
+
+thread_local! {
+    static STORAGE: RefCell<Rc<dyn StorageBackend>> = RefCell::new(default_storage());
+}
+
+pub fn storage() -> Rc<dyn StorageBackend> {
+    STORAGE.with(|storage| storage.borrow().clone())
+}
+
+pub fn set_storage(backend: Rc<dyn StorageBackend>) {
+    log::info!("storage backend:{}", backend.name());
+    STORAGE.with(|storage| *storage.borrow_mut() = backend);
+}
+
+fn default_storage() -> Rc<dyn StorageBackend> {
+    match LocalStorageBackend::new() {
+        Some(backend) => Rc::new(backend),
+        // e.g. disabled by the browser
+        None => Rc::new(MemoryBackend::default()),
+    }
+}
+
+// choose the backend before rendering, the desktop shell persists to files,
+// the browsers to IndexedDB which isn't limited to a few MB like the local storage
+pub async fn init_storage() {
+    let opened = if tauri::is_tauri() {
+        CachedBackend::open(Persist::Tauri).await
+    } else {
+        CachedBackend::open_indexed_db().await
+    };
+    match opened {
+        Ok(backend) => {
+            let local = storage();
+            // the first start after this change, keep the data of the local storage
+            if backend.keys().is_empty() {
+                copy_storage(&*local, &backend);
+            }
+            // the local copy has the secret key in plain text, removed once it's persisted
+            match backend.flushed().await {
+                Ok(_) => remove_copied(&*local, &backend),
+                Err(e) => log::error!("copy the local storage error:{e:?}"),
+            }
+            set_storage(Rc::new(backend));
+        }
+        Err(e) => log::error!("open storage error:{e:?}, use the local storage"),
+    }
+}
+
+fn copy_storage(from: &dyn StorageBackend, to: &dyn StorageBackend) {
+    for key in from.keys() {
+        if let Some(value) = from.get(&key) {
+            to.set(&key, &value).ok();
+        }
+    }
+}
+
+// the keys in `to` are stale in `from`
+fn remove_copied(from: &dyn StorageBackend, to: &dyn StorageBackend) {
+    for key in from.keys() {
+        if to.get(&key).is_some() {
+            from.remove(&key);
+        }
+    }
+}
+
+pub struct LocalStorageBackend(Storage);
+
+impl LocalStorageBackend {
+    pub fn new() -> Option<Self> {
+        web_sys::window()?.local_storage().ok()?.map(Self)
+    }
+}
+
+impl StorageBackend for LocalStorageBackend {
+    fn name(&self) -> &'static str {
+        "local-storage"
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        self.0.get_item(key).ok()?
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), StoreError> {
+        self.0
+            .set_item(key, value)
+            .map_err(|e| StoreError::from_js(key, e))
+    }
+
+    fn remove(&self, key: &str) {
+        self.0.remove_item(key).ok();
+    }
+
+    fn keys(&self) -> Vec<String> {
+        let len = self.0.length().unwrap_or(0);
+        (0..len).filter_map(|i| self.0.key(i).ok()?).collect()
+    }
+}
+
+// not persisted, for tests and browsers without storage
+#[derive(Default)]
+pub struct MemoryBackend(RefCell<HashMap<String, String>>);
+
+impl StorageBackend for MemoryBackend {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        self.0.borrow().get(key).cloned()
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), StoreError> {
+        self.0.borrow_mut().insert(key.to_owned(), value.to_owned());
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) {
+        self.0.borrow_mut().remove(key);
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.0.borrow().keys().cloned().collect()
+    }
+}
+
+// where a `CachedBackend` writes to
+#[derive(Clone)]
+pub enum Persist {
+    IndexedDb(IdbDatabase),
+    // the storage commands of src-tauri, a file per key in the app data directory
+    Tauri,
+    // written synchronously, for the tests
+    #[cfg(test)]
+    Memory(Rc<MemoryBackend>),
+}
+
+type PendingWrites = RefCell<IndexMap<String, Option<String>>>;
+
+// the data is in memory, the writes go to `persist` in the background
+pub struct CachedBackend {
+    cache: MemoryBackend,
+    persist: Persist,
+    // the latest value of each key not written yet, None removes it
+    pending: Rc<PendingWrites>,
+}
+
+fn idb_future(request: &IdbRequest) -> JsFuture {
+    let request = request.clone();
+    JsFuture::from(Promise::new(&mut |resolve, reject| {
+        request.set_onsuccess(Some(&resolve));
+        request.set_onerror(Some(&reject));
+    }))
+}
+
+async fn idb_result(request: IdbRequest) -> Result<JsValue, JsValue> {
+    match idb_future(&request).await {
+        Ok(_) => request.result(),
+        Err(_) => Err(request
+            .error()?
+            .map(JsValue::from)
+            .unwrap_or_else(|| "indexeddb error".into())),
+    }
+}
+
+async fn open_idb() -> Result<IdbDatabase, JsValue> {
+    let factory = web_sys::window()
+        .ok_or("no window")?
+        .indexed_db()?
+        .ok_or("no indexeddb")?;
+    let request = factory.open_with_u32(IDB_NAME, 1)?;
+    let upgrade = wasm_bindgen::closure::Closure::<dyn Fn(JsValue)>::new({
+        let request = request.clone();
+        move |_| {
+            if let Ok(db) = request.result().and_then(|db| db.dyn_into::<IdbDatabase>()) {
+                db.create_object_store(IDB_STORE).ok();
+            }
+        }
+    });
+    request.set_onupgradeneeded(Some(upgrade.as_ref().unchecked_ref()));
+    let db = idb_result(request.clone().into()).await?;
+    request.set_onupgradeneeded(None);
+    db.dyn_into::<IdbDatabase>()
+}
+
+fn tauri_args(fields: &[(&str, &str)]) -> Object {
+    let args = Object::new();
+    for (name, value) in fields {
+        let _ = Reflect::set(&args, &(*name).into(), &(*value).into());
+    }
+    args
+}
+
+impl Persist {
+    async fn load_all(&self) -> Result<HashMap<String, String>, JsValue> {
+        let mut data = HashMap::new();
+        match self {
+            Self::IndexedDb(db) => {
+                let store = db
+                    .transaction_with_str(IDB_STORE)?
+                    .object_store(IDB_STORE)?;
+                let keys = Array::from(&idb_result(store.get_all_keys()?).await?);
+                let values = Array::from(&idb_result(store.get_all()?).await?);
+                for (key, value) in keys.iter().zip(values.iter()) {
+                    if let (Some(key), Some(value)) = (key.as_string(), value.as_string()) {
+                        data.insert(key, value);
+                    }
+                }
+            }
+            Self::Tauri => {
+                let all = tauri::invoke("storage_load_all", &Object::new()).await?;
+                let all = all.dyn_into::<Object>()?;
+                for entry in Object::entries(&all).iter() {
+                    let entry = Array::from(&entry);
+                    if let (Some(key), Some(value)) =
+                        (entry.get(0).as_string(), entry.get(1).as_string())
+                    {
+                        data.insert(key, value);
+                    }
+                }
+            }
+            #[cfg(test)]
+            Self::Memory(memory) => data = memory.0.borrow().clone(),
+        }
+        Ok(data)
+    }
+
+    // the requests are made before it returns, the promise is None when it's written already
+    fn write_all(
+        &self,
+        writes: IndexMap<String, Option<String>>,
+    ) -> Result<Option<Promise>, JsValue> {
+        match self {
+            Self::IndexedDb(db) => {
+                let tx =
+                    db.transaction_with_str_and_mode(IDB_STORE, IdbTransactionMode::Readwrite)?;
+                let store = tx.object_store(IDB_STORE)?;
+                for (key, value) in &writes {
+                    match value {
+                        Some(value) => store.put_with_key(&value.into(), &key.into())?,
+                        None => store.delete(&key.into())?,
+                    };
+                }
+                Ok(Some(Promise::new(&mut |resolve, reject| {
+                    tx.set_oncomplete(Some(&resolve));
+                    let aborted = {
+                        let tx = tx.clone();
+                        Closure::once_into_js(move |_: JsValue| {
+                            let error = tx
+                                .error()
+                                .map(JsValue::from)
+                                .unwrap_or_else(|| "indexeddb error".into());
+                            reject.call1(&JsValue::NULL, &error).ok();
+                        })
+                    };
+                    tx.set_onabort(Some(aborted.unchecked_ref()));
+                })))
+            }
+            Self::Tauri => {
+                let promises = Array::new();
+                for (key, value) in &writes {
+                    let promise = match value {
+                        Some(value) => tauri::invoke_now(
+                            "storage_set",
+                            &tauri_args(&[("key", key.as_str()), ("value", value.as_str())]),
+                        )?,
+                        None => tauri::invoke_now(
+                            "storage_remove",
+                            &tauri_args(&[("key", key.as_str())]),
+                        )?,
+                    };
+                    promises.push(&promise);
+                }
+                Ok(Some(Promise::all(&promises)))
+            }
+            #[cfg(test)]
+            Self::Memory(memory) => {
+                for (key, value) in writes {
+                    match value {
+                        Some(value) => memory.set(&key, &value).map_err(|e| e.to_string())?,
+                        None => memory.remove(&key),
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl CachedBackend {
+    pub async fn open(persist: Persist) -> Result<Self, JsValue> {
+        let data = persist.load_all().await?;
+        Ok(Self::new(data, persist))
+    }
+
+    fn new(data: HashMap<String, String>, persist: Persist) -> Self {
+        Self {
+            cache: MemoryBackend(RefCell::new(data)),
+            persist,
+            pending: Rc::new(RefCell::new(IndexMap::new())),
+        }
+    }
+
+    pub async fn open_indexed_db() -> Result<Self, JsValue> {
+        Self::open(Persist::IndexedDb(open_idb().await?)).await
+    }
+
+    // all the pending writes in one batch, the error is reported with the keys
+    fn write_pending(persist: &Persist, pending: &PendingWrites) -> Option<(String, Promise)> {
+        let writes = std::mem::take(&mut *pending.borrow_mut());
+        if writes.is_empty() {
+            return None;
+        }
+        let keys = writes.keys().cloned().collect::<Vec<_>>().join(",");
+        match persist.write_all(writes) {
+            Ok(promise) => promise.map(|promise| (keys, promise)),
+            Err(e) => {
+                crate::ctxs::report_store_error(StoreError::from_js(&keys, e));
+                None
+            }
+        }
+    }
+
+    fn spawn_write(persist: &Persist, pending: &PendingWrites) {
+        if let Some((keys, promise)) = Self::write_pending(persist, pending) {
+            spawn_local(async move {
+                if let Err(e) = JsFuture::from(promise).await {
+                    crate::ctxs::report_store_error(StoreError::from_js(&keys, e));
+                }
+            });
+        }
+    }
+
+    // the writes made in the same task are batched
+    fn write_back(&self, key: &str, value: Option<String>) {
+        let first = {
+            let mut pending = self.pending.borrow_mut();
+            pending.insert(key.to_owned(), value);
+            pending.len() == 1
+        };
+        // written by `flush` in the tests
+        #[cfg(test)]
+        if let Persist::Memory(_) = &self.persist {
+            return;
+        }
+        if first {
+            let persist = self.persist.clone();
+            let pending = self.pending.clone();
+            spawn_local(async move { Self::spawn_write(&persist, &pending) });
+        }
+    }
+
+    // wait until the pending writes are persisted
+    pub async fn flushed(&self) -> Result<(), JsValue> {
+        let writes = std::mem::take(&mut *self.pending.borrow_mut());
+        if writes.is_empty() {
+            return Ok(());
+        }
+        if let Some(promise) = self.persist.write_all(writes)? {
+            JsFuture::from(promise).await?;
+        }
+        Ok(())
+    }
+}
+
+impl StorageBackend for CachedBackend {
+    fn name(&self) -> &'static str {
+        match self.persist {
+            Persist::IndexedDb(_) => "indexeddb",
+            Persist::Tauri => "tauri",
+            #[cfg(test)]
+            Persist::Memory(_) => "memory",
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        self.cache.get(key)
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), StoreError> {
+        self.cache.set(key, value)?;
+        self.write_back(key, Some(value.to_owned()));
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) {
+        self.cache.remove(key);
+        self.write_back(key, None);
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.cache.keys()
+    }
+
+    fn flush(&self) {
+        Self::spawn_write(&self.persist, &self.pending);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted_keys(backend: &dyn StorageBackend) -> Vec<String> {
+        let mut keys = backend.keys();
+        keys.sort();
+        keys
+    }
+
+    fn get_set_remove(backend: &dyn StorageBackend) {
+        assert_eq!(backend.get("a"), None);
+        backend.set("a", "1").unwrap();
+        backend.set("b", "2").unwrap();
+        backend.set("a", "3").unwrap();
+        assert_eq!(backend.get("a").as_deref(), Some("3"));
+        assert_eq!(sorted_keys(backend), vec!["a", "b"]);
+        backend.remove("a");
+        backend.remove("c");
+        assert_eq!(backend.get("a"), None);
+        assert_eq!(sorted_keys(backend), vec!["b"]);
+    }
+
+    #[test]
+    fn memory_backend() {
+        get_set_remove(&MemoryBackend::default());
+    }
+
+    #[test]
+    fn cached_backend() {
+        let persisted = Rc::new(MemoryBackend::default());
+        persisted.set("old", "0").unwrap();
+        let data = persisted.0.borrow().clone();
+        let backend = CachedBackend::new(data, Persist::Memory(persisted.clone()));
+        assert_eq!(backend.get("old").as_deref(), Some("0"));
+        backend.remove("old");
+        get_set_remove(&backend);
+        // written back in a batch
+        assert_eq!(sorted_keys(&*persisted), vec!["old"]);
+        backend.flush();
+        assert_eq!(sorted_keys(&*persisted), vec!["b"]);
+        assert_eq!(persisted.get("b").as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn copy_to_empty_backend() {
+        let from = MemoryBackend::default();
+        from.set("a", "1").unwrap();
+        from.set("b", "2").unwrap();
+        let to = MemoryBackend::default();
+        copy_storage(&from, &to);
+        assert_eq!(sorted_keys(&to), vec!["a", "b"]);
+        assert_eq!(to.get("b").as_deref(), Some("2"));
+        from.set("c", "3").unwrap();
+        remove_copied(&from, &to);
+        assert_eq!(sorted_keys(&from), vec!["c"]);
+    }
+}
@@ -0,0 +1,116 @@
+// 主题: 浅色, 深色, 或者跟随系统(prefers-color-scheme)
+use crate::ctxs::*;
+
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+use yew::Reducible;
+
+const DARK_QUERY: &str = "(prefers-color-scheme: dark)";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ThemeMode {
+    Light,
+    Dark,
+    #[default]
+    System,
+}
+
+impl ThemeMode {
+    // the order of the toggle in the layout
+    pub fn next(self) -> Self {
+        match self {
+            Self::Light => Self::Dark,
+            Self::Dark => Self::System,
+            Self::System => Self::Light,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Theme {
+    pub mode: ThemeMode,
+    // the system prefers the dark one
+    #[serde(skip)]
+    pub system_dark: bool,
+    #[serde(skip)]
+    count: u64,
+}
+
+impl PartialEq for Theme {
+    fn eq(&self, other: &Self) -> bool {
+        self.count == other.count
+    }
+}
+
+impl LoadStoreKey for Theme {
+    fn load_store_key() -> &'static str {
+        "theme"
+    }
+}
+
+pub enum ThemeAction {
+    Set(ThemeMode),
+    SystemChanged(bool),
+}
+
+pub fn dark_media_query() -> Option<web_sys::MediaQueryList> {
+    web_sys::window()?.match_media(DARK_QUERY).ok()?
+}
+
+impl Theme {
+    pub fn init() -> Self {
+        let mut theme = Self::load();
+        theme.system_dark = dark_media_query().map(|q| q.matches()).unwrap_or(false);
+        theme.apply();
+        theme
+    }
+
+    pub fn is_dark(&self) -> bool {
+        match self.mode {
+            ThemeMode::Light => false,
+            ThemeMode::Dark => true,
+            ThemeMode::System => self.system_dark,
+        }
+    }
+
+    // the styles in `styles/theme.scss` follow `data-theme` of <html>
+    fn apply(&self) {
+        let root = web_sys::window()
+            .and_then(|w| w.document())
+            .and_then(|d| d.document_element());
+        if let Some(root) = root {
+            let name = if self.is_dark() { "dark" } else { "light" };
+            root.set_attribute("data-theme", name).ok();
+        }
+    }
+}
+
+impl Reducible for Theme {
+    type Action = ThemeAction;
+
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        let count = self.count.checked_add(1).or(Some(0)).unwrap();
+        let mut mode = self.mode;
+        let mut system_dark = self.system_dark;
+        let mut store = false;
+        match action {
+            ThemeAction::Set(v) => {
+                mode = v;
+                store = true;
+            }
+            ThemeAction::SystemChanged(dark) => system_dark = dark,
+        }
+        let ret = Rc::new(Self {
+            mode,
+            system_dark,
+            count,
+        });
+        ret.apply();
+        if store {
+            ret.store();
+        }
+        ret
+    }
+}
+
+pub type ThemeContext = yew::UseReducerHandle<Theme>;
@@ -21,7 +21,11 @@ pub fn run_app() {
     wasm_logger::init(wasm_logger::Config::new(log::Level::Debug));
     #[cfg(not(debug_assertions))]
     wasm_logger::init(wasm_logger::Config::new(log::Level::Info));
-    yew::Renderer::<App>::new().render();
+    // the contexts load from the storage when they are created
+    wasm_bindgen_futures::spawn_local(async {
+        crate::ctxs::init_storage().await;
+        yew::Renderer::<App>::new().render();
+    });
     log::info!("available_locales:{:?}", crate::available_locales());
     for i in 0..8 {
         log::info!(
@@ -33,5 +37,4 @@ pub fn run_app() {
             .as_str()
         );
     }
-    rust_i18n::set_locale("zh-cn");
 }
@@ -1,11 +1,10 @@
-use crate::ctxs::*;
 use yew::prelude::*;
 
+// the suspense fallback is outside of the locale context, use the global locale
+// that `I18nLocale::init` has set
 #[function_component(PleaseWait)]
 pub fn please_wait() -> Html {
-    let locale_ctx = use_context::<I18nLocaleContext>().unwrap();
-    let lang = locale_ctx.to_string();
-    let loading = rust_i18n::t!("loading...", locale = &lang);
+    let loading = rust_i18n::t!("loading");
     html! {
         <span class="icon-text has-text-info">
             <span class="icon">
@@ -1,11 +1,14 @@
-// pub mod agent;
+pub mod agent;
 
 pub mod app_ctx;
 pub mod app_ws;
+pub mod backup;
 pub mod ctxs;
 pub mod dapp_ui;
 pub mod fallback;
 pub mod route;
+pub mod tauri;
+pub mod upload;
 pub mod utils;
 pub mod views;
 #[macro_use]
@@ -7,6 +7,12 @@ pub enum MainRoute {
     Home,
     #[at("/contact")]
     Contact,
+    #[at("/search")]
+    Search,
+    #[at("/scheduled")]
+    Scheduled,
+    #[at("/notifications")]
+    Notifications,
     #[at("/settings")]
     SettingsRoot,
     #[at("/settings/*")]
@@ -34,8 +40,11 @@ pub fn switch_main(route: MainRoute) -> Html {
                 match route {
                 MainRoute::Home => html!(<Home/>),
                 MainRoute::Contact => html!(<Contact/>),
+                MainRoute::Search => html!(<Search/>),
+                MainRoute::Scheduled => html!(<Scheduled/>),
+                MainRoute::Notifications => html!(<Notifications/>),
                 MainRoute::SettingsRoot | MainRoute::Settings =>  html!(<Switch<SettingsRoute> render={switch_settings} />),
-                MainRoute::NotFound => html!(<h1>{"Not found"}</h1>),
+                MainRoute::NotFound => html!(<h1 class="title">{rust_i18n::t!("not found")}</h1>),
             }
         }
         </Layout>
@@ -45,7 +54,7 @@ pub fn switch_main(route: MainRoute) -> Html {
 fn switch_settings(route: SettingsRoute) -> Html {
     match route {
         SettingsRoute::Profile => html! {
-                <h1>{"Profile"}</h1>
+                <h1>{rust_i18n::t!("profile")}</h1>
         },
         SettingsRoute::Relay => html! {
                  <Settings/>
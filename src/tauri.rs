@@ -0,0 +1,49 @@
+// Bridge to the apis the tauri shell injects as `window.__TAURI__` (`withGlobalTauri`)
+use js_sys::{Function, Object, Promise, Reflect};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+fn tauri_api(path: &[&str]) -> Option<JsValue> {
+    let mut value = JsValue::from(web_sys::window()?);
+    for name in ["__TAURI__"].iter().chain(path.iter()) {
+        value = Reflect::get(&value, &JsValue::from_str(name)).ok()?;
+        if value.is_undefined() || value.is_null() {
+            return None;
+        }
+    }
+    Some(value)
+}
+
+// whether the app runs in the tauri desktop shell
+pub fn is_tauri() -> bool {
+    tauri_api(&[]).is_some()
+}
+
+// show a desktop notification, do nothing in the browser
+pub fn send_notification(title: &str, body: &str) {
+    let send = match tauri_api(&["notification", "sendNotification"])
+        .and_then(|f| f.dyn_into::<Function>().ok())
+    {
+        Some(send) => send,
+        None => return,
+    };
+    let options = Object::new();
+    let _ = Reflect::set(&options, &"title".into(), &title.into());
+    let _ = Reflect::set(&options, &"body".into(), &body.into());
+    if let Err(e) = send.call1(&JsValue::NULL, &options) {
+        log::warn!("send notification error:{e:?}");
+    }
+}
+
+// call a command of `src-tauri`, the args are the fields of an object
+pub async fn invoke(cmd: &str, args: &Object) -> Result<JsValue, JsValue> {
+    JsFuture::from(invoke_now(cmd, args)?).await
+}
+
+// the command is sent before it returns, e.g. when the page is being closed
+pub fn invoke_now(cmd: &str, args: &Object) -> Result<Promise, JsValue> {
+    let invoke = tauri_api(&["tauri", "invoke"])
+        .and_then(|f| f.dyn_into::<Function>().ok())
+        .ok_or_else(|| JsValue::from_str("not in tauri"))?;
+    invoke.call2(&JsValue::NULL, &cmd.into(), args)?.dyn_into()
+}
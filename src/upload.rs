@@ -0,0 +1,610 @@
+// 上传媒体文件到 NIP-96 或 Blossom 服务器, 用 NIP-98 / BUD-01 的事件认证,
+// 结果是文件的地址和 NIP-94 的 imeta 标签
+use crate::ctxs::*;
+
+use anyhow::{anyhow, bail};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use nostr_sdk::nostr::hashes::{sha256, Hash};
+use nostr_sdk::nostr::prelude::TagKind;
+use nostr_sdk::nostr::{EventBuilder, Keys, Kind, Tag, Timestamp, Url};
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    CanvasRenderingContext2d, File, FormData, HtmlCanvasElement, HtmlImageElement, ProgressEvent,
+    XmlHttpRequest,
+};
+use yew::Callback;
+
+// NIP-98 http auth
+const HTTP_AUTH_KIND: u64 = 27235;
+// BUD-01 authorization
+const BLOSSOM_AUTH_KIND: u64 = 24242;
+const BLOSSOM_AUTH_SECONDS: u64 = 300;
+const NIP96_PATH: &str = ".well-known/nostr/nip96.json";
+const PROCESSING_RETRIES: u32 = 30;
+const PROCESSING_INTERVAL_MS: u32 = 1000;
+// the image is scaled down before the blurhash
+const BLURHASH_SIZE: u32 = 32;
+const BLURHASH_COMPONENTS: (u32, u32) = (4, 3);
+
+// the uploaded file, the url goes to the content and the tag to the tags
+#[derive(Debug, Clone, PartialEq)]
+pub struct Media {
+    pub url: String,
+    pub imeta: Tag,
+}
+
+// NIP-94 fields of the file
+#[derive(Debug, Clone, Default)]
+struct FileInfo {
+    url: String,
+    mime: String,
+    sha256: String,
+    // the hash before the server transformed it
+    original_sha256: Option<String>,
+    dim: Option<(u32, u32)>,
+    blurhash: Option<String>,
+}
+
+impl FileInfo {
+    // NIP-92 `imeta`, every value is `name value`
+    fn imeta(&self) -> Tag {
+        let mut values = vec![format!("url {}", self.url)];
+        if !self.mime.is_empty() {
+            values.push(format!("m {}", self.mime));
+        }
+        values.push(format!("x {}", self.sha256));
+        if let Some(ox) = &self.original_sha256 {
+            values.push(format!("ox {ox}"));
+        }
+        if let Some((w, h)) = self.dim {
+            values.push(format!("dim {w}x{h}"));
+        }
+        if let Some(blurhash) = &self.blurhash {
+            values.push(format!("blurhash {blurhash}"));
+        }
+        Tag::Generic(TagKind::Custom("imeta".to_owned()), values)
+    }
+
+    // the tags of `nip94_event` in the NIP-96 response
+    fn merge_nip94(&mut self, tags: &[Vec<String>]) {
+        for tag in tags {
+            let (name, value) = match (tag.first(), tag.get(1)) {
+                (Some(name), Some(value)) => (name.as_str(), value.clone()),
+                _ => continue,
+            };
+            match name {
+                "url" => self.url = value,
+                "m" => self.mime = value,
+                "x" => {
+                    if value != self.sha256 {
+                        self.original_sha256 = Some(self.sha256.clone());
+                        self.sha256 = value;
+                    }
+                }
+                "dim" => {
+                    if let Some((w, h)) = value.split_once('x') {
+                        if let (Ok(w), Ok(h)) = (w.parse(), h.parse()) {
+                            self.dim = Some((w, h));
+                        }
+                    }
+                }
+                "blurhash" => self.blurhash = Some(value),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Nip96Info {
+    #[serde(default)]
+    api_url: String,
+    #[serde(default)]
+    delegated_to_url: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct Nip94Event {
+    #[serde(default)]
+    tags: Vec<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct Nip96Response {
+    status: String,
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    processing_url: Option<String>,
+    #[serde(default)]
+    nip94_event: Option<Nip94Event>,
+}
+
+// BUD-02 blob descriptor
+#[derive(Deserialize)]
+struct BlobDescriptor {
+    url: String,
+    #[serde(default)]
+    sha256: Option<String>,
+    #[serde(default, rename = "type")]
+    mime: Option<String>,
+}
+
+// not `btoa`, it only takes latin1 and the file names may be anything
+fn auth_header(keys: &Keys, builder: EventBuilder) -> anyhow::Result<String> {
+    let event = builder.to_event(keys)?;
+    Ok(format!("Nostr {}", BASE64.encode(event.as_json())))
+}
+
+// NIP-98
+fn http_auth(keys: &Keys, url: &str, method: &str, payload: &str) -> anyhow::Result<String> {
+    let tag = |name: &str, value: &str| {
+        Tag::Generic(TagKind::Custom(name.to_owned()), vec![value.to_owned()])
+    };
+    let tags = [
+        tag("u", url),
+        tag("method", method),
+        tag("payload", payload),
+    ];
+    auth_header(
+        keys,
+        EventBuilder::new(Kind::from(HTTP_AUTH_KIND), "", &tags),
+    )
+}
+
+// BUD-01, valid for the file only, and only for a while
+fn blossom_auth(keys: &Keys, name: &str, sha256: &str) -> anyhow::Result<String> {
+    let tag = |name: &str, value: &str| {
+        Tag::Generic(TagKind::Custom(name.to_owned()), vec![value.to_owned()])
+    };
+    let expiration = Timestamp::now() + BLOSSOM_AUTH_SECONDS;
+    let tags = [
+        tag("t", "upload"),
+        tag("x", sha256),
+        Tag::Expiration(expiration),
+    ];
+    let content = format!("Upload {name}");
+    auth_header(
+        keys,
+        EventBuilder::new(Kind::from(BLOSSOM_AUTH_KIND), content, &tags),
+    )
+}
+
+fn js_error(e: JsValue) -> anyhow::Error {
+    anyhow!("{}", e.as_string().unwrap_or_else(|| format!("{e:?}")))
+}
+
+async fn read_bytes(file: &File) -> anyhow::Result<Vec<u8>> {
+    let buffer = JsFuture::from(file.array_buffer())
+        .await
+        .map_err(js_error)?;
+    Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+}
+
+enum Body<'a> {
+    File(&'a File),
+    Form(&'a FormData),
+}
+
+// XMLHttpRequest, `fetch` can't tell the upload progress
+async fn send(
+    method: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: Option<Body<'_>>,
+    on_progress: &Callback<(f64, f64)>,
+) -> anyhow::Result<(u16, String)> {
+    let xhr = XmlHttpRequest::new().map_err(js_error)?;
+    xhr.open_with_async(method, url, true).map_err(js_error)?;
+    for (name, value) in headers {
+        xhr.set_request_header(name, value).map_err(js_error)?;
+    }
+    let progress = {
+        let on_progress = on_progress.clone();
+        Closure::<dyn Fn(ProgressEvent)>::new(move |e: ProgressEvent| {
+            if e.length_computable() {
+                on_progress.emit((e.loaded(), e.total()));
+            }
+        })
+    };
+    xhr.upload()
+        .map_err(js_error)?
+        .set_onprogress(Some(progress.as_ref().unchecked_ref()));
+    let done = js_sys::Promise::new(&mut |resolve, reject| {
+        xhr.set_onload(Some(&resolve));
+        xhr.set_onerror(Some(&reject));
+    });
+    match body {
+        Some(Body::File(file)) => xhr.send_with_opt_blob(Some(file)),
+        Some(Body::Form(form)) => xhr.send_with_opt_form_data(Some(form)),
+        None => xhr.send(),
+    }
+    .map_err(js_error)?;
+    JsFuture::from(done)
+        .await
+        .map_err(|_| anyhow!("{method} {url} failed"))?;
+    let status = xhr.status().map_err(js_error)?;
+    let text = xhr.response_text().map_err(js_error)?.unwrap_or_default();
+    if !(200..300).contains(&status) {
+        bail!("{method} {url}: {status} {text}");
+    }
+    Ok((status, text))
+}
+
+async fn nip96_api_url(server: &Url) -> anyhow::Result<String> {
+    let mut server = server.clone();
+    // one level of `delegated_to_url`
+    for _ in 0..2 {
+        let resp = gloo_net::http::Request::get(server.join(NIP96_PATH)?.as_str())
+            .send()
+            .await?;
+        let info = serde_json::from_str::<Nip96Info>(&resp.text().await?)?;
+        match info.delegated_to_url.filter(|url| !url.is_empty()) {
+            Some(url) if info.api_url.is_empty() => server = Url::parse(&url)?,
+            _ if !info.api_url.is_empty() => return Ok(info.api_url),
+            _ => break,
+        }
+    }
+    bail!("no NIP-96 api_url on {server}")
+}
+
+async fn upload_nip96(
+    server: &Url,
+    keys: &Keys,
+    file: &File,
+    info: &mut FileInfo,
+    on_progress: &Callback<(f64, f64)>,
+) -> anyhow::Result<()> {
+    let api_url = nip96_api_url(server).await?;
+    let form = FormData::new().map_err(js_error)?;
+    form.append_with_blob_and_filename("file", file, &file.name())
+        .map_err(js_error)?;
+    form.append_with_str("content_type", &info.mime)
+        .map_err(js_error)?;
+    form.append_with_str("size", &file.size().to_string())
+        .map_err(js_error)?;
+    let auth = http_auth(keys, &api_url, "POST", &info.sha256)?;
+    let headers = [("Authorization", auth.as_str())];
+    let (_, text) = send(
+        "POST",
+        &api_url,
+        &headers,
+        Some(Body::Form(&form)),
+        on_progress,
+    )
+    .await?;
+    let mut resp = serde_json::from_str::<Nip96Response>(&text)?;
+    // the server transforms the file later
+    let mut retries = 0;
+    while resp.nip94_event.is_none() && resp.status == "processing" && retries < PROCESSING_RETRIES
+    {
+        let url = match resp.processing_url.clone() {
+            Some(url) => url,
+            None => break,
+        };
+        gloo_timers::future::TimeoutFuture::new(PROCESSING_INTERVAL_MS).await;
+        let text = gloo_net::http::Request::get(&url)
+            .send()
+            .await?
+            .text()
+            .await?;
+        resp = serde_json::from_str::<Nip96Response>(&text)?;
+        retries += 1;
+    }
+    if resp.status == "error" {
+        bail!("{}", resp.message);
+    }
+    let nip94 = resp
+        .nip94_event
+        .ok_or_else(|| anyhow!("no nip94_event: {}", resp.message))?;
+    info.merge_nip94(&nip94.tags);
+    Ok(())
+}
+
+async fn upload_blossom(
+    server: &Url,
+    keys: &Keys,
+    file: &File,
+    info: &mut FileInfo,
+    on_progress: &Callback<(f64, f64)>,
+) -> anyhow::Result<()> {
+    let url = server.join("upload")?;
+    let auth = blossom_auth(keys, &file.name(), &info.sha256)?;
+    let mut headers = vec![("Authorization", auth.as_str())];
+    if !info.mime.is_empty() {
+        headers.push(("Content-Type", info.mime.as_str()));
+    }
+    let (_, text) = send(
+        "PUT",
+        url.as_str(),
+        &headers,
+        Some(Body::File(file)),
+        on_progress,
+    )
+    .await?;
+    let blob = serde_json::from_str::<BlobDescriptor>(&text)?;
+    if blob.sha256.as_ref().map_or(false, |x| *x != info.sha256) {
+        bail!("the server changed the file: {}", blob.url);
+    }
+    info.url = blob.url;
+    if info.mime.is_empty() {
+        info.mime = blob.mime.unwrap_or_default();
+    }
+    Ok(())
+}
+
+// the size of the image and the blurhash of its scaled down copy
+async fn image_info(file: &File) -> anyhow::Result<((u32, u32), String)> {
+    let src = web_sys::Url::create_object_url_with_blob(file).map_err(js_error)?;
+    let img = HtmlImageElement::new().map_err(js_error)?;
+    let loaded = js_sys::Promise::new(&mut |resolve, reject| {
+        img.set_onload(Some(&resolve));
+        img.set_onerror(Some(&reject));
+    });
+    img.set_src(&src);
+    let result = JsFuture::from(loaded).await;
+    web_sys::Url::revoke_object_url(&src).ok();
+    result.map_err(|_| anyhow!("{} is not an image", file.name()))?;
+    let (width, height) = (img.natural_width(), img.natural_height());
+    if width == 0 || height == 0 {
+        bail!("{} is empty", file.name());
+    }
+    let scale = (BLURHASH_SIZE as f64 / width.max(height) as f64).min(1.0);
+    let w = ((width as f64 * scale).round() as u32).max(1);
+    let h = ((height as f64 * scale).round() as u32).max(1);
+    let canvas = web_sys::window()
+        .and_then(|w| w.document())
+        .ok_or_else(|| anyhow!("no document"))?
+        .create_element("canvas")
+        .map_err(js_error)?
+        .dyn_into::<HtmlCanvasElement>()
+        .map_err(|_| anyhow!("no canvas"))?;
+    canvas.set_width(w);
+    canvas.set_height(h);
+    let ctx = canvas
+        .get_context("2d")
+        .map_err(js_error)?
+        .and_then(|ctx| ctx.dyn_into::<CanvasRenderingContext2d>().ok())
+        .ok_or_else(|| anyhow!("no 2d context"))?;
+    ctx.draw_image_with_html_image_element_and_dw_and_dh(&img, 0.0, 0.0, w as f64, h as f64)
+        .map_err(js_error)?;
+    let pixels = ctx
+        .get_image_data(0.0, 0.0, w as f64, h as f64)
+        .map_err(js_error)?
+        .data();
+    let (x, y) = BLURHASH_COMPONENTS;
+    Ok(((width, height), blurhash(&pixels, w, h, x, y)))
+}
+
+const BASE83_CHARS: &[u8] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz#$%*+,-.:;=?@[]^_{|}~";
+
+fn base83(value: u32, len: u32, out: &mut String) {
+    for i in 1..=len {
+        let digit = value / 83u32.pow(len - i) % 83;
+        out.push(BASE83_CHARS[digit as usize] as char);
+    }
+}
+
+fn srgb_to_linear(v: u8) -> f64 {
+    let v = v as f64 / 255.0;
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(v: f64) -> u32 {
+    let v = v.clamp(0.0, 1.0);
+    if v <= 0.0031308 {
+        (v * 12.92 * 255.0 + 0.5) as u32
+    } else {
+        ((1.055 * v.powf(1.0 / 2.4) - 0.055) * 255.0 + 0.5) as u32
+    }
+}
+
+// https://github.com/woltapp/blurhash/blob/master/Algorithm.md, rgba pixels
+fn blurhash(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    x_components: u32,
+    y_components: u32,
+) -> String {
+    use std::f64::consts::PI;
+    let mut factors = Vec::with_capacity((x_components * y_components) as usize);
+    for j in 0..y_components {
+        for i in 0..x_components {
+            let normalisation = if i == 0 && j == 0 { 1.0 } else { 2.0 };
+            let mut rgb = [0.0; 3];
+            for y in 0..height {
+                for x in 0..width {
+                    let basis = normalisation
+                        * (PI * i as f64 * x as f64 / width as f64).cos()
+                        * (PI * j as f64 * y as f64 / height as f64).cos();
+                    let p = ((y * width + x) * 4) as usize;
+                    for (c, v) in rgb.iter_mut().enumerate() {
+                        *v += basis * srgb_to_linear(pixels[p + c]);
+                    }
+                }
+            }
+            let scale = 1.0 / (width * height) as f64;
+            factors.push(rgb.map(|v| v * scale));
+        }
+    }
+    let mut hash = String::new();
+    base83((x_components - 1) + (y_components - 1) * 9, 1, &mut hash);
+    let (dc, ac) = factors.split_first().unwrap();
+    let max_value = if ac.is_empty() {
+        base83(0, 1, &mut hash);
+        1.0
+    } else {
+        let actual_max = ac.iter().flatten().fold(0.0f64, |m, v| m.max(v.abs()));
+        let quantised = (actual_max * 166.0 - 0.5).floor().clamp(0.0, 82.0) as u32;
+        base83(quantised, 1, &mut hash);
+        (quantised + 1) as f64 / 166.0
+    };
+    let dc = (linear_to_srgb(dc[0]) << 16) + (linear_to_srgb(dc[1]) << 8) + linear_to_srgb(dc[2]);
+    base83(dc, 4, &mut hash);
+    for rgb in ac {
+        let quant = |v: f64| {
+            let v = v / max_value;
+            (v.abs().sqrt().copysign(v) * 9.0 + 9.5)
+                .floor()
+                .clamp(0.0, 18.0) as u32
+        };
+        base83(
+            quant(rgb[0]) * 19 * 19 + quant(rgb[1]) * 19 + quant(rgb[2]),
+            2,
+            &mut hash,
+        );
+    }
+    hash
+}
+
+// the progress is (sent, total) in bytes
+pub async fn upload(
+    server: &MediaServer,
+    keys: &Keys,
+    file: File,
+    on_progress: Callback<(f64, f64)>,
+) -> anyhow::Result<Media> {
+    let url = server
+        .url
+        .clone()
+        .ok_or_else(|| anyhow!("no media server"))?;
+    let bytes = read_bytes(&file).await?;
+    let mut info = FileInfo {
+        mime: file.type_(),
+        sha256: sha256::Hash::hash(&bytes).to_string(),
+        ..Default::default()
+    };
+    if info.mime.starts_with("image/") {
+        match image_info(&file).await {
+            Ok((dim, blurhash)) => {
+                info.dim = Some(dim);
+                info.blurhash = Some(blurhash);
+            }
+            Err(e) => log::warn!("{e}"),
+        }
+    }
+    match server.kind {
+        MediaServerKind::Nip96 => upload_nip96(&url, keys, &file, &mut info, &on_progress).await?,
+        MediaServerKind::Blossom => {
+            upload_blossom(&url, keys, &file, &mut info, &on_progress).await?
+        }
+    }
+    Ok(Media {
+        url: info.url.clone(),
+        imeta: info.imeta(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr_sdk::nostr::Event;
+
+    fn solid(rgb: [u8; 3], width: u32, height: u32) -> Vec<u8> {
+        (0..width * height)
+            .flat_map(|_| [rgb[0], rgb[1], rgb[2], 255])
+            .collect()
+    }
+
+    // the reference encoder samples the cosines at x / width, so even a flat image
+    // has AC components; the values match the reference implementation
+    #[test]
+    fn blurhash_solid_colors() {
+        assert_eq!(
+            blurhash(&solid([255, 255, 255], 8, 6), 8, 6, 4, 3),
+            "LsTSUA_3fQ_3~qt7fQt7fQfQfQfQ"
+        );
+        assert_eq!(
+            blurhash(&solid([255, 0, 0], 8, 6), 8, 6, 4, 3),
+            "LsTI:j]9fQ]9|csUfQsUfQfQfQfQ"
+        );
+        assert_eq!(blurhash(&solid([0, 0, 0], 4, 4), 4, 4, 1, 1), "000000");
+    }
+
+    #[test]
+    fn merge_nip94_tags() {
+        let mut info = FileInfo {
+            url: "https://example.com/upload".to_owned(),
+            sha256: "aa".to_owned(),
+            dim: Some((1, 1)),
+            ..FileInfo::default()
+        };
+        let tags = [
+            vec!["url", "https://example.com/bb.webp"],
+            vec!["m", "image/webp"],
+            vec!["x", "bb"],
+            vec!["dim", "640x480"],
+            vec!["blurhash", "L0TSUA"],
+            vec!["size"],
+            vec!["unknown", "value"],
+        ]
+        .map(|tag| tag.iter().map(|v| v.to_string()).collect::<Vec<_>>());
+        info.merge_nip94(&tags);
+        assert_eq!(info.url, "https://example.com/bb.webp");
+        assert_eq!(info.mime, "image/webp");
+        assert_eq!(info.sha256, "bb");
+        // transformed by the server
+        assert_eq!(info.original_sha256.as_deref(), Some("aa"));
+        assert_eq!(info.dim, Some((640, 480)));
+        assert_eq!(
+            info.imeta(),
+            Tag::Generic(
+                TagKind::Custom("imeta".to_owned()),
+                vec![
+                    "url https://example.com/bb.webp".to_owned(),
+                    "m image/webp".to_owned(),
+                    "x bb".to_owned(),
+                    "ox aa".to_owned(),
+                    "dim 640x480".to_owned(),
+                    "blurhash L0TSUA".to_owned(),
+                ]
+            )
+        );
+        // the same hash, and a dim that can't be read, are kept
+        info.merge_nip94(&[
+            vec!["x".to_owned(), "bb".to_owned()],
+            vec!["dim".to_owned(), "large".to_owned()],
+        ]);
+        assert_eq!(info.original_sha256.as_deref(), Some("aa"));
+        assert_eq!(info.dim, Some((640, 480)));
+    }
+
+    #[test]
+    fn imeta_without_optional_fields() {
+        let info = FileInfo {
+            url: "https://example.com/a".to_owned(),
+            sha256: "aa".to_owned(),
+            ..FileInfo::default()
+        };
+        assert_eq!(
+            info.imeta().as_vec(),
+            vec!["imeta", "url https://example.com/a", "x aa"]
+        );
+    }
+
+    #[test]
+    fn http_auth_header() {
+        let keys = Keys::generate();
+        let header = http_auth(&keys, "https://example.com/upload", "POST", "ab").unwrap();
+        let json = BASE64
+            .decode(header.strip_prefix("Nostr ").unwrap())
+            .unwrap();
+        let event = Event::from_json(String::from_utf8(json).unwrap()).unwrap();
+        assert!(event.verify().is_ok());
+        assert_eq!(event.kind, Kind::from(HTTP_AUTH_KIND));
+        assert_eq!(event.pubkey, keys.public_key());
+        let tags = event.tags.iter().map(|t| t.as_vec()).collect::<Vec<_>>();
+        assert!(tags.contains(&vec!["u".to_owned(), "https://example.com/upload".to_owned()]));
+        assert!(tags.contains(&vec!["method".to_owned(), "POST".to_owned()]));
+    }
+}
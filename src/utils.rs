@@ -1,128 +1,74 @@
-use chrono::Utc;
-use nostr_sdk::nostr::Timestamp;
+use nostr_sdk::nostr::nips::nip11::RelayInformationDocument;
+use nostr_sdk::nostr::prelude::{FromBech32, XOnlyPublicKey};
+use nostr_sdk::nostr::{Tag, Timestamp, Url};
 use rust_i18n::t;
-use std::fmt::Display;
-
-#[derive(Clone, Copy, PartialEq, PartialOrd, Eq, Ord)]
-pub struct DiffDayHourMinuteSecond {
-    pub seconds: i16,
-    pub hours: i16,
-    pub minutes: i16,
-    pub days: i16,
-    pub months: i16,
-    pub years: i16,
-}
-
-impl DiffDayHourMinuteSecond {
-    pub fn calc_diff_hours(t: Timestamp) -> Option<DiffDayHourMinuteSecond> {
-        let now = Timestamp::now();
-        let mut diff_seconds = now.as_i64() - t.as_i64();
-        if diff_seconds > 0 {
-            let years = diff_seconds / YEAR_SECONDS;
-            diff_seconds -= years * YEAR_SECONDS;
-            let months = diff_seconds / MONTH_SECONDS;
-            diff_seconds -= months * MONTH_SECONDS;
-            let days = diff_seconds / DAY_SECONDS;
-            diff_seconds -= days * DAY_SECONDS;
-            let hours = diff_seconds / HOUR_SECONDS;
-            diff_seconds -= hours * HOUR_SECONDS;
-            let minutes = diff_seconds / MINUTE_SECONDS;
-            diff_seconds -= minutes * MINUTE_SECONDS;
-            return Some(DiffDayHourMinuteSecond {
-                seconds: diff_seconds as i16,
-                hours: hours as i16,
-                minutes: minutes as i16,
-                days: days as i16,
-                years: years as i16,
-                months: months as i16,
-            });
-        }
-        None
-    }
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Blob, BlobPropertyBag, File, HtmlAnchorElement};
 
-    fn inner_to_string(&self) -> String {
-        let v = |v: i16| if v > 1 { "s" } else { "" };
-        let mut ret: Vec<_> = Vec::with_capacity(18);
-        if self.years > 0 {
-            ret.push(format!("{}", self.years));
-            ret.push(t!("year", plural = v(self.years)));
-            ret.push(",".to_owned());
-        }
-        if self.months > 0 {
-            ret.push(format!("{}", self.months));
-            ret.push(t!("month", plural = v(self.months)));
-            ret.push(",".to_owned());
-        }
-        if self.days > 0 {
-            ret.push(format!("{}", self.days));
-            ret.push(t!("day", plural = v(self.days)));
-            ret.push(",".to_owned());
-        }
-        if self.hours > 0 {
-            ret.push(format!("{}", self.hours));
-            ret.push(t!("hour", plural = v(self.hours)));
-        }
-        if self.minutes > 0 {
-            ret.push(format!("{}", self.minutes));
-            ret.push(t!("minute", plural = v(self.minutes)));
-        }
-        if self.seconds > 0 {
-            ret.push(format!("{}", self.seconds));
-            ret.push(t!("second", plural = v(self.seconds)));
-        }
-        if ret.is_empty() {
-            "".to_owned()
-        } else {
-            format!("{}{}", ret.join(""), t!("ago"))
-        }
-    }
-}
 const YEAR_SECONDS: i64 = 365 * 86400;
 const MONTH_SECONDS: i64 = 30 * 86400;
 const DAY_SECONDS: i64 = 86400;
 const HOUR_SECONDS: i64 = 3600;
 const MINUTE_SECONDS: i64 = 60;
 
-impl Display for DiffDayHourMinuteSecond {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.inner_to_string())
+// only the most significant unit, "3 min ago" or "2 天前"
+pub fn format_relative_time(t: Timestamp, now: Timestamp) -> String {
+    let diff = now.as_i64() - t.as_i64();
+    if diff >= YEAR_SECONDS {
+        t!("years ago", count = diff / YEAR_SECONDS)
+    } else if diff >= MONTH_SECONDS {
+        t!("months ago", count = diff / MONTH_SECONDS)
+    } else if diff >= DAY_SECONDS {
+        t!("days ago", count = diff / DAY_SECONDS)
+    } else if diff >= HOUR_SECONDS {
+        t!("hours ago", count = diff / HOUR_SECONDS)
+    } else if diff >= MINUTE_SECONDS {
+        t!("minutes ago", count = diff / MINUTE_SECONDS)
+    } else {
+        // the clock of the author may be a little ahead
+        t!("just now")
     }
 }
 
-pub fn calc_diff_hours_string(t: Timestamp) -> String {
-    if let Some(d) = DiffDayHourMinuteSecond::calc_diff_hours(t) {
-        d.to_string()
+// in the local time zone, the formats are in the locale files
+pub fn format_local_timestamp(seconds: i64) -> String {
+    use chrono::{Datelike, Days, Local, TimeZone};
+    let local = match Local.timestamp_opt(seconds, 0).earliest() {
+        Some(local) => local,
+        None => return "".to_owned(),
+    };
+    let today = Local::now().date_naive();
+    let date = local.date_naive();
+    let time = local.format(&t!("time format")).to_string();
+    if date == today {
+        time
+    } else if today.checked_sub_days(Days::new(1)) == Some(date) {
+        t!("yesterday at", time = time)
+    } else if date.year() == today.year() {
+        local.format(&t!("date format this year")).to_string()
     } else {
-        "".to_owned()
+        local.format(&t!("date format")).to_string()
     }
 }
 
-const TS_FORMAT: &'static str = "%Y-%m-%d %H:%M:%S";
-const TS_FORMAT1: &'static str = "%H:%M:%S";
-pub fn format_local_timestamp(seconds: i64) -> String {
-    use chrono::{Days, Local, LocalResult, TimeZone};
-    match Utc.timestamp_opt(seconds, 0) {
-        LocalResult::Single(utc) | LocalResult::Ambiguous(utc, _) => {
-            let now = Utc::now();
-            let now_native = now.date_naive();
-            let utc_native = utc.date_naive();
-            // Local.from_utc_datetime(utc)
-            let local_dt = Local.from_utc_datetime(&utc.naive_local());
-            // fixme: transfer utc to local
-            if utc_native == now_native {
-                local_dt.format(TS_FORMAT1).to_string()
-            } else if now_native.checked_sub_days(Days::new(1)).unwrap() == utc_native {
-                format!(
-                    "{}{}",
-                    t!("yesterday"),
-                    local_dt.format(TS_FORMAT1).to_string()
-                )
-            } else {
-                local_dt.format(TS_FORMAT).to_string()
-            }
-        }
-        LocalResult::None => "".to_owned(),
-    }
+// the value of `<input type="datetime-local">`, in the local time zone
+const DATETIME_LOCAL_FORMAT: &str = "%Y-%m-%dT%H:%M";
+
+pub fn parse_datetime_local(value: &str) -> Option<Timestamp> {
+    use chrono::{Local, NaiveDateTime, TimeZone};
+    let naive = NaiveDateTime::parse_from_str(value, DATETIME_LOCAL_FORMAT).ok()?;
+    let local = Local.from_local_datetime(&naive).earliest()?;
+    u64::try_from(local.timestamp()).ok().map(Timestamp::from)
+}
+
+pub fn datetime_local_value(timestamp: Timestamp) -> String {
+    use chrono::{Local, TimeZone};
+    Local
+        .timestamp_opt(timestamp.as_i64(), 0)
+        .earliest()
+        .map(|local| local.format(DATETIME_LOCAL_FORMAT).to_string())
+        .unwrap_or_default()
 }
 
 pub fn front_n_chars<T: AsRef<str>>(data: T, n: usize) -> String {
@@ -133,3 +79,288 @@ pub fn front_n_chars<T: AsRef<str>>(data: T, n: usize) -> String {
         .filter_map(|(i, c)| if i < n { Some(c) } else { None })
         .fold("".to_owned(), |acc, val| format!("{acc}{val}"))
 }
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NoteToken {
+    Text(String),
+    // `nostr:npub1...` of NIP-27
+    Mention(XOnlyPublicKey),
+    // without the `#`
+    Hashtag(String),
+    Url(String),
+}
+
+const MENTION_PREFIX: &str = "nostr:";
+
+fn note_word_token(word: &str) -> Option<(NoteToken, usize)> {
+    let is_tag_char = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    if let Some(rest) = word.strip_prefix(MENTION_PREFIX) {
+        let len = rest
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(rest.len());
+        let pk = XOnlyPublicKey::from_bech32(&rest[..len]).ok()?;
+        Some((NoteToken::Mention(pk), MENTION_PREFIX.len() + len))
+    } else if let Some(rest) = word.strip_prefix('#') {
+        let len = rest.find(|c| !is_tag_char(c)).unwrap_or(rest.len());
+        (len > 0).then(|| (NoteToken::Hashtag(rest[..len].to_owned()), len + 1))
+    } else if word.starts_with("https://") || word.starts_with("http://") {
+        // the punctuation after the link isn't part of it, but a `)` closing one in the link is
+        let mut url = word;
+        while let Some(last) = url.chars().last() {
+            let unbalanced = last == ')' && url.matches('(').count() < url.matches(')').count();
+            if !(unbalanced || ['.', ',', '!', '?', ';', ':'].contains(&last)) {
+                break;
+            }
+            url = &url[..url.len() - 1];
+        }
+        Some((NoteToken::Url(url.to_owned()), url.len()))
+    } else {
+        None
+    }
+}
+
+// the mentions, hashtags and links of the content, the rest is kept as text
+pub fn tokenize_note(content: &str) -> Vec<NoteToken> {
+    let mut tokens = vec![];
+    let mut text = String::new();
+    let mut rest = content;
+    while !rest.is_empty() {
+        let word_len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let word = &rest[..word_len];
+        match note_word_token(word) {
+            Some((token, len)) => {
+                if !text.is_empty() {
+                    tokens.push(NoteToken::Text(std::mem::take(&mut text)));
+                }
+                tokens.push(token);
+                text.push_str(&word[len..]);
+            }
+            None => text.push_str(word),
+        }
+        rest = &rest[word_len..];
+        let space_len = rest
+            .find(|c: char| !c.is_whitespace())
+            .unwrap_or(rest.len());
+        text.push_str(&rest[..space_len]);
+        rest = &rest[space_len..];
+    }
+    if !text.is_empty() {
+        tokens.push(NoteToken::Text(text));
+    }
+    tokens
+}
+
+// a `p` tag for every mentioned one and a `t` tag for every hashtag
+pub fn note_tags(content: &str) -> Vec<Tag> {
+    let mut tags: Vec<Tag> = vec![];
+    for token in tokenize_note(content) {
+        let tag = match token {
+            NoteToken::Mention(pk) => Tag::PubKey(pk, None),
+            NoteToken::Hashtag(hashtag) => Tag::Hashtag(hashtag.to_lowercase()),
+            _ => continue,
+        };
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+// `limitation` of NIP-11, not in `RelayInformationDocument` yet
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RelayLimitation {
+    pub min_pow_difficulty: Option<u8>,
+}
+
+impl RelayLimitation {
+    // the relays fill it in many ways, e.g. `null`, a string, or a difficulty no one can mine,
+    // such a field is ignored instead of failing the whole document
+    fn parse(text: &str) -> Self {
+        let value = serde_json::from_str::<serde_json::Value>(text).unwrap_or_default();
+        let limitation = &value["limitation"];
+        let min_pow_difficulty = limitation["min_pow_difficulty"]
+            .as_u64()
+            .and_then(|d| u8::try_from(d).ok());
+        Self { min_pow_difficulty }
+    }
+}
+
+// fetch the NIP-11 information document of the relay
+pub async fn fetch_relay_document(
+    uri: &Url,
+) -> anyhow::Result<(RelayInformationDocument, RelayLimitation)> {
+    let mut uri = uri.clone();
+    let scheme = match uri.scheme() {
+        "wss" => "https",
+        "ws" => "http",
+        _ => "",
+    };
+    if !scheme.is_empty() && uri.set_scheme(scheme).is_err() {
+        anyhow::bail!("invalid relay uri: {uri}");
+    }
+    let resp = gloo_net::http::Request::get(uri.as_str())
+        .header("Accept", "application/nostr+json")
+        .send()
+        .await?;
+    let text = resp.text().await?;
+    let doc = serde_json::from_str::<RelayInformationDocument>(&text)?;
+    Ok((doc, RelayLimitation::parse(&text)))
+}
+
+// read the file chosen by an `<input type="file">` as text
+pub async fn read_file(file: File) -> anyhow::Result<String> {
+    let text = JsFuture::from(file.text())
+        .await
+        .map_err(|e| anyhow::anyhow!("read {} error:{e:?}", file.name()))?;
+    text.as_string()
+        .ok_or_else(|| anyhow::anyhow!("{} is not a text file", file.name()))
+}
+
+// save the content as a file by the browser
+pub fn download(name: &str, mime: &str, content: &str) -> Result<(), JsValue> {
+    let parts = js_sys::Array::of1(&JsValue::from_str(content));
+    let options = BlobPropertyBag::new();
+    options.set_type(mime);
+    let blob = Blob::new_with_str_sequence_and_options(&parts, &options)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+    let document = web_sys::window().unwrap().document().unwrap();
+    let a = document
+        .create_element("a")?
+        .dyn_into::<HtmlAnchorElement>()?;
+    a.set_href(&url);
+    a.set_download(name);
+    a.click();
+    web_sys::Url::revoke_object_url(&url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ago(seconds: i64) -> String {
+        let now = 1_700_000_000i64;
+        format_relative_time(
+            Timestamp::from((now - seconds) as u64),
+            Timestamp::from(now as u64),
+        )
+    }
+
+    #[test]
+    fn relative_time_boundaries() {
+        assert_eq!(ago(0), "just now");
+        assert_eq!(ago(59), "just now");
+        assert_eq!(ago(60), "1 min ago");
+        assert_eq!(ago(59 * 60 + 59), "59 min ago");
+        assert_eq!(ago(60 * 60), "1 h ago");
+        assert_eq!(ago(23 * 3600 + 3599), "23 h ago");
+        assert_eq!(ago(24 * 3600), "1 d ago");
+        assert_eq!(ago(30 * 86400), "1 mo ago");
+        assert_eq!(ago(365 * 86400), "1 yr ago");
+        // the clock of the author is ahead
+        assert_eq!(ago(-5), "just now");
+        assert_eq!(ago(-86400), "just now");
+    }
+
+    fn text(s: &str) -> NoteToken {
+        NoteToken::Text(s.to_owned())
+    }
+
+    fn url(s: &str) -> NoteToken {
+        NoteToken::Url(s.to_owned())
+    }
+
+    fn hashtag(s: &str) -> NoteToken {
+        NoteToken::Hashtag(s.to_owned())
+    }
+
+    #[test]
+    fn tokenize_url_punctuation() {
+        assert_eq!(
+            tokenize_note("see https://example.com/a. ok"),
+            vec![text("see "), url("https://example.com/a"), text(". ok")]
+        );
+        assert_eq!(
+            tokenize_note("https://example.com?q=1, http://example.com!?"),
+            vec![
+                url("https://example.com?q=1"),
+                text(", "),
+                url("http://example.com"),
+                text("!?")
+            ]
+        );
+        // the parentheses of the link are kept, the one around it isn't
+        assert_eq!(
+            tokenize_note("https://en.wikipedia.org/wiki/Nostr_(protocol)."),
+            vec![url("https://en.wikipedia.org/wiki/Nostr_(protocol)"), text(".")]
+        );
+        assert_eq!(
+            tokenize_note("(https://example.com/a)"),
+            vec![text("(https://example.com/a)")]
+        );
+        assert_eq!(
+            tokenize_note("https://example.com/a)"),
+            vec![url("https://example.com/a"), text(")")]
+        );
+    }
+
+    #[test]
+    fn tokenize_hashtags() {
+        assert_eq!(
+            tokenize_note("#nostr, #rust_lang-2!"),
+            vec![hashtag("nostr"), text(", "), hashtag("rust_lang-2"), text("!")]
+        );
+        // not at the start of a word
+        assert_eq!(tokenize_note("a#b c#"), vec![text("a#b c#")]);
+        assert_eq!(tokenize_note("# ##"), vec![text("# ##")]);
+        assert_eq!(tokenize_note("#中文"), vec![hashtag("中文")]);
+    }
+
+    #[test]
+    fn tokenize_mentions() {
+        let keys = nostr_sdk::nostr::Keys::generate();
+        let npub = nostr_sdk::nostr::prelude::ToBech32::to_bech32(&keys.public_key()).unwrap();
+        assert_eq!(
+            tokenize_note(&format!("hi nostr:{npub}, bye")),
+            vec![
+                text("hi "),
+                NoteToken::Mention(keys.public_key()),
+                text(", bye")
+            ]
+        );
+        // an invalid bech32 is kept as text
+        let last = if npub.ends_with('q') { 'p' } else { 'q' };
+        let invalid = format!("nostr:{}{last}", &npub[..npub.len() - 1]);
+        assert_eq!(tokenize_note(&invalid), vec![text(&invalid)]);
+        assert_eq!(tokenize_note("nostr:npub1"), vec![text("nostr:npub1")]);
+        assert_eq!(tokenize_note("nostr:"), vec![text("nostr:")]);
+    }
+
+    #[test]
+    fn note_tags_dedup() {
+        let keys = nostr_sdk::nostr::Keys::generate();
+        let npub = nostr_sdk::nostr::prelude::ToBech32::to_bech32(&keys.public_key()).unwrap();
+        let content = format!("#Nostr nostr:{npub} #nostr #NOSTR nostr:{npub} #rust");
+        assert_eq!(
+            note_tags(&content),
+            vec![
+                Tag::Hashtag("nostr".to_owned()),
+                Tag::PubKey(keys.public_key(), None),
+                Tag::Hashtag("rust".to_owned()),
+            ]
+        );
+        assert!(note_tags("a#b https://example.com/#x").is_empty());
+    }
+
+    #[test]
+    fn relay_limitation_lenient() {
+        let parse = |text: &str| RelayLimitation::parse(text).min_pow_difficulty;
+        assert_eq!(parse(r#"{"limitation":{"min_pow_difficulty":20}}"#), Some(20));
+        assert_eq!(parse(r#"{"name":"relay"}"#), None);
+        assert_eq!(parse(r#"{"limitation":null}"#), None);
+        assert_eq!(parse(r#"{"limitation":{"min_pow_difficulty":null}}"#), None);
+        assert_eq!(parse(r#"{"limitation":{"min_pow_difficulty":"20"}}"#), None);
+        assert_eq!(parse(r#"{"limitation":{"min_pow_difficulty":1000}}"#), None);
+        assert_eq!(parse(r#"{"limitation":{"min_pow_difficulty":-1}}"#), None);
+        assert_eq!(parse("not json"), None);
+    }
+}
@@ -0,0 +1,687 @@
+use crate::agent::{Miner, PowOutput, PowRequest};
+use crate::ctxs::*;
+use crate::upload::{upload, Media};
+use crate::utils::*;
+use crate::views::NoteContent;
+use indexmap::IndexMap;
+use nostr_sdk::nostr::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use wasm_bindgen::JsCast;
+use web_sys::{ClipboardEvent, FileList, HtmlInputElement, HtmlSelectElement, HtmlTextAreaElement};
+use yew::prelude::*;
+
+const MAX_SUGGESTIONS: usize = 8;
+// NIP-40, how long the note lives, in seconds
+const EXPIRATIONS: [(u64, &str); 5] = [
+    (0, "never expires"),
+    (60 * 60, "expires in an hour"),
+    (24 * 60 * 60, "expires in a day"),
+    (7 * 24 * 60 * 60, "expires in a week"),
+    (30 * 24 * 60 * 60, "expires in a month"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+struct Suggestion {
+    label: String,
+    detail: String,
+    // replaces the `@name` or `#tag` being typed
+    insert: String,
+}
+
+// the `@` or `#` word before the cursor, in byte offsets of the content
+#[derive(Debug, Clone, PartialEq)]
+struct Suggest {
+    start: usize,
+    end: usize,
+    items: Vec<Suggestion>,
+    selected: usize,
+}
+
+// `selectionStart` counts in utf-16
+fn utf16_to_byte(s: &str, pos: u32) -> usize {
+    let mut units = 0;
+    for (i, c) in s.char_indices() {
+        if units >= pos as usize {
+            return i;
+        }
+        units += c.len_utf16();
+    }
+    s.len()
+}
+
+fn byte_to_utf16(s: &str, pos: usize) -> u32 {
+    s[..pos].encode_utf16().count() as u32
+}
+
+// the contacts, then the authors of the cached notes by their npub
+fn suggest_mentions(
+    user_contact_ctx: &UserContactContext,
+    user_events_ctx: &UserEventContext,
+    query: &str,
+) -> Vec<Suggestion> {
+    let query = query.to_lowercase();
+    let npub = |pk: &XOnlyPublicKey| pk.to_bech32().unwrap_or_default();
+    let suggestion = |pk: &XOnlyPublicKey, label: String, detail: String| Suggestion {
+        label,
+        detail,
+        insert: format!("nostr:{}", npub(pk)),
+    };
+    let contacts = user_contact_ctx.data.borrow();
+    let mut items = contacts
+        .iter()
+        .filter_map(|(pk, info)| {
+            let name = info.nickname.clone().unwrap_or_default();
+            let nip05 = info.nip05.clone().unwrap_or_default();
+            let hit = name.to_lowercase().contains(&query)
+                || nip05.to_lowercase().contains(&query)
+                || npub(pk).starts_with(&query);
+            hit.then(|| {
+                let short = front_n_chars(npub(pk), 16);
+                let label = if name.is_empty() { short.clone() } else { name };
+                let detail = if nip05.is_empty() { short } else { nip05 };
+                suggestion(pk, label, detail)
+            })
+        })
+        .take(MAX_SUGGESTIONS)
+        .collect::<Vec<_>>();
+    if items.len() < MAX_SUGGESTIONS && !query.is_empty() {
+        let mut seen = HashSet::new();
+        let authors = user_events_ctx
+            .events
+            .borrow()
+            .values()
+            .map(|e| e.event.pubkey)
+            .filter(|pk| !contacts.contains_key(pk) && npub(pk).starts_with(&query))
+            .filter(|pk| seen.insert(*pk))
+            .collect::<Vec<_>>();
+        items.extend(
+            authors
+                .iter()
+                .take(MAX_SUGGESTIONS - items.len())
+                .map(|pk| suggestion(pk, front_n_chars(npub(pk), 16), String::new())),
+        );
+    }
+    items
+}
+
+// the hashtags of the cached notes, the most used first
+fn suggest_hashtags(user_events_ctx: &UserEventContext, query: &str) -> Vec<Suggestion> {
+    let query = query.to_lowercase();
+    let mut counts = HashMap::<String, usize>::new();
+    for e in user_events_ctx.events.borrow().values() {
+        for tag in e.event.tags.iter() {
+            if let Tag::Hashtag(hashtag) = tag {
+                let hashtag = hashtag.to_lowercase();
+                if hashtag.starts_with(&query) {
+                    *counts.entry(hashtag).or_default() += 1;
+                }
+            }
+        }
+    }
+    let mut hashtags = counts.into_iter().collect::<Vec<_>>();
+    hashtags.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    hashtags
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(hashtag, count)| Suggestion {
+            label: format!("#{hashtag}"),
+            detail: count.to_string(),
+            insert: format!("#{hashtag}"),
+        })
+        .collect()
+}
+
+struct Upload {
+    name: String,
+    // bytes sent and the total
+    progress: (f64, f64),
+    error: Option<String>,
+}
+
+impl Upload {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            progress: (0.0, 0.0),
+            error: None,
+        }
+    }
+}
+
+#[function_component(Compose)]
+pub fn compose() -> Html {
+    let locale_ctx = use_context::<I18nLocaleContext>().unwrap();
+    let lang = locale_ctx.to_string();
+    let user_ctx = use_context::<UserContext>().unwrap();
+    let send_event_ctx = use_context::<SendMsgEventContext>().unwrap();
+    let pow_ctx = use_context::<PowContext>().unwrap();
+    let relay_ctx = use_context::<RelayContext>().unwrap();
+    let user_contact_ctx = use_context::<UserContactContext>().unwrap();
+    let user_events_ctx = use_context::<UserEventContext>().unwrap();
+    let draft_ctx = use_context::<DraftContext>().unwrap();
+    let media_ctx = use_context::<MediaServerContext>().unwrap();
+    let schedule_ctx = use_context::<ScheduleContext>().unwrap();
+    let pk = user_ctx.keys.as_ref().map(|keys| keys.public_key());
+    let content = use_state(|| pk.map(|pk| draft_ctx.get(&pk)).unwrap_or_default());
+    {
+        // the draft of the account switched to
+        let content = content.clone();
+        let draft_ctx = draft_ctx.clone();
+        use_effect_with_deps(
+            move |pk| {
+                content.set(pk.map(|pk| draft_ctx.get(&pk)).unwrap_or_default());
+                || ()
+            },
+            pk,
+        );
+    }
+    let suggest = use_state(|| None::<Suggest>);
+    // NIP-36, Some with the reason, which may be empty
+    let content_warning = use_state(|| None::<String>);
+    let cw_toggle = {
+        let content_warning = content_warning.clone();
+        Callback::from(move |_: MouseEvent| {
+            content_warning.set(match *content_warning {
+                Some(_) => None,
+                None => Some(String::new()),
+            })
+        })
+    };
+    let cw_input = {
+        let content_warning = content_warning.clone();
+        Callback::from(move |e: InputEvent| {
+            let input = e.target_unchecked_into::<HtmlInputElement>();
+            content_warning.set(Some(input.value()));
+        })
+    };
+    // seconds after the sending, 0 never expires
+    let expires_in = use_state(|| 0u64);
+    let expiration_change = {
+        let expires_in = expires_in.clone();
+        Callback::from(move |e: yew::Event| {
+            let secs = e
+                .target_dyn_into::<HtmlSelectElement>()
+                .and_then(|select| EXPIRATIONS.get(select.selected_index() as usize));
+            if let Some((secs, _)) = secs {
+                expires_in.set(*secs);
+            }
+        })
+    };
+    let expirations = EXPIRATIONS
+        .iter()
+        .map(|(secs, name)| {
+            html!(<option key={*name} selected={*secs == *expires_in}>{rust_i18n::t!(name, locale=&lang)}</option>)
+        })
+        .collect::<Html>();
+    // the value of the time input when the note is scheduled
+    let schedule = use_state(|| None::<String>);
+    // signed when it's queued, or when it's published
+    let sign_now = use_state(|| true);
+    let schedule_toggle = {
+        let schedule = schedule.clone();
+        Callback::from(move |_: MouseEvent| {
+            schedule.set(match *schedule {
+                Some(_) => None,
+                // an hour later by default
+                None => Some(datetime_local_value(Timestamp::now() + 60 * 60_u64)),
+            })
+        })
+    };
+    let schedule_input = {
+        let schedule = schedule.clone();
+        Callback::from(move |e: InputEvent| {
+            let input = e.target_unchecked_into::<HtmlInputElement>();
+            schedule.set(Some(input.value()));
+        })
+    };
+    let sign_now_change = {
+        let sign_now = sign_now.clone();
+        Callback::from(move |e: yew::Event| {
+            let input = e.target_unchecked_into::<HtmlInputElement>();
+            sign_now.set(input.checked());
+        })
+    };
+    // Some(None) if the time isn't in the future
+    let publish_at = schedule.as_ref().map(|value| {
+        parse_datetime_local(value).filter(|publish_at| *publish_at > Timestamp::now())
+    });
+    // the mining of the note being sent, dropping it cancels
+    let miner = use_mut_ref(|| None::<Miner>);
+    // hashes tried, best difficulty so far, and the target
+    let mining = use_state(|| None::<(u64, u8, u8)>);
+    let txtarea_ref = use_node_ref();
+    let empty_txt_area = use_state(|| false);
+    // the cursor after a suggestion is inserted, set once the value is rendered
+    let pending_cursor = use_mut_ref(|| None::<u32>);
+    {
+        let txtarea_ref = txtarea_ref.clone();
+        let pending_cursor = pending_cursor.clone();
+        use_effect(move || {
+            if let Some(pos) = pending_cursor.borrow_mut().take() {
+                if let Some(txt_area) = txtarea_ref.cast::<HtmlTextAreaElement>() {
+                    txt_area.focus().ok();
+                    txt_area.set_selection_range(pos, pos).ok();
+                }
+            }
+            || ()
+        });
+    }
+    // the uploads finish later, the url is added to what is typed by then
+    let latest = use_mut_ref(String::new);
+    *latest.borrow_mut() = (*content).clone();
+    let set_content = {
+        let content = content.clone();
+        let draft_ctx = draft_ctx.clone();
+        let latest = latest.clone();
+        Callback::from(move |value: String| {
+            if let Some(pk) = pk {
+                draft_ctx.dispatch(DraftAction::Save(pk, value.clone()));
+            }
+            *latest.borrow_mut() = value.clone();
+            content.set(value);
+        })
+    };
+    let uploads = use_mut_ref(IndexMap::<u32, Upload>::new);
+    let next_upload = use_mut_ref(|| 0u32);
+    // the imeta tags of the uploaded files, sent with the note
+    let media = use_mut_ref(Vec::<Media>::new);
+    let update = use_force_update();
+    let upload_files = {
+        let lang = lang.clone();
+        let user_ctx = user_ctx.clone();
+        let uploads = uploads.clone();
+        let media = media.clone();
+        let latest = latest.clone();
+        let set_content = set_content.clone();
+        let update = update.clone();
+        Callback::from(move |files: FileList| {
+            if user_ctx.show_modal() {
+                let cb = user_ctx.show_modal_cb.as_ref().unwrap().clone();
+                cb.emit(true);
+                return;
+            }
+            let keys = user_ctx.keys.clone().unwrap();
+            for file in (0..files.length()).filter_map(|i| files.get(i)) {
+                let id = {
+                    let mut next = next_upload.borrow_mut();
+                    *next += 1;
+                    *next
+                };
+                let mut entry = Upload::new(file.name());
+                if media_ctx.url.is_none() {
+                    entry.error = Some(rust_i18n::t!("no media server", locale = &lang));
+                    uploads.borrow_mut().insert(id, entry);
+                    continue;
+                }
+                uploads.borrow_mut().insert(id, entry);
+                let on_progress = {
+                    let uploads = uploads.clone();
+                    let update = update.clone();
+                    Callback::from(move |(sent, total)| {
+                        if let Some(upload) = uploads.borrow_mut().get_mut(&id) {
+                            upload.progress = (sent, total);
+                        }
+                        update.force_update();
+                    })
+                };
+                let server = (*media_ctx).clone();
+                let keys = keys.clone();
+                let uploads = uploads.clone();
+                let media = media.clone();
+                let latest = latest.clone();
+                let set_content = set_content.clone();
+                let update = update.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    match upload(&server, &keys, file, on_progress).await {
+                        Ok(m) => {
+                            uploads.borrow_mut().remove(&id);
+                            let mut value = latest.borrow().clone();
+                            if !value.is_empty() && !value.ends_with(char::is_whitespace) {
+                                value.push(' ');
+                            }
+                            value.push_str(&m.url);
+                            media.borrow_mut().push(m);
+                            set_content.emit(value);
+                        }
+                        Err(e) => {
+                            log::warn!("upload error:{e:?}");
+                            if let Some(upload) = uploads.borrow_mut().get_mut(&id) {
+                                upload.error = Some(e.to_string());
+                            }
+                        }
+                    }
+                    update.force_update();
+                });
+            }
+            update.force_update();
+        })
+    };
+    let on_dragover = Callback::from(|e: DragEvent| e.prevent_default());
+    let on_drop = {
+        let upload_files = upload_files.clone();
+        Callback::from(move |e: DragEvent| {
+            let files = e.data_transfer().and_then(|data| data.files());
+            if let Some(files) = files.filter(|files| files.length() > 0) {
+                e.prevent_default();
+                upload_files.emit(files);
+            }
+        })
+    };
+    // the text is pasted as usual
+    let on_paste = Callback::from(move |e: yew::Event| {
+        let files = e
+            .dyn_ref::<ClipboardEvent>()
+            .and_then(|e| e.clipboard_data())
+            .and_then(|data| data.files());
+        if let Some(files) = files.filter(|files| files.length() > 0) {
+            e.prevent_default();
+            upload_files.emit(files);
+        }
+    });
+    let upload_list = uploads
+        .borrow()
+        .iter()
+        .map(|(id, upload)| {
+            let onclick = {
+                let id = *id;
+                let uploads = uploads.clone();
+                let update = update.clone();
+                Callback::from(move |_: MouseEvent| {
+                    uploads.borrow_mut().remove(&id);
+                    update.force_update();
+                })
+            };
+            let (sent, total) = upload.progress;
+            html!(
+                <div key={*id} class="compose-upload is-flex is-align-items-center">
+                    <span class="is-size-7 mr-2">{&upload.name}</span>
+                    if let Some(error) = &upload.error {
+                        <span class="is-size-7 has-text-danger mr-2">{rust_i18n::t!("upload failed", locale=&lang, error = error)}</span>
+                        <button class="delete is-small" {onclick}></button>
+                    } else {
+                        <progress class="progress is-small is-info" value={sent.to_string()} max={total.max(1.0).to_string()}></progress>
+                    }
+                </div>
+            )
+        })
+        .collect::<Html>();
+    let on_input = {
+        let set_content = set_content.clone();
+        let suggest = suggest.clone();
+        let empty_txt_area = empty_txt_area.clone();
+        let user_contact_ctx = user_contact_ctx.clone();
+        let user_events_ctx = user_events_ctx.clone();
+        Callback::from(move |e: InputEvent| {
+            let txt_area = e.target_unchecked_into::<HtmlTextAreaElement>();
+            let value = txt_area.value();
+            let cursor = txt_area
+                .selection_start()
+                .ok()
+                .flatten()
+                .map(|pos| utf16_to_byte(&value, pos))
+                .unwrap_or(value.len());
+            let start = value[..cursor]
+                .rfind(char::is_whitespace)
+                .map(|i| i + value[i..].chars().next().unwrap().len_utf8())
+                .unwrap_or(0);
+            let word = &value[start..cursor];
+            let items = if let Some(query) = word.strip_prefix('@') {
+                suggest_mentions(&user_contact_ctx, &user_events_ctx, query)
+            } else if let Some(query) = word.strip_prefix('#') {
+                suggest_hashtags(&user_events_ctx, query)
+            } else {
+                vec![]
+            };
+            suggest.set((!items.is_empty()).then(|| Suggest {
+                start,
+                end: cursor,
+                items,
+                selected: 0,
+            }));
+            empty_txt_area.set(false);
+            set_content.emit(value);
+        })
+    };
+    let accept = {
+        let content = content.clone();
+        let suggest = suggest.clone();
+        let set_content = set_content.clone();
+        let pending_cursor = pending_cursor.clone();
+        Callback::from(move |index: usize| {
+            let cur = match (*suggest).clone() {
+                Some(cur) => cur,
+                None => return,
+            };
+            if let Some(item) = cur.items.get(index) {
+                let mut value = (*content).clone();
+                let insert = format!("{} ", item.insert);
+                value.replace_range(cur.start..cur.end, &insert);
+                *pending_cursor.borrow_mut() =
+                    Some(byte_to_utf16(&value, cur.start + insert.len()));
+                set_content.emit(value);
+            }
+            suggest.set(None);
+        })
+    };
+    let cancel_clk = {
+        let miner = miner.clone();
+        let mining = mining.clone();
+        Callback::from(move |_: MouseEvent| {
+            miner.borrow_mut().take();
+            mining.set(None);
+        })
+    };
+    let send = {
+        let content = content.clone();
+        let content_warning = content_warning.clone();
+        let expires_in = expires_in.clone();
+        let schedule = schedule.clone();
+        let sign_now = sign_now.clone();
+        let user_ctx = user_ctx.clone();
+        let draft_ctx = draft_ctx.clone();
+        let miner = miner.clone();
+        let mining = mining.clone();
+        let empty_txt_area = empty_txt_area.clone();
+        Callback::from(move |_: ()| {
+            if user_ctx.show_modal() {
+                let cb = user_ctx.show_modal_cb.as_ref().unwrap().clone();
+                cb.emit(true);
+                return;
+            }
+            if mining.is_some() {
+                log::debug!("mining, not sent again");
+                return;
+            }
+            if content.trim().is_empty() {
+                empty_txt_area.set(true);
+                return;
+            }
+            let publish_at = match schedule.as_ref().map(|value| parse_datetime_local(value)) {
+                Some(Some(publish_at)) if publish_at > Timestamp::now() => Some(publish_at),
+                Some(_) => return,
+                None => None,
+            };
+            let keys = user_ctx.keys.as_ref().unwrap();
+            // NIP-27, the mentions and hashtags are tagged too
+            let mut tags = note_tags(&content);
+            // the files still in the note
+            tags.extend(
+                media
+                    .borrow()
+                    .iter()
+                    .filter(|m| content.contains(&m.url))
+                    .map(|m| m.imeta.clone()),
+            );
+            if let Some(reason) = content_warning.as_ref() {
+                let reason = reason.trim();
+                tags.push(Tag::ContentWarning {
+                    reason: (!reason.is_empty()).then(|| reason.to_owned()),
+                });
+            }
+            if *expires_in > 0 {
+                let from = publish_at.unwrap_or_else(Timestamp::now);
+                tags.push(Tag::Expiration(from + *expires_in));
+            }
+            // sent or queued, the draft isn't needed
+            let clear = {
+                let content = content.clone();
+                let draft_ctx = draft_ctx.clone();
+                let pk = keys.public_key();
+                let media = media.clone();
+                let content_warning = content_warning.clone();
+                let expires_in = expires_in.clone();
+                let schedule = schedule.clone();
+                move || {
+                    content.set(String::new());
+                    content_warning.set(None);
+                    expires_in.set(0);
+                    schedule.set(None);
+                    media.borrow_mut().clear();
+                    draft_ctx.dispatch(DraftAction::Clear(pk));
+                }
+            };
+            let builder = EventBuilder::new_text_note(content.as_str(), &tags);
+            let difficulty = pow_ctx.target(
+                relay_ctx
+                    .list
+                    .borrow()
+                    .values()
+                    .filter_map(|info| info.uri.as_ref()),
+            );
+            // with the nonce if it's mined, sent now or queued
+            let done: Rc<dyn Fn(UnsignedEvent)> = if let Some(publish_at) = publish_at {
+                // published by the app when it's due, signed at the scheduled time so the id keeps the work
+                let keys = keys.clone();
+                let sign_now = *sign_now;
+                let schedule_ctx = schedule_ctx.clone();
+                let id = js_sys::Date::now() as u64;
+                Rc::new(move |unsigned: UnsignedEvent| {
+                    let mut note = ScheduledNote {
+                        id,
+                        pubkey: unsigned.pubkey,
+                        content: unsigned.content,
+                        tags: unsigned.tags,
+                        publish_at,
+                        signed: None,
+                        missed: false,
+                        error: None,
+                        sending: false,
+                    };
+                    if sign_now {
+                        note.signed = note.sign(&keys);
+                    }
+                    schedule_ctx.dispatch(ScheduleAction::Add(note));
+                    clear();
+                })
+            } else {
+                let keys = keys.clone();
+                let send_event_ctx = send_event_ctx.clone();
+                Rc::new(move |unsigned: UnsignedEvent| match unsigned.sign(&keys) {
+                    Ok(e) => {
+                        clear();
+                        send_event_ctx.dispatch(ClientMessage::new_event(e));
+                    }
+                    Err(e) => log::warn!("{e}"),
+                })
+            };
+            if difficulty == 0 {
+                done(builder.to_unsigned_event(keys.public_key()));
+                return;
+            }
+            // NIP-13, the nonce is mined in a worker, and the event is signed here
+            let on_output = {
+                let miner = miner.clone();
+                let mining = mining.clone();
+                Callback::from(move |output| match output {
+                    PowOutput::Progress(hashes, best) => {
+                        mining.set(Some((hashes, best, difficulty)))
+                    }
+                    PowOutput::Done(unsigned) => {
+                        // dropped later, it's running this callback
+                        let finished = miner.borrow_mut().take();
+                        wasm_bindgen_futures::spawn_local(async move { drop(finished) });
+                        mining.set(None);
+                        done(unsigned);
+                    }
+                    PowOutput::Ready => {}
+                })
+            };
+            let mut req = PowRequest::new(keys, builder, difficulty);
+            if let Some(publish_at) = publish_at {
+                req = req.at(publish_at);
+            }
+            match Miner::start(req, on_output) {
+                Ok(m) => {
+                    *miner.borrow_mut() = Some(m);
+                    mining.set(Some((0, 0, difficulty)));
+                }
+                Err(e) => log::error!("start pow worker error:{e:?}"),
+            }
+        })
+    };
+    let send_clk = {
+        let send = send.clone();
+        Callback::from(move |_: MouseEvent| send.emit(()))
+    };
+    let on_keydown = {
+        let suggest = suggest.clone();
+        let accept = accept.clone();
+        Callback::from(move |e: KeyboardEvent| {
+            if (e.ctrl_key() || e.meta_key()) && e.key() == "Enter" {
+                e.prevent_default();
+                suggest.set(None);
+                send.emit(());
+                return;
+            }
+            let cur = match (*suggest).clone() {
+                Some(cur) => cur,
+                None => return,
+            };
+            let len = cur.items.len();
+            let select = |selected| {
+                suggest.set(Some(Suggest {
+                    selected,
+                    ..cur.clone()
+                }))
+            };
+            match e.key().as_str() {
+                "ArrowDown" => select((cur.selected + 1) % len),
+                "ArrowUp" => select((cur.selected + len - 1) % len),
+                "Enter" | "Tab" => accept.emit(cur.selected),
+                "Escape" => suggest.set(None),
+                _ => return,
+            }
+            e.prevent_default();
+        })
+    };
+    let suggestions = (*suggest)
+        .as_ref()
+        .map(|cur| {
+            cur.items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| {
+                    let accept = accept.clone();
+                    // before the blur of the textarea
+                    let onmousedown = Callback::from(move |e: MouseEvent| {
+                        e.prevent_default();
+                        accept.emit(i);
+                    });
+                    html!(
+                        <a key={item.insert.clone()} class={classes!("dropdown-item", if i == cur.selected {"is-active"} else {""})} {onmousedown}>
+                            <span>{&item.label}</span>
+                            <small class="ml-2 has-text-grey">{&item.detail}</small>
+                        </a>
+                    )
+                })
+                .collect::<Html>()
+        })
+        .unwrap_or_default();
+    let on_blur = {
+        let suggest = suggest.clone();
+        Callback::from(move |_: FocusEvent| suggest.set(None))
+    };
+    include!("html/compose.html")
+}
@@ -58,6 +58,8 @@ pub enum EntryMsg {
     Confirm,
     Cancel,
     Check,
+    ToggleGroup(String),
+    Open,
 }
 
 impl Entry {
@@ -170,6 +172,7 @@ impl Component for Entry {
                                 avatar_url: None, // todo
                                 relay,
                                 nickname: Some(Self::input_node_val(&self.nickname_node)),
+                                nip05: ctx.props().info.nip05.clone(),
                             },
                         ));
                         ctx.link().send_message(Delete);
@@ -198,8 +201,21 @@ impl Component for Entry {
                 ctx.props().cb_action.emit(EntryCbAction::Check(item));
                 update = true;
             }
-            _ => {
-                log::info!("{msg:?}");
+            Open => {
+                if let Some(pk) = ctx.props().info.pubkey {
+                    self.user_contact_handle
+                        .dispatch(UserContactAction::Recent(vec![pk]));
+                }
+            }
+            ToggleGroup(name) => {
+                if let Some(pk) = ctx.props().info.pubkey {
+                    self.user_contact_handle
+                        .dispatch(UserContactAction::ToggleGroupMember(name, pk));
+                }
+            }
+            UserContactCtx(user_contact_handle) => {
+                self.user_contact_handle = user_contact_handle;
+                update = true;
             }
         }
         update
@@ -210,6 +226,7 @@ impl Component for Entry {
         let check = ctx.props().check;
         // log::info!("is_edit-:{is_edit}");
         let check_btn_clk = { ctx.link().callback(move |_: MouseEvent| EntryMsg::Check) };
+        let open_clk = ctx.link().callback(|_: MouseEvent| EntryMsg::Open);
         let update_confirm_btn_clk = ctx.link().callback(move |_: MouseEvent| {
             if is_edit {
                 EntryMsg::Confirm
@@ -254,6 +271,26 @@ impl Component for Entry {
             .or(Some(Self::input_node_val(&self.relay_node)))
             .unwrap();
 
+        let groups = if let Some(pk) = info.pubkey.as_ref().filter(|_| !is_edit) {
+            self.user_contact_handle
+                .groups
+                .borrow()
+                .keys()
+                .map(|name| {
+                    let member = self.user_contact_handle.is_in_group(name, pk);
+                    let group = name.clone();
+                    let onclick = ctx
+                        .link()
+                        .callback(move |_: MouseEvent| EntryMsg::ToggleGroup(group.clone()));
+                    html!(
+                        <a key={name.clone()} {onclick}
+                            class={classes!("tag", "is-link", if member {""} else {"is-light"})}>{name}</a>
+                    )
+                })
+                .collect::<Html>()
+        } else {
+            html!(<></>)
+        };
         include!("./components/entry.html")
     }
 }
@@ -0,0 +1,56 @@
+use crate::ctxs::*;
+
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+#[function_component(Groups)]
+pub fn groups() -> Html {
+    let user_contact_ctx = use_context::<UserContactContext>().unwrap();
+    let input_ref = use_node_ref();
+    let add_group = {
+        let user_contact_ctx_clone = user_contact_ctx.clone();
+        let input_ref = input_ref.clone();
+        Callback::from(move |_: ()| {
+            if let Some(input) = input_ref.cast::<HtmlInputElement>() {
+                let name = input.value().trim().to_owned();
+                if !name.is_empty() {
+                    user_contact_ctx_clone.dispatch(UserContactAction::AddGroup(name));
+                    input.set_value("");
+                }
+            }
+        })
+    };
+    let add_clk = {
+        let add_group = add_group.clone();
+        Callback::from(move |_: MouseEvent| add_group.emit(()))
+    };
+    let add_keydown = Callback::from(move |e: KeyboardEvent| {
+        if e.key() == "Enter" {
+            add_group.emit(());
+        }
+    });
+    let groups = user_contact_ctx
+        .groups
+        .borrow()
+        .iter()
+        .map(|(name, group)| {
+            let remove_clk = {
+                let user_contact_ctx_clone = user_contact_ctx.clone();
+                let name = name.clone();
+                Callback::from(move |_: MouseEvent| {
+                    user_contact_ctx_clone.dispatch(UserContactAction::RemoveGroup(name.clone()))
+                })
+            };
+            html!(
+                <div class="control" key={name.clone()}>
+                    <div class="tags has-addons">
+                        <span class="tag is-link is-light">{name}</span>
+                        <span class="tag">{group.members.len()}</span>
+                        <a class="tag is-delete" onclick={remove_clk}></a>
+                    </div>
+                </div>
+            )
+        })
+        .collect::<Html>();
+    include!("./components/groups.html")
+}
@@ -1,12 +1,16 @@
 mod entry;
+mod groups;
 mod recent;
+mod transfer;
 use std::borrow::Borrow;
 use std::collections::HashSet;
 use std::mem::swap;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 use self::entry::*;
+use self::groups::*;
 use self::recent::*;
+use self::transfer::*;
 use crate::ctxs::*;
 
 use yew::prelude::*;
@@ -32,7 +36,9 @@ pub enum ContactMsg {
     CbAction(EntryCbAction), // revert or not
     // CheckOne(EntryItem),
     // RemoveOne(EntryItem),
-    DeleteCheckedUserContactInfo, // Delete all checked contacts
+    DeleteCheckedUserContactInfo,       // Delete all checked contacts
+    ResolveSync(ContactSyncResolution), // resolve the diff with the contact list on relays
+    RetrySync,                          // fetch the contact list on relays again
 }
 static PRIVATE_ID_COUNTER: AtomicUsize = AtomicUsize::new(1);
 impl Contact {
@@ -95,6 +101,104 @@ impl Contact {
             .collect::<Html>()
     }
 
+    fn sync_view(&self, ctx: &Context<Self>) -> Html {
+        let preview = match &self.user_contact_handle.sync {
+            ContactSync::Preview(preview) => preview.clone(),
+            ContactSync::Pending => {
+                return html!(
+                    <p class="help is-info">{rust_i18n::t!("syncing contacts")}</p>
+                )
+            }
+            ContactSync::Synced => return html!(<></>),
+            ContactSync::Failed => {
+                let onclick = ctx.link().callback(|_: MouseEvent| ContactMsg::RetrySync);
+                return html!(
+                    <div class="notification is-warning is-light">
+                        <p>{rust_i18n::t!("fetch contact list failed")}</p>
+                        <button class="button is-small is-warning" {onclick}>{rust_i18n::t!("retry")}</button>
+                    </div>
+                );
+            }
+        };
+        let name = |info: &UserContactInfo| {
+            let pk = info.pubkey.as_ref().unwrap().to_string();
+            let relay = info
+                .relay
+                .as_ref()
+                .map(|u| u.to_string())
+                .unwrap_or_default();
+            format!(
+                "{} {}",
+                info.nickname
+                    .clone()
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or(pk.chars().take(12).collect()),
+                relay
+            )
+        };
+        let tags = |infos: &[UserContactInfo], class: &'static str| {
+            infos
+                .iter()
+                .map(|info| {
+                    html!(
+                        <span class={classes!("tag", class, "is-light")}
+                            title={info.pubkey.as_ref().unwrap().to_string()}>{name(info)}</span>
+                    )
+                })
+                .collect::<Html>()
+        };
+        let resolve_clk = |resolution| {
+            ctx.link()
+                .callback(move |_: MouseEvent| ContactMsg::ResolveSync(resolution))
+        };
+        let remote_only = tags(&preview.remote_only, "is-success");
+        let local_only = tags(&preview.local_only, "is-warning");
+        let conflicts = preview
+            .conflicts
+            .iter()
+            .map(|(local, remote)| {
+                html!(
+                    <li>
+                        <span class="tag is-warning is-light">{name(local)}</span>
+                        <span class="icon is-small"><i class="fas fa-arrows-left-right"></i></span>
+                        <span class="tag is-success is-light">{name(remote)}</span>
+                    </li>
+                )
+            })
+            .collect::<Html>();
+        html!(
+            <div class="notification is-info is-light sync-box">
+                <p><strong>{rust_i18n::t!("contact list differs from relays", remote = preview.remote.len())}</strong></p>
+                if !preview.remote_only.is_empty() {
+                    <p>{rust_i18n::t!("only on relays")}</p>
+                    <div class="tags">{remote_only}</div>
+                }
+                if !preview.local_only.is_empty() {
+                    <p>{rust_i18n::t!("only on this device")}</p>
+                    <div class="tags">{local_only}</div>
+                }
+                if !preview.conflicts.is_empty() {
+                    <p>{rust_i18n::t!("conflicts")}</p>
+                    <ul>{conflicts}</ul>
+                }
+                <div class="buttons mt-2">
+                    <button class="button is-info" onclick={resolve_clk(ContactSyncResolution::MergeKeepRemote)}>
+                        {rust_i18n::t!("merge, keep relays")}
+                    </button>
+                    <button class="button is-info is-outlined" onclick={resolve_clk(ContactSyncResolution::MergeKeepLocal)}>
+                        {rust_i18n::t!("merge, keep this device")}
+                    </button>
+                    <button class="button is-success is-outlined" onclick={resolve_clk(ContactSyncResolution::UseRemote)}>
+                        {rust_i18n::t!("use relays")}
+                    </button>
+                    <button class="button is-warning is-outlined" onclick={resolve_clk(ContactSyncResolution::KeepLocal)}>
+                        {rust_i18n::t!("keep this device")}
+                    </button>
+                </div>
+            </div>
+        )
+    }
+
     fn recents_view(&self, _ctx: &Context<Self>) -> Html {
         (*self.user_contact_handle.recent)
             .borrow()
@@ -269,7 +373,19 @@ impl Component for Contact {
                         .dispatch(UserContactAction::Update(info));
                 }
             }*/
-            UserContactCtx(_) | I18nCtx(_) => update = true,
+            ResolveSync(resolution) => {
+                self.user_contact_handle
+                    .dispatch(UserContactAction::ResolveSync(resolution));
+            }
+            RetrySync => {
+                self.user_contact_handle
+                    .dispatch(UserContactAction::RetrySync);
+            }
+            UserContactCtx(user_contact_handle) => {
+                self.user_contact_handle = user_contact_handle;
+                update = true;
+            }
+            I18nCtx(_) => update = true,
             _ => {
                 log::info!("{:?}", msg);
             }
@@ -293,6 +409,7 @@ impl Component for Contact {
         let contacts = self.contacts_view(ctx, cb_action);
 
         let recents = self.recents_view(ctx);
+        let sync = self.sync_view(ctx);
         include!("../html/contact.html")
     }
 }
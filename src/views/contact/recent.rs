@@ -1,5 +1,6 @@
 use crate::ctxs::*;
 use crate::utils::*;
+use crate::views::RelativeTime;
 use nostr_sdk::nostr::secp256k1::XOnlyPublicKey;
 use yew::prelude::*;
 #[derive(Debug, Clone, Copy, Properties, PartialEq)]
@@ -11,6 +12,7 @@ pub enum RecentMsg {
 }
 pub struct Recent {
     user_contact_handle: UserContactContext,
+    _user_contact_listener: ContextHandle<UserContactContext>,
 }
 
 impl Component for Recent {
@@ -18,12 +20,22 @@ impl Component for Recent {
     type Properties = RecentProps;
 
     fn create(ctx: &Context<Self>) -> Self {
-        let (user_contact_handle, _) = ctx
+        let (user_contact_handle, _user_contact_listener) = ctx
             .link()
             .context(ctx.link().callback(RecentMsg::UserContactCtx))
             .expect("No UserContactContext Provided");
         Self {
             user_contact_handle,
+            _user_contact_listener,
+        }
+    }
+
+    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            RecentMsg::UserContactCtx(user_contact_handle) => {
+                self.user_contact_handle = user_contact_handle;
+                true
+            }
         }
     }
 
@@ -36,6 +48,7 @@ impl Component for Recent {
         let info = bw.get(&pk);
         if let Some(info) = info {
             let info = info.clone();
+            let recent_at = self.user_contact_handle.recent_at(&pk);
             include!("./components/recent.html")
         } else {
             html!(<></>)
@@ -0,0 +1,379 @@
+use std::collections::HashSet;
+
+use crate::ctxs::*;
+use crate::utils::*;
+
+use nostr_sdk::key::{FromPkStr, Keys};
+use nostr_sdk::nostr::prelude::*;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+const CSV_HEADER: &str = "pubkey,relay,nickname";
+
+fn csv_field(v: &str) -> String {
+    if v.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", v.replace('"', "\"\""))
+    } else {
+        v.to_owned()
+    }
+}
+
+fn to_csv(infos: &[UserContactInfo]) -> String {
+    let mut csv = vec![CSV_HEADER.to_owned()];
+    for info in infos {
+        csv.push(
+            [
+                info.pubkey.map(|pk| pk.to_string()).unwrap_or_default(),
+                info.relay
+                    .as_ref()
+                    .map(|u| u.to_string())
+                    .unwrap_or_default(),
+                info.nickname.clone().unwrap_or_default(),
+            ]
+            .iter()
+            .map(|v| csv_field(v))
+            .collect::<Vec<_>>()
+            .join(","),
+        );
+    }
+    csv.join("\n")
+}
+
+fn parse_csv_line(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields.into_iter().map(|f| f.trim().to_owned()).collect()
+}
+
+// the lines of the records, a quoted field may have line breaks
+fn csv_records(text: &str) -> Vec<String> {
+    let mut records = vec![];
+    let mut record = String::new();
+    for line in text.lines() {
+        if !record.is_empty() {
+            record.push('\n');
+        }
+        record.push_str(line);
+        // a field is still open
+        if record.matches('"').count() % 2 == 1 {
+            continue;
+        }
+        records.push(std::mem::take(&mut record));
+    }
+    if !record.is_empty() {
+        records.push(record);
+    }
+    records
+}
+
+// pubkey(hex or npub),relay,nickname; the header line is optional
+fn parse_csv(text: &str) -> Vec<UserContactInfo> {
+    // saved by Excel with a BOM
+    csv_records(text.trim_start_matches('\u{feff}'))
+        .iter()
+        .map(|record| parse_csv_line(record))
+        .filter_map(|fields| {
+            let pk = Keys::from_pk_str(fields.first()?).ok()?.public_key();
+            Some(UserContactInfo {
+                pubkey: Some(pk),
+                avatar_url: None,
+                relay: fields.get(1).and_then(|u| Url::parse(u).ok()),
+                nickname: fields.get(2).cloned().filter(|name| !name.is_empty()),
+                nip05: None,
+            })
+        })
+        .collect()
+}
+
+// an exported contact list, or a raw kind 3 event
+fn parse_json(text: &str) -> anyhow::Result<Vec<UserContactInfo>> {
+    if let Ok(infos) = serde_json::from_str::<Vec<UserContactInfo>>(text) {
+        return Ok(infos
+            .into_iter()
+            .filter(|info| info.pubkey.is_some())
+            .collect());
+    }
+    let event = nostr_sdk::Event::from_json(text)?;
+    event.verify()?;
+    if event.kind != Kind::ContactList {
+        anyhow::bail!("not a contact list: {:?}", event.kind);
+    }
+    Ok(UserContact::contacts_of_event(event)
+        .iter()
+        .map(UserContactInfo::from)
+        .collect())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ExportFormat {
+    Csv,
+    Json,
+    Kind3,
+}
+
+#[function_component(Transfer)]
+pub fn transfer() -> Html {
+    let locale_ctx = use_context::<I18nLocaleContext>().unwrap();
+    let lang = locale_ctx.to_string();
+    let user_ctx = use_context::<UserContext>().unwrap();
+    let user_contact_ctx = use_context::<UserContactContext>().unwrap();
+    let npub_ref = use_node_ref();
+    let error = use_state(|| None::<String>);
+    // the candidates whose default choice is flipped, new ones are chosen by default
+    let toggled = use_state(HashSet::<XOnlyPublicKey>::new);
+
+    let file_change = {
+        let user_contact_ctx_clone = user_contact_ctx.clone();
+        let error = error.clone();
+        let toggled = toggled.clone();
+        Callback::from(move |e: yew::Event| {
+            let input = e.target_unchecked_into::<HtmlInputElement>();
+            let file = match input.files().and_then(|files| files.get(0)) {
+                Some(file) => file,
+                None => return,
+            };
+            input.set_value("");
+            let user_contact_ctx_clone = user_contact_ctx_clone.clone();
+            let error = error.clone();
+            toggled.set(HashSet::new());
+            spawn_local(async move {
+                let name = file.name();
+                let infos = read_file(file).await.and_then(|text| {
+                    if name.to_lowercase().ends_with(".csv") {
+                        Ok(parse_csv(&text))
+                    } else {
+                        parse_json(&text)
+                    }
+                });
+                match infos {
+                    Ok(infos) => {
+                        error.set(None);
+                        user_contact_ctx_clone
+                            .dispatch(UserContactAction::ImportPreview(name, infos));
+                    }
+                    Err(e) => {
+                        log::warn!("import {name} error:{e:?}");
+                        error.set(Some(name));
+                    }
+                }
+            });
+        })
+    };
+    let copy_follows_clk = {
+        let user_contact_ctx_clone = user_contact_ctx.clone();
+        let npub_ref = npub_ref.clone();
+        let error = error.clone();
+        let toggled = toggled.clone();
+        Callback::from(move |_: MouseEvent| {
+            if let Some(input) = npub_ref.cast::<HtmlInputElement>() {
+                match Keys::from_pk_str(input.value().trim()) {
+                    Ok(keys) => {
+                        error.set(None);
+                        toggled.set(HashSet::new());
+                        user_contact_ctx_clone
+                            .dispatch(UserContactAction::FetchFollows(keys.public_key()));
+                    }
+                    Err(_) => error.set(Some(input.value())),
+                }
+            }
+        })
+    };
+    let export_clk = |format: ExportFormat| {
+        let user_contact_ctx_clone = user_contact_ctx.clone();
+        let user_ctx_clone = user_ctx.clone();
+        Callback::from(move |_: MouseEvent| {
+            let infos = user_contact_ctx_clone
+                .data
+                .borrow()
+                .values()
+                .cloned()
+                .collect::<Vec<_>>();
+            let ret = match format {
+                ExportFormat::Csv => download("contacts.csv", "text/csv", &to_csv(&infos)),
+                ExportFormat::Json => download(
+                    "contacts.json",
+                    "application/json",
+                    &serde_json::to_string_pretty(&infos).unwrap(),
+                ),
+                ExportFormat::Kind3 => {
+                    if user_ctx_clone.show_modal() {
+                        let cb = user_ctx_clone.show_modal_cb.as_ref().unwrap().clone();
+                        cb.emit(true);
+                        return;
+                    }
+                    let keys = user_ctx_clone.keys.as_ref().unwrap();
+                    match EventBuilder::set_contact_list(user_contact_ctx_clone.nostr_contacts())
+                        .to_event(keys)
+                    {
+                        Ok(event) => {
+                            download("contacts-kind3.json", "application/json", &event.as_json())
+                        }
+                        Err(e) => {
+                            log::warn!("sign contact list error:{e:?}");
+                            return;
+                        }
+                    }
+                }
+            };
+            if let Err(e) = ret {
+                log::warn!("export {format:?} error:{e:?}");
+            }
+        })
+    };
+    let cancel_clk = {
+        let user_contact_ctx_clone = user_contact_ctx.clone();
+        Callback::from(move |_: MouseEvent| {
+            user_contact_ctx_clone.dispatch(UserContactAction::CancelImport)
+        })
+    };
+
+    let (fetching, preview) = match &user_contact_ctx.import {
+        Some(ContactImport::Fetching(pk)) => (Some(*pk), None),
+        Some(ContactImport::Preview(preview)) => (None, Some(preview.clone())),
+        _ => (None, None),
+    };
+    let fetch_error = match &user_contact_ctx.import {
+        Some(ContactImport::Failed(pk, error)) => Some((*pk, error.clone())),
+        _ => None,
+    };
+    let is_chosen = {
+        let toggled = toggled.clone();
+        move |candidate: &ContactImportCandidate| {
+            toggled.contains(candidate.info.pubkey.as_ref().unwrap()) == candidate.existing
+        }
+    };
+    let chosen = preview
+        .as_ref()
+        .map(|preview| {
+            preview
+                .candidates
+                .iter()
+                .filter(|c| is_chosen(c))
+                .map(|c| c.info.pubkey.unwrap())
+                .collect::<HashSet<_>>()
+        })
+        .unwrap_or_default();
+    let import_clk = {
+        let user_contact_ctx_clone = user_contact_ctx.clone();
+        let chosen = chosen.clone();
+        let toggled = toggled.clone();
+        Callback::from(move |_: MouseEvent| {
+            toggled.set(HashSet::new());
+            user_contact_ctx_clone.dispatch(UserContactAction::ApplyImport(chosen.clone()))
+        })
+    };
+    let candidates = preview
+        .as_ref()
+        .map(|preview| {
+            preview
+                .candidates
+                .iter()
+                .map(|candidate| {
+                    let pk = candidate.info.pubkey.unwrap();
+                    let check = is_chosen(candidate);
+                    let onclick = {
+                        let toggled = toggled.clone();
+                        Callback::from(move |_: MouseEvent| {
+                            let mut set = (*toggled).clone();
+                            if !set.remove(&pk) {
+                                set.insert(pk);
+                            }
+                            toggled.set(set);
+                        })
+                    };
+                    html!(
+                        <tr key={pk.to_string()}>
+                            <td><input type="checkbox" checked={check} {onclick} /></td>
+                            <td title={pk.to_string()}>{front_n_chars(pk.to_bech32().unwrap_or(pk.to_string()), 20)}</td>
+                            <td>{candidate.info.nickname.clone().unwrap_or_default()}</td>
+                            <td>{candidate.info.relay.as_ref().map(|u| u.to_string()).unwrap_or_default()}</td>
+                            <td>
+                                if candidate.existing {
+                                <span class="tag is-warning is-light">{rust_i18n::t!("existing", locale=&lang)}</span>
+                                } else {
+                                <span class="tag is-success is-light">{rust_i18n::t!("new", locale=&lang)}</span>
+                                }
+                            </td>
+                        </tr>
+                    )
+                })
+                .collect::<Html>()
+        })
+        .unwrap_or_default();
+    include!("./components/transfer.html")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(nickname: Option<&str>, relay: Option<&str>) -> UserContactInfo {
+        UserContactInfo {
+            pubkey: Some(Keys::generate().public_key()),
+            avatar_url: None,
+            relay: relay.map(|u| Url::parse(u).unwrap()),
+            nickname: nickname.map(|n| n.to_owned()),
+            nip05: None,
+        }
+    }
+
+    #[test]
+    fn parse_csv_line_quoted() {
+        assert_eq!(parse_csv_line("a,b,c"), vec!["a", "b", "c"]);
+        assert_eq!(parse_csv_line("\"a,b\",c"), vec!["a,b", "c"]);
+        assert_eq!(parse_csv_line("a,\"say \"\"hi\"\"\","), vec!["a", "say \"hi\"", ""]);
+        assert_eq!(parse_csv_line(" a , b "), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn parse_csv_header_crlf_and_bom() {
+        let a = info(Some("alice"), Some("wss://relay.example.com"));
+        let b = info(None, None);
+        let text = format!(
+            "\u{feff}pubkey,relay,nickname\r\n{},wss://relay.example.com,alice\r\n{},,\r\n",
+            a.pubkey.unwrap(),
+            b.pubkey.unwrap().to_bech32().unwrap(),
+        );
+        assert_eq!(parse_csv(&text), vec![a, b]);
+        // without the header
+        let c = info(Some("carol"), None);
+        let text = format!("\u{feff}{},,carol", c.pubkey.unwrap());
+        assert_eq!(parse_csv(&text), vec![c]);
+    }
+
+    #[test]
+    fn parse_csv_skips_invalid_lines() {
+        let a = info(None, None);
+        let text = format!("not a key,,x\n\n{}\n", a.pubkey.unwrap());
+        assert_eq!(parse_csv(&text), vec![a]);
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let infos = vec![
+            info(Some("a, b"), Some("wss://relay.example.com")),
+            info(Some("say \"hi\""), None),
+            info(Some("two\nlines"), None),
+            info(None, Some("wss://relay.example.com/path")),
+        ];
+        let csv = to_csv(&infos);
+        assert!(csv.starts_with(CSV_HEADER));
+        assert_eq!(parse_csv(&csv), infos);
+        assert_eq!(parse_csv(&csv.replace('\n', "\r\n")), infos);
+    }
+}
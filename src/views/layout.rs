@@ -1,5 +1,7 @@
 use crate::ctxs::*;
 use crate::{route::MainRoute, views::UserModal};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
 use yew::prelude::*;
 use yew_hooks::{use_is_first_mount, use_mount};
 use yew_router::prelude::*;
@@ -13,14 +15,30 @@ pub fn layout(props: &LayoutProps) -> Html {
     let route: MainRoute = use_route().unwrap_or(MainRoute::Home);
     let user_ctx = use_context::<UserContext>().unwrap();
     let locale_ctx = use_context::<I18nLocaleContext>().unwrap();
-    let locale_ctx_clone = locale_ctx.clone();
     let cur_lang = locale_ctx.to_string();
-    let eng_clk = Callback::from(move |_e: MouseEvent| locale_ctx.dispatch(I18nLocale::En));
     log::trace!("{}", &cur_lang);
-    let zhcn_clk =
-        Callback::from(move |_e: MouseEvent| locale_ctx_clone.dispatch(I18nLocale::Zhcn));
+    let theme_ctx = use_context::<ThemeContext>().unwrap();
+    let theme_mode = theme_ctx.mode;
+    let theme_clk = Callback::from(move |_e: MouseEvent| {
+        theme_ctx.dispatch(ThemeAction::Set(theme_mode.next()))
+    });
+    let (theme_icon, theme_title) = match theme_mode {
+        ThemeMode::Light => ("fa-sun", "light theme"),
+        ThemeMode::Dark => ("fa-moon", "dark theme"),
+        ThemeMode::System => ("fa-circle-half-stroke", "system theme"),
+    };
     let is_home = || route == MainRoute::Home;
     let is_contact = || route == MainRoute::Contact;
+    let is_search = || route == MainRoute::Search;
+    let is_scheduled = || route == MainRoute::Scheduled;
+    let schedule_ctx = use_context::<ScheduleContext>().unwrap();
+    let scheduled = user_ctx
+        .keys
+        .as_ref()
+        .map_or(0, |keys| schedule_ctx.of(&keys.public_key()).len());
+    let is_notification = || route == MainRoute::Notifications;
+    let notification_ctx = use_context::<UserNotificationContext>().unwrap();
+    let unread = notification_ctx.unread();
     let is_setting = || route == MainRoute::Settings || route == MainRoute::SettingsRoot;
     let is_first = use_is_first_mount();
     let user_ctx_clone = user_ctx.clone();
@@ -39,6 +57,47 @@ pub fn layout(props: &LayoutProps) -> Html {
         user_ctx_clone2.dispatch(UserContextMessage::ModalCb(cb));
     });
 
+    // 启动时读取失败的本地数据, 已另存一份并使用了默认值
+    let load_errors = use_state(load_errors);
+    let dismiss_clk = {
+        let load_errors = load_errors.clone();
+        Callback::from(move |_: MouseEvent| {
+            dismiss_load_errors();
+            load_errors.set(vec![]);
+        })
+    };
+
+    // 本地存储写入失败(例如空间不足)时提示, 数据只保存在内存中
+    let store_error = use_state(|| None::<StoreError>);
+    {
+        let store_error = store_error.clone();
+        use_effect_with_deps(
+            move |_| {
+                set_store_error_cb(Some(Callback::from(move |e| store_error.set(Some(e)))));
+                // 页面关闭前写入还没写入的数据
+                let pagehide = Closure::<dyn Fn()>::new(flush_stores);
+                let window = web_sys::window().unwrap();
+                window
+                    .add_event_listener_with_callback("pagehide", pagehide.as_ref().unchecked_ref())
+                    .ok();
+                move || {
+                    set_store_error_cb(None);
+                    window
+                        .remove_event_listener_with_callback(
+                            "pagehide",
+                            pagehide.as_ref().unchecked_ref(),
+                        )
+                        .ok();
+                }
+            },
+            (),
+        );
+    }
+    let dismiss_store_error_clk = {
+        let store_error = store_error.clone();
+        Callback::from(move |_: MouseEvent| store_error.set(None))
+    };
+
     let profile_clk = {
         let user_modal_clone = user_modal.clone();
         Callback::from(move |_: MouseEvent| {
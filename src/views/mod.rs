@@ -1,11 +1,23 @@
+mod compose;
 mod contact;
 mod home;
 mod layout;
+mod note;
+mod notifications;
+mod scheduled;
+mod search;
 mod settings;
+mod time;
 mod user_modal;
 
+pub use compose::Compose;
 pub use contact::Contact;
 pub use home::Home;
 pub use layout::Layout;
+pub use note::{mention_name, NoteBody, NoteContent};
+pub use notifications::Notifications;
+pub use scheduled::Scheduled;
+pub use search::Search;
 pub use settings::Settings;
+pub use time::{LocalTime, RelativeTime};
 pub use user_modal::UserModal;
@@ -0,0 +1,89 @@
+use crate::ctxs::*;
+use crate::utils::*;
+use nostr_sdk::nostr::prelude::*;
+use yew::prelude::*;
+
+#[derive(Properties, PartialEq)]
+pub struct NoteContentProps {
+    pub content: String,
+}
+
+// the name of the contact, or the beginning of the npub
+pub fn mention_name(user_contact_ctx: &UserContactContext, pk: &XOnlyPublicKey) -> String {
+    user_contact_ctx
+        .data
+        .borrow()
+        .get(pk)
+        .and_then(|info| info.nickname.clone())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| {
+            let npub = pk.to_bech32().unwrap_or_else(|_| pk.to_string());
+            front_n_chars(npub, 12)
+        })
+}
+
+// the content of a note, with the mentions, hashtags and links
+#[function_component(NoteContent)]
+pub fn note_content(props: &NoteContentProps) -> Html {
+    let user_contact_ctx = use_context::<UserContactContext>().unwrap();
+    let tokens = tokenize_note(&props.content)
+        .into_iter()
+        .map(|token| match token {
+            NoteToken::Text(text) => html!({ text }),
+            NoteToken::Mention(pk) => {
+                let title = pk.to_bech32().unwrap_or_default();
+                html!(
+                    <span class="mention has-text-link" {title}>{format!("@{}", mention_name(&user_contact_ctx, &pk))}</span>
+                )
+            }
+            NoteToken::Hashtag(hashtag) => html!(
+                <span class="hashtag has-text-link">{format!("#{hashtag}")}</span>
+            ),
+            NoteToken::Url(url) => html!(
+                <a href={url.clone()} target="_blank" rel="noopener noreferrer">{url}</a>
+            ),
+        })
+        .collect::<Html>();
+    html!(<div class="note-content">{tokens}</div>)
+}
+
+#[derive(Properties, PartialEq)]
+pub struct NoteBodyProps {
+    pub event: nostr_sdk::Event,
+}
+
+// NIP-36, the content behind a banner if the author warned about it
+#[function_component(NoteBody)]
+pub fn note_body(props: &NoteBodyProps) -> Html {
+    let locale_ctx = use_context::<I18nLocaleContext>().unwrap();
+    let lang = locale_ctx.to_string();
+    let cw_ctx = use_context::<ContentWarningContext>().unwrap();
+    let event = &props.event;
+    if !cw_ctx.is_collapsed(event) {
+        return html!(<NoteContent content={event.content.clone()}/>);
+    }
+    let reason = content_warning(event).unwrap_or_default();
+    let onclick = {
+        let cw_ctx = cw_ctx.clone();
+        let id = event.id;
+        Callback::from(move |e: MouseEvent| {
+            // not the click of the note
+            e.stop_propagation();
+            cw_ctx.dispatch(ContentWarningAction::Reveal(id))
+        })
+    };
+    html!(
+        <div class="content-warning notification is-warning is-light is-flex is-align-items-center">
+            <span class="icon"><i class="fas fa-eye-slash"></i></span>
+            <span class="is-flex-grow-1">
+                {rust_i18n::t!("content warning", locale=&lang)}
+                if !reason.is_empty() {
+                    {format!(": {reason}")}
+                }
+            </span>
+            if cw_ctx.mode == ContentWarningMode::Collapse {
+                <button class="button is-small is-warning is-outlined" {onclick}>{rust_i18n::t!("show content", locale=&lang)}</button>
+            }
+        </div>
+    )
+}
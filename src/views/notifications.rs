@@ -0,0 +1,88 @@
+use crate::ctxs::*;
+use crate::utils::*;
+use crate::views::{NoteBody, RelativeTime};
+
+use yew::prelude::*;
+
+fn group_text(group: &NotificationGroup, lang: &str) -> String {
+    let count = group.authors().len();
+    match group.kind {
+        NotificationKind::Mention => rust_i18n::t!("mentioned you", locale = lang),
+        NotificationKind::Reply if count > 1 => {
+            rust_i18n::t!("people replied to your note", locale = lang, count = count)
+        }
+        NotificationKind::Reply => rust_i18n::t!("replied to your note", locale = lang),
+        NotificationKind::Reaction if count > 1 => {
+            rust_i18n::t!("people reacted to your note", locale = lang, count = count)
+        }
+        NotificationKind::Reaction => rust_i18n::t!("reacted to your note", locale = lang),
+        NotificationKind::Repost if count > 1 => {
+            rust_i18n::t!("people reposted your note", locale = lang, count = count)
+        }
+        NotificationKind::Repost => rust_i18n::t!("reposted your note", locale = lang),
+    }
+}
+
+fn group_icon(kind: NotificationKind) -> &'static str {
+    match kind {
+        NotificationKind::Mention => "fa-at",
+        NotificationKind::Reply => "fa-reply",
+        NotificationKind::Reaction => "fa-heart",
+        NotificationKind::Repost => "fa-retweet",
+    }
+}
+
+#[function_component(Notifications)]
+pub fn notifications() -> Html {
+    let locale_ctx = use_context::<I18nLocaleContext>().unwrap();
+    let lang = locale_ctx.to_string();
+    let notification_ctx = use_context::<UserNotificationContext>().unwrap();
+    let user_events_ctx = use_context::<UserEventContext>().unwrap();
+    let user_contact_ctx = use_context::<UserContactContext>().unwrap();
+    let cw_ctx = use_context::<ContentWarningContext>().unwrap();
+    let read_all_clk = {
+        let notification_ctx_clone = notification_ctx.clone();
+        Callback::from(move |_: MouseEvent| {
+            notification_ctx_clone.dispatch(UserNotificationAction::ReadAll);
+        })
+    };
+    let read_clk = {
+        let notification_ctx_clone = notification_ctx.clone();
+        Callback::from(move |key: NotificationGroupKey| {
+            notification_ctx_clone.dispatch(UserNotificationAction::ReadGroup(key));
+        })
+    };
+    let author_name = |pk: &nostr_sdk::prelude::XOnlyPublicKey| {
+        user_contact_ctx
+            .data
+            .borrow()
+            .get(pk)
+            .and_then(|info| info.nickname.clone())
+            .filter(|name| !name.is_empty())
+            .unwrap_or(front_n_chars(pk.to_string(), 12))
+    };
+    // the content of the note that was reacted, reposted or replied, if it's cached
+    let target_content = |group: &NotificationGroup| {
+        group.target.and_then(|id| {
+            user_events_ctx
+                .events
+                .borrow()
+                .get(&id)
+                .map(|e| front_n_chars(&e.event.content, 80))
+        })
+    };
+    // the latest mention or reply that isn't hidden by its content warning
+    let latest_shown = |group: &NotificationGroup| {
+        let with_content = matches!(
+            group.kind,
+            NotificationKind::Mention | NotificationKind::Reply
+        );
+        group
+            .events
+            .iter()
+            .find(|e| !(with_content && cw_ctx.is_hidden(e)))
+            .cloned()
+    };
+    let unread = notification_ctx.unread();
+    include!("html/notifications.html")
+}
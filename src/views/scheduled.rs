@@ -0,0 +1,213 @@
+use crate::agent::{Miner, PowOutput, PowRequest};
+use crate::ctxs::*;
+use crate::utils::*;
+use crate::views::{LocalTime, NoteContent};
+use nostr_sdk::nostr::prelude::*;
+
+use web_sys::{HtmlInputElement, HtmlTextAreaElement};
+use yew::prelude::*;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Editing {
+    id: u64,
+    content: String,
+    // the value of the time input
+    publish_at: String,
+}
+
+// the mentions and hashtags of the new content, the expiration moved with the schedule,
+// the nonce of the old one is mined again
+fn edited(note: &ScheduledNote, content: String, publish_at: Timestamp) -> ScheduledNote {
+    let mut tags = note
+        .tags
+        .iter()
+        .filter(|tag| !matches!(tag, Tag::PubKey(..) | Tag::Hashtag(_) | Tag::POW { .. }))
+        .map(|tag| match tag {
+            Tag::Expiration(t) => {
+                Tag::Expiration(publish_at + t.as_u64().saturating_sub(note.publish_at.as_u64()))
+            }
+            tag => tag.clone(),
+        })
+        .collect::<Vec<_>>();
+    tags.extend(note_tags(&content));
+    ScheduledNote {
+        content,
+        tags,
+        publish_at,
+        signed: None,
+        missed: false,
+        error: None,
+        ..note.clone()
+    }
+}
+
+#[function_component(Scheduled)]
+pub fn scheduled() -> Html {
+    let locale_ctx = use_context::<I18nLocaleContext>().unwrap();
+    let lang = locale_ctx.to_string();
+    let user_ctx = use_context::<UserContext>().unwrap();
+    let schedule_ctx = use_context::<ScheduleContext>().unwrap();
+    let pow_ctx = use_context::<PowContext>().unwrap();
+    let relay_ctx = use_context::<RelayContext>().unwrap();
+    let keys = user_ctx.keys.clone();
+    // the notes of the other accounts are shown when they're logged in
+    let notes = keys
+        .as_ref()
+        .map(|keys| schedule_ctx.of(&keys.public_key()))
+        .unwrap_or_default();
+    let editing = use_state(|| None::<Editing>);
+    // the mining of the edited note, dropping it cancels
+    let miner = use_mut_ref(|| None::<Miner>);
+    // the hashes tried, the best and the target difficulty
+    let mining = use_state(|| None::<(u64, u8, u8)>);
+    let review_missed_change = {
+        let schedule_ctx = schedule_ctx.clone();
+        Callback::from(move |e: yew::Event| {
+            let input = e.target_unchecked_into::<HtmlInputElement>();
+            schedule_ctx.dispatch(ScheduleAction::ReviewMissed(input.checked()));
+        })
+    };
+    let edit_clk = |note: &ScheduledNote| {
+        let editing = editing.clone();
+        let start = Editing {
+            id: note.id,
+            content: note.content.clone(),
+            publish_at: datetime_local_value(note.publish_at),
+        };
+        Callback::from(move |_: MouseEvent| editing.set(Some(start.clone())))
+    };
+    let cancel_clk = |id: u64| {
+        let schedule_ctx = schedule_ctx.clone();
+        let editing = editing.clone();
+        Callback::from(move |_: MouseEvent| {
+            if editing.as_ref().map(|e| e.id) == Some(id) {
+                editing.set(None);
+            }
+            schedule_ctx.dispatch(ScheduleAction::Cancel(id));
+        })
+    };
+    let publish_now_clk = |id: u64| {
+        let schedule_ctx = schedule_ctx.clone();
+        Callback::from(move |_: MouseEvent| schedule_ctx.dispatch(ScheduleAction::PublishNow(id)))
+    };
+    let content_input = {
+        let editing = editing.clone();
+        Callback::from(move |e: InputEvent| {
+            let txt = e.target_unchecked_into::<HtmlTextAreaElement>();
+            if let Some(cur) = editing.as_ref() {
+                editing.set(Some(Editing {
+                    content: txt.value(),
+                    ..cur.clone()
+                }));
+            }
+        })
+    };
+    let time_input = {
+        let editing = editing.clone();
+        Callback::from(move |e: InputEvent| {
+            let input = e.target_unchecked_into::<HtmlInputElement>();
+            if let Some(cur) = editing.as_ref() {
+                editing.set(Some(Editing {
+                    publish_at: input.value(),
+                    ..cur.clone()
+                }));
+            }
+        })
+    };
+    // None if the time isn't in the future
+    let edit_publish_at = editing.as_ref().and_then(|e| {
+        parse_datetime_local(&e.publish_at).filter(|publish_at| *publish_at > Timestamp::now())
+    });
+    let save_clk = {
+        let editing = editing.clone();
+        let schedule_ctx = schedule_ctx.clone();
+        let notes = notes.clone();
+        let miner = miner.clone();
+        let mining = mining.clone();
+        Callback::from(move |_: MouseEvent| {
+            if mining.is_some() {
+                return;
+            }
+            let (cur, publish_at, keys) = match (editing.as_ref(), edit_publish_at, keys.as_ref()) {
+                (Some(cur), Some(publish_at), Some(keys)) if !cur.content.trim().is_empty() => {
+                    (cur, publish_at, keys)
+                }
+                _ => return,
+            };
+            let note = match notes.iter().find(|note| note.id == cur.id) {
+                Some(note) => note,
+                None => {
+                    editing.set(None);
+                    return;
+                }
+            };
+            let new = edited(note, cur.content.clone(), publish_at);
+            // the signature is for the old content, signed again
+            let sign = note.signed.is_some();
+            let done = {
+                let keys = keys.clone();
+                let schedule_ctx = schedule_ctx.clone();
+                let editing = editing.clone();
+                move |mut new: ScheduledNote| {
+                    if sign {
+                        new.signed = new.sign(&keys);
+                    }
+                    schedule_ctx.dispatch(ScheduleAction::Update(new));
+                    editing.set(None);
+                }
+            };
+            let difficulty = pow_ctx.target(
+                relay_ctx
+                    .list
+                    .borrow()
+                    .values()
+                    .filter_map(|info| info.uri.as_ref()),
+            );
+            if difficulty == 0 {
+                done(new);
+                return;
+            }
+            // NIP-13 like a new note, at the scheduled time
+            let builder = EventBuilder::new_text_note(new.content.as_str(), &new.tags);
+            let req = PowRequest::new(keys, builder, difficulty).at(publish_at);
+            let on_output = {
+                let miner = miner.clone();
+                let mining = mining.clone();
+                Callback::from(move |output| match output {
+                    PowOutput::Progress(hashes, best) => {
+                        mining.set(Some((hashes, best, difficulty)))
+                    }
+                    PowOutput::Done(unsigned) => {
+                        // dropped later, it's running this callback
+                        let finished = miner.borrow_mut().take();
+                        wasm_bindgen_futures::spawn_local(async move { drop(finished) });
+                        mining.set(None);
+                        done(ScheduledNote {
+                            tags: unsigned.tags,
+                            ..new.clone()
+                        });
+                    }
+                    PowOutput::Ready => {}
+                })
+            };
+            match Miner::start(req, on_output) {
+                Ok(m) => {
+                    *miner.borrow_mut() = Some(m);
+                    mining.set(Some((0, 0, difficulty)));
+                }
+                Err(e) => log::error!("start pow worker error:{e:?}"),
+            }
+        })
+    };
+    let close_edit_clk = {
+        let editing = editing.clone();
+        let miner = miner.clone();
+        let mining = mining.clone();
+        Callback::from(move |_: MouseEvent| {
+            miner.borrow_mut().take();
+            mining.set(None);
+            editing.set(None);
+        })
+    };
+    include!("html/scheduled.html")
+}
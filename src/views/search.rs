@@ -0,0 +1,96 @@
+use std::rc::Rc;
+
+use crate::ctxs::*;
+use crate::utils::*;
+use crate::views::{NoteBody, RelativeTime};
+use nostr_sdk::nostr::prelude::ToBech32;
+
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+fn match_contact(info: &UserContactInfo, query: &str) -> bool {
+    let contains = |v: &Option<String>| {
+        v.as_ref()
+            .map(|v| v.to_lowercase().contains(query))
+            .unwrap_or(false)
+    };
+    if contains(&info.nickname) || contains(&info.nip05) {
+        return true;
+    }
+    if let Some(pk) = info.pubkey.as_ref() {
+        if pk.to_string().contains(query) {
+            return true;
+        }
+        if let Ok(npub) = pk.to_bech32() {
+            return npub.contains(query);
+        }
+    }
+    false
+}
+
+#[function_component(Search)]
+pub fn search() -> Html {
+    let locale_ctx = use_context::<I18nLocaleContext>().unwrap();
+    let lang = locale_ctx.to_string();
+    let search_ctx = use_context::<UserSearchContext>().unwrap();
+    let cw_ctx = use_context::<ContentWarningContext>().unwrap();
+    let user_events_ctx = use_context::<UserEventContext>().unwrap();
+    let user_contact_ctx = use_context::<UserContactContext>().unwrap();
+    let input_ref = use_node_ref();
+    let do_search = {
+        let input_ref_clone = input_ref.clone();
+        let search_ctx_clone = search_ctx.clone();
+        let user_events_ctx_clone = user_events_ctx.clone();
+        Rc::new(move || {
+            if let Some(input) = input_ref_clone.cast::<HtmlInputElement>() {
+                let query = input.value().trim().to_owned();
+                search_ctx_clone.dispatch(UserSearchAction::Query(query.clone()));
+                if query.is_empty() {
+                    return;
+                }
+                // the local events are searched immediately, the relays' results come later
+                let query = query.to_lowercase();
+                let locals = user_events_ctx_clone
+                    .events
+                    .borrow()
+                    .values()
+                    .filter(|e| e.event.content.to_lowercase().contains(&query))
+                    .map(|e| e.event.clone())
+                    .collect::<Vec<_>>();
+                search_ctx_clone.dispatch(UserSearchAction::Hits(SearchSource::Local, locals));
+            }
+        })
+    };
+    let search_clk = {
+        let do_search_clone = do_search.clone();
+        Callback::from(move |_: MouseEvent| (*do_search_clone)())
+    };
+    let search_keydown = Callback::from(move |e: KeyboardEvent| {
+        if e.key() == "Enter" {
+            (*do_search)();
+        }
+    });
+    let clear_clk = {
+        let input_ref_clone = input_ref.clone();
+        let search_ctx_clone = search_ctx.clone();
+        Callback::from(move |_: MouseEvent| {
+            if let Some(input) = input_ref_clone.cast::<HtmlInputElement>() {
+                input.set_value("");
+            }
+            search_ctx_clone.dispatch(UserSearchAction::Clear);
+        })
+    };
+    let query = search_ctx.query.to_lowercase();
+    let contacts = if query.is_empty() {
+        vec![]
+    } else {
+        user_contact_ctx
+            .data
+            .borrow()
+            .values()
+            .filter(|info| match_contact(info, &query))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    include!("html/search.html")
+}
@@ -0,0 +1,278 @@
+use std::rc::Rc;
+
+use crate::backup::*;
+use crate::ctxs::*;
+use crate::utils::*;
+
+use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+#[function_component(BackupRestore)]
+pub fn backup_restore() -> Html {
+    let user_ctx = use_context::<UserContext>().unwrap();
+    let user_contact_ctx = use_context::<UserContactContext>().unwrap();
+    let relay_ctx = use_context::<RelayContext>().unwrap();
+    let mute_ctx = use_context::<UserMuteContext>().unwrap();
+    let notification_ctx = use_context::<UserNotificationContext>().unwrap();
+    let user_events_ctx = use_context::<UserEventContext>().unwrap();
+    let locale_ctx = use_context::<I18nLocaleContext>().unwrap();
+    let theme_ctx = use_context::<ThemeContext>().unwrap();
+    let pow_ctx = use_context::<PowContext>().unwrap();
+    let media_ctx = use_context::<MediaServerContext>().unwrap();
+    let cw_ctx = use_context::<ContentWarningContext>().unwrap();
+    let draft_ctx = use_context::<DraftContext>().unwrap();
+    let schedule_ctx = use_context::<ScheduleContext>().unwrap();
+    let with_events_ref = use_node_ref();
+    let export_passphrase_ref = use_node_ref();
+    let import_passphrase_ref = use_node_ref();
+    // the chosen backup file, and the backup opened from it
+    let file = use_state(|| None::<Rc<BackupFile>>);
+    let backup = use_state(|| None::<Rc<Backup>>);
+    let error = use_state(|| None::<String>);
+
+    let export_clk = {
+        let user_ctx = user_ctx.clone();
+        let user_contact_ctx = user_contact_ctx.clone();
+        let relay_ctx = relay_ctx.clone();
+        let mute_ctx = mute_ctx.clone();
+        let notification_ctx = notification_ctx.clone();
+        let user_events_ctx = user_events_ctx.clone();
+        let locale_ctx = locale_ctx.clone();
+        let theme_ctx = theme_ctx.clone();
+        let pow_ctx = pow_ctx.clone();
+        let media_ctx = media_ctx.clone();
+        let cw_ctx = cw_ctx.clone();
+        let draft_ctx = draft_ctx.clone();
+        let schedule_ctx = schedule_ctx.clone();
+        let with_events_ref = with_events_ref.clone();
+        let export_passphrase_ref = export_passphrase_ref.clone();
+        Callback::from(move |_: MouseEvent| {
+            let with_events = with_events_ref
+                .cast::<HtmlInputElement>()
+                .map(|input| input.checked())
+                .unwrap_or(false);
+            let passphrase = export_passphrase_ref
+                .cast::<HtmlInputElement>()
+                .map(|input| input.value());
+            let events = if with_events {
+                user_events_ctx
+                    .events
+                    .borrow()
+                    .values()
+                    .map(|msg| msg.event.clone())
+                    .collect()
+            } else {
+                vec![]
+            };
+            let backup = Backup {
+                user: (*user_ctx).clone(),
+                contacts: (*user_contact_ctx).clone(),
+                relays: (*relay_ctx).clone(),
+                mute: (*mute_ctx).clone(),
+                notifications: (*notification_ctx).clone(),
+                locale: Some(*locale_ctx),
+                theme: Some((*theme_ctx).clone()),
+                pow: Some((*pow_ctx).clone()),
+                media_server: Some((*media_ctx).clone()),
+                content_warnings: Some((*cw_ctx).clone()),
+                drafts: Some((*draft_ctx).clone()),
+                schedule: Some((*schedule_ctx).clone()),
+                events,
+                invalid_events: 0,
+            };
+            let ret = BackupFile::new(&backup, passphrase.as_deref())
+                .and_then(|file| Ok(serde_json::to_string_pretty(&file)?))
+                .and_then(|json| {
+                    let name = format!("dapp-backup-{}.json", nostr_sdk::Timestamp::now());
+                    download(&name, "application/json", &json).map_err(|e| anyhow::anyhow!("{e:?}"))
+                });
+            if let Err(e) = ret {
+                log::warn!("export backup error:{e:?}");
+            }
+        })
+    };
+
+    let open_backup = {
+        let backup = backup.clone();
+        let error = error.clone();
+        move |file: &BackupFile, passphrase: Option<String>| match file.open(passphrase.as_deref())
+        {
+            Ok(opened) => {
+                error.set(None);
+                backup.set(Some(Rc::new(opened)));
+            }
+            Err(e) => {
+                log::warn!("open backup error:{e:?}");
+                error.set(Some(e.to_string()));
+            }
+        }
+    };
+    let file_change = {
+        let file = file.clone();
+        let backup = backup.clone();
+        let error = error.clone();
+        let open_backup = open_backup.clone();
+        Callback::from(move |e: yew::Event| {
+            let input = e.target_unchecked_into::<HtmlInputElement>();
+            let chosen = match input.files().and_then(|files| files.get(0)) {
+                Some(chosen) => chosen,
+                None => return,
+            };
+            input.set_value("");
+            backup.set(None);
+            let file = file.clone();
+            let error = error.clone();
+            let open_backup = open_backup.clone();
+            spawn_local(async move {
+                match read_file(chosen)
+                    .await
+                    .and_then(|text| BackupFile::parse(&text))
+                {
+                    Ok(parsed) => {
+                        if !parsed.is_encrypted() {
+                            open_backup(&parsed, None);
+                        } else {
+                            error.set(None);
+                        }
+                        file.set(Some(Rc::new(parsed)));
+                    }
+                    Err(e) => {
+                        log::warn!("read backup error:{e:?}");
+                        file.set(None);
+                        error.set(Some(e.to_string()));
+                    }
+                }
+            });
+        })
+    };
+    let unlock_clk = {
+        let file = file.clone();
+        let import_passphrase_ref = import_passphrase_ref.clone();
+        Callback::from(move |_: MouseEvent| {
+            if let Some(file) = (*file).as_ref() {
+                let passphrase = import_passphrase_ref
+                    .cast::<HtmlInputElement>()
+                    .map(|input| input.value());
+                open_backup(file, passphrase);
+            }
+        })
+    };
+    let cancel_clk = {
+        let file = file.clone();
+        let backup = backup.clone();
+        Callback::from(move |_: MouseEvent| {
+            file.set(None);
+            backup.set(None);
+        })
+    };
+    let restore_clk = |merge: bool| {
+        let user_ctx = user_ctx.clone();
+        let user_contact_ctx = user_contact_ctx.clone();
+        let relay_ctx = relay_ctx.clone();
+        let mute_ctx = mute_ctx.clone();
+        let notification_ctx = notification_ctx.clone();
+        let user_events_ctx = user_events_ctx.clone();
+        let locale_ctx = locale_ctx.clone();
+        let theme_ctx = theme_ctx.clone();
+        let pow_ctx = pow_ctx.clone();
+        let media_ctx = media_ctx.clone();
+        let cw_ctx = cw_ctx.clone();
+        let draft_ctx = draft_ctx.clone();
+        let schedule_ctx = schedule_ctx.clone();
+        let file = file.clone();
+        let backup = backup.clone();
+        Callback::from(move |_: MouseEvent| {
+            let restored = if let Some(restored) = (*backup).as_ref() {
+                restored.clone()
+            } else {
+                return;
+            };
+            // merging keeps the current account
+            if !merge || user_ctx.keys.is_none() {
+                let mut user = restored.user.clone();
+                user.show_modal_cb = user_ctx.show_modal_cb.clone();
+                user_ctx.dispatch(UserContextMessage::UserMsg(user));
+            }
+            user_contact_ctx.dispatch(UserContactAction::Restore(
+                Box::new(restored.contacts.clone()),
+                merge,
+            ));
+            let relays = restored
+                .relays
+                .list
+                .borrow()
+                .values()
+                .filter_map(|info| info.uri.clone())
+                .collect();
+            relay_ctx.dispatch(RelayAction::Restore(relays, merge));
+            mute_ctx.dispatch(UserMuteAction::Restore(
+                restored.mute.entries.borrow().clone(),
+                merge,
+            ));
+            notification_ctx.dispatch(UserNotificationAction::Restore(
+                restored.notifications.read_until,
+            ));
+            // the drafts and the scheduled notes of the backup are added, merging keeps the current drafts and settings
+            if let Some(drafts) = &restored.drafts {
+                for (pk, content) in drafts.drafts.borrow().iter() {
+                    if !merge || draft_ctx.get(pk).is_empty() {
+                        draft_ctx.dispatch(DraftAction::Save(*pk, content.clone()));
+                    }
+                }
+            }
+            if let Some(schedule) = &restored.schedule {
+                let mut notes = schedule_ctx.notes.borrow().clone();
+                for note in schedule.notes.borrow().iter() {
+                    if !notes.iter().any(|n| n.id == note.id) {
+                        notes.push(ScheduledNote {
+                            sending: false,
+                            ..note.clone()
+                        });
+                    }
+                }
+                schedule_ctx.dispatch(ScheduleAction::Synced(notes));
+                if !merge {
+                    schedule_ctx.dispatch(ScheduleAction::ReviewMissed(schedule.review_missed));
+                }
+            }
+            if !merge {
+                if let Some(locale) = restored.locale {
+                    locale_ctx.dispatch(locale);
+                }
+                if let Some(theme) = &restored.theme {
+                    theme_ctx.dispatch(ThemeAction::Set(theme.mode));
+                }
+                if let Some(pow) = &restored.pow {
+                    pow_ctx.dispatch(PowAction::SetDifficulty(pow.difficulty));
+                    pow_ctx.dispatch(PowAction::SetMinDifficulty(pow.min_difficulty));
+                    let current = pow_ctx.relays.borrow().keys().cloned().collect::<Vec<_>>();
+                    for uri in current {
+                        pow_ctx.dispatch(PowAction::SetRelay(uri, None));
+                    }
+                    for (uri, difficulty) in pow.relays.borrow().iter() {
+                        pow_ctx.dispatch(PowAction::SetRelay(uri.clone(), Some(*difficulty)));
+                    }
+                }
+                if let Some(media_server) = &restored.media_server {
+                    media_ctx.dispatch(MediaServerAction::SetKind(media_server.kind));
+                    media_ctx.dispatch(MediaServerAction::SetUrl(media_server.url.clone()));
+                }
+                if let Some(content_warnings) = &restored.content_warnings {
+                    cw_ctx.dispatch(ContentWarningAction::SetMode(content_warnings.mode));
+                }
+            }
+            if !restored.events.is_empty() {
+                user_events_ctx.dispatch(UserEventAction::AddVisibleBatch(restored.events.clone()));
+            }
+            file.set(None);
+            backup.set(None);
+        })
+    };
+
+    let cur_pubkey = user_ctx
+        .keys
+        .as_ref()
+        .map(|keys| keys.public_key().to_string());
+    include!("./components/backup.html")
+}
@@ -0,0 +1,39 @@
+use crate::ctxs::*;
+
+use web_sys::HtmlSelectElement;
+use yew::prelude::*;
+
+const CONTENT_WARNING_MODES: [(ContentWarningMode, &str); 3] = [
+    (ContentWarningMode::Collapse, "collapse sensitive content"),
+    (
+        ContentWarningMode::AlwaysShow,
+        "always show sensitive content",
+    ),
+    (
+        ContentWarningMode::AlwaysHide,
+        "always hide sensitive content",
+    ),
+];
+
+#[function_component(ContentWarningSettings)]
+pub fn content_warning_settings() -> Html {
+    let cw_ctx = use_context::<ContentWarningContext>().unwrap();
+    let onchange = {
+        let cw_ctx = cw_ctx.clone();
+        Callback::from(move |e: yew::Event| {
+            let mode = e
+                .target_dyn_into::<HtmlSelectElement>()
+                .and_then(|select| CONTENT_WARNING_MODES.get(select.selected_index() as usize));
+            if let Some((mode, _)) = mode {
+                cw_ctx.dispatch(ContentWarningAction::SetMode(*mode));
+            }
+        })
+    };
+    let modes = CONTENT_WARNING_MODES
+        .iter()
+        .map(|(mode, name)| {
+            html!(<option key={*name} selected={*mode == cw_ctx.mode}>{rust_i18n::t!(name)}</option>)
+        })
+        .collect::<Html>();
+    include!("./components/content_warning.html")
+}
@@ -1,4 +1,5 @@
 use crate::ctxs::*;
+use crate::utils::format_local_timestamp;
 
 use web_sys::HtmlInputElement;
 use yew::prelude::*;
@@ -38,12 +39,16 @@ pub struct Entry {
     input_error_state: u8, // use bits tu judge the state of inputs
     relay_handle: RelayContext,
     relay_handle_listener: ContextHandle<RelayContext>,
+    relay_status_handle: RelayStatusContext,
+    _relay_status_listener: ContextHandle<RelayStatusContext>,
     relay_node: NodeRef,
 }
 
 #[derive(Debug)]
 pub enum EntryMsg {
     RelayCtx(RelayContext),
+    RelayStatusCtx(RelayStatusContext),
+    ToggleAuth,
     Update,
     Delete,
     Confirm,
@@ -104,11 +109,17 @@ impl Component for Entry {
             .link()
             .context(ctx.link().callback(EntryMsg::RelayCtx))
             .expect("No UserContactContext Provided");
+        let (relay_status_handle, _relay_status_listener) = ctx
+            .link()
+            .context(ctx.link().callback(EntryMsg::RelayStatusCtx))
+            .expect("No RelayStatusContext Provided");
         Self {
             edit: pros.idx.is_some(),
             input_error_state: 0,
             relay_handle,
             relay_handle_listener,
+            relay_status_handle,
+            _relay_status_listener,
             relay_node: NodeRef::default(),
         }
     }
@@ -160,6 +171,15 @@ impl Component for Entry {
                 ctx.props().cb_action.emit(EntryCbAction::Check(item));
                 update = true;
             }
+            ToggleAuth => {
+                let info = &ctx.props().info;
+                self.relay_handle
+                    .dispatch(RelayAction::SetAuth(info.id, !info.auth));
+            }
+            RelayStatusCtx(relay_status_handle) => {
+                self.relay_status_handle = relay_status_handle;
+                update = true;
+            }
             RelayCtx(_) => {}
             _ => {
                 log::info!("{msg:?}");
@@ -200,6 +220,26 @@ impl Component for Entry {
             .or(Some(Self::input_node_val(&self.relay_node)))
             .unwrap();
 
+        let auth_clk = ctx.link().callback(|_: MouseEvent| EntryMsg::ToggleAuth);
+        let state = info
+            .uri
+            .as_ref()
+            .and_then(|uri| self.relay_status_handle.relays.borrow().get(uri).cloned())
+            .unwrap_or_default();
+        let auth_state = state.auth.clone();
+        let notices_title = state
+            .notices
+            .iter()
+            .map(|(t, message)| format!("{} {message}", format_local_timestamp(t.as_i64())))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let (auth_cls, auth_label, auth_title) = match &auth_state {
+            AuthState::None => ("", "", String::new()),
+            AuthState::Required => ("is-warning", "auth required", String::new()),
+            AuthState::Authenticating => ("is-info", "authenticating", String::new()),
+            AuthState::Authenticated => ("is-success", "authenticated", String::new()),
+            AuthState::Failed(reason) => ("is-danger", "auth failed", reason.clone()),
+        };
         include!("./components/entry.html")
     }
 }
@@ -0,0 +1,34 @@
+use crate::ctxs::*;
+
+use web_sys::HtmlSelectElement;
+use yew::prelude::*;
+
+#[function_component(LocaleSettings)]
+pub fn locale_settings() -> Html {
+    let locale_ctx = use_context::<I18nLocaleContext>().unwrap();
+    let cur_lang = locale_ctx.to_string();
+    let locales = I18nLocale::all();
+    let onchange = {
+        let locale_ctx = locale_ctx.clone();
+        let locales = locales.clone();
+        Callback::from(move |e: yew::Event| {
+            let selected = e
+                .target_dyn_into::<HtmlSelectElement>()
+                .and_then(|select| locales.get(select.selected_index() as usize));
+            if let Some(locale) = selected {
+                locale_ctx.dispatch(*locale);
+            }
+        })
+    };
+    let options = locales
+        .iter()
+        .map(|locale| {
+            html!(
+                <option key={locale.code()} value={locale.code()} selected={*locale == *locale_ctx}>
+                    {locale.name()}
+                </option>
+            )
+        })
+        .collect::<Html>();
+    include!("./components/locale.html")
+}
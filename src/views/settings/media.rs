@@ -0,0 +1,63 @@
+use crate::ctxs::*;
+
+use nostr_sdk::nostr::Url;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+const MEDIA_SERVER_KINDS: [(MediaServerKind, &str); 2] = [
+    (MediaServerKind::Nip96, "NIP-96"),
+    (MediaServerKind::Blossom, "Blossom"),
+];
+
+#[function_component(MediaSettings)]
+pub fn media_settings() -> Html {
+    let media_ctx = use_context::<MediaServerContext>().unwrap();
+    let invalid_url = use_state(|| false);
+    let kind_change = {
+        let media_ctx = media_ctx.clone();
+        Callback::from(move |e: yew::Event| {
+            let kind = e
+                .target_dyn_into::<HtmlSelectElement>()
+                .and_then(|select| MEDIA_SERVER_KINDS.get(select.selected_index() as usize));
+            if let Some((kind, _)) = kind {
+                media_ctx.dispatch(MediaServerAction::SetKind(*kind));
+            }
+        })
+    };
+    let url_change = {
+        let media_ctx = media_ctx.clone();
+        let invalid_url = invalid_url.clone();
+        Callback::from(move |e: yew::Event| {
+            let value = match e.target_dyn_into::<HtmlInputElement>() {
+                Some(input) => input.value().trim().to_owned(),
+                None => return,
+            };
+            // empty disables the upload
+            if value.is_empty() {
+                invalid_url.set(false);
+                media_ctx.dispatch(MediaServerAction::SetUrl(None));
+                return;
+            }
+            // the paths are joined to it
+            let value = if value.ends_with('/') {
+                value
+            } else {
+                format!("{value}/")
+            };
+            match Url::parse(&value) {
+                Ok(url) if url.scheme() == "https" || url.scheme() == "http" => {
+                    invalid_url.set(false);
+                    media_ctx.dispatch(MediaServerAction::SetUrl(Some(url)));
+                }
+                _ => invalid_url.set(true),
+            }
+        })
+    };
+    let kinds = MEDIA_SERVER_KINDS
+        .iter()
+        .map(|(kind, name)| {
+            html!(<option key={*name} selected={*kind == media_ctx.kind}>{*name}</option>)
+        })
+        .collect::<Html>();
+    include!("./components/media.html")
+}
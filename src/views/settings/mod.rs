@@ -1,7 +1,19 @@
+mod backup;
+mod content_warning;
 mod entry;
+mod locale;
+mod media;
+mod mute;
+mod pow;
 use yew::prelude::*;
 
+use self::backup::*;
+use self::content_warning::*;
 use self::entry::*;
+use self::locale::*;
+use self::media::*;
+use self::mute::*;
+use self::pow::*;
 use crate::ctxs::*;
 
 use std::borrow::Borrow;
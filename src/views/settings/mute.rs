@@ -0,0 +1,95 @@
+use crate::ctxs::*;
+
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+const MUTE_ITEM_KINDS: [(MuteItemKind, &str); 4] = [
+    (MuteItemKind::PubKey, "public key"),
+    (MuteItemKind::Event, "mute event"),
+    (MuteItemKind::Hashtag, "hashtag"),
+    (MuteItemKind::Word, "word"),
+];
+
+fn kind_name(kind: MuteItemKind) -> &'static str {
+    MUTE_ITEM_KINDS
+        .iter()
+        .find(|(k, _)| *k == kind)
+        .map(|(_, name)| *name)
+        .unwrap()
+}
+
+#[function_component(MuteList)]
+pub fn mute_list() -> Html {
+    let mute_ctx = use_context::<UserMuteContext>().unwrap();
+    let kind_ref = use_node_ref();
+    let input_ref = use_node_ref();
+    let private_ref = use_node_ref();
+    let input_error = use_state(|| false);
+    let add_clk = {
+        let mute_ctx_clone = mute_ctx.clone();
+        let kind_ref = kind_ref.clone();
+        let input_ref = input_ref.clone();
+        let private_ref = private_ref.clone();
+        let input_error = input_error.clone();
+        Callback::from(move |_: MouseEvent| {
+            let kind = kind_ref
+                .cast::<HtmlSelectElement>()
+                .and_then(|select| MUTE_ITEM_KINDS.get(select.selected_index() as usize))
+                .map(|(kind, _)| *kind)
+                .unwrap_or(MuteItemKind::PubKey);
+            let private = private_ref
+                .cast::<HtmlInputElement>()
+                .map(|input| input.checked())
+                .unwrap_or(false);
+            if let Some(input) = input_ref.cast::<HtmlInputElement>() {
+                match MuteItem::parse(kind, &input.value()) {
+                    Some(item) => {
+                        mute_ctx_clone.dispatch(UserMuteAction::Add(item, private));
+                        input.set_value("");
+                        input_error.set(false);
+                    }
+                    None => input_error.set(true),
+                }
+            }
+        })
+    };
+    let entries = mute_ctx
+        .entries
+        .borrow()
+        .iter()
+        .map(|entry| {
+            let remove_clk = {
+                let mute_ctx_clone = mute_ctx.clone();
+                let item = entry.item.clone();
+                Callback::from(move |_: MouseEvent| {
+                    mute_ctx_clone.dispatch(UserMuteAction::Remove(item.clone()))
+                })
+            };
+            let private_clk = {
+                let mute_ctx_clone = mute_ctx.clone();
+                let item = entry.item.clone();
+                Callback::from(move |_: MouseEvent| {
+                    mute_ctx_clone.dispatch(UserMuteAction::TogglePrivate(item.clone()))
+                })
+            };
+            let title = if entry.private {
+                rust_i18n::t!("private, only visible to you")
+            } else {
+                rust_i18n::t!("public")
+            };
+            html!(
+                <div class="control" key={entry.item.to_string()}>
+                    <div class="tags has-addons">
+                        <span class="tag is-dark">{rust_i18n::t!(kind_name(entry.item.kind()))}</span>
+                        <span class="tag is-light mute-item" title={entry.item.to_string()}>{entry.item.to_string()}</span>
+                        <a class="tag is-info is-light" {title} onclick={private_clk}>
+                            <i class={classes!("fas", if entry.private {"fa-lock"} else {"fa-lock-open"})}></i>
+                        </a>
+                        <a class="tag is-delete" onclick={remove_clk}></a>
+                    </div>
+                </div>
+            )
+        })
+        .collect::<Html>();
+    include!("./components/mute.html")
+}
@@ -0,0 +1,62 @@
+use crate::ctxs::*;
+
+use nostr_sdk::nostr::Url;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+// empty is None
+fn input_difficulty(e: &yew::Event) -> Option<u8> {
+    let input = e.target_dyn_into::<HtmlInputElement>()?;
+    input.value().trim().parse::<u8>().ok()
+}
+
+#[function_component(PowSettings)]
+pub fn pow_settings() -> Html {
+    let pow_ctx = use_context::<PowContext>().unwrap();
+    let relay_ctx = use_context::<RelayContext>().unwrap();
+    let difficulty_change = {
+        let pow_ctx = pow_ctx.clone();
+        Callback::from(move |e: yew::Event| {
+            pow_ctx.dispatch(PowAction::SetDifficulty(
+                input_difficulty(&e).unwrap_or_default(),
+            ))
+        })
+    };
+    let min_difficulty_change = {
+        let pow_ctx = pow_ctx.clone();
+        Callback::from(move |e: yew::Event| {
+            pow_ctx.dispatch(PowAction::SetMinDifficulty(
+                input_difficulty(&e).unwrap_or_default(),
+            ))
+        })
+    };
+    let relays = relay_ctx
+        .list
+        .borrow()
+        .values()
+        .filter_map(|info| info.uri.clone())
+        .map(|uri: Url| {
+            let learned = pow_ctx.learned.borrow().get(&uri).copied();
+            let custom = pow_ctx.relays.borrow().get(&uri).copied();
+            let onchange = {
+                let pow_ctx = pow_ctx.clone();
+                let uri = uri.clone();
+                Callback::from(move |e: yew::Event| {
+                    pow_ctx.dispatch(PowAction::SetRelay(uri.clone(), input_difficulty(&e)))
+                })
+            };
+            html!(
+                <tr key={uri.to_string()}>
+                    <td class="pow-relay" title={uri.to_string()}>{uri.to_string()}</td>
+                    <td>{learned.map(|v| v.to_string()).unwrap_or_else(|| "-".to_owned())}</td>
+                    <td>
+                        <input class="input is-small" type="number" min="0" max={MAX_POW_DIFFICULTY.to_string()}
+                            value={custom.map(|v| v.to_string()).unwrap_or_default()}
+                            placeholder={rust_i18n::t!("relay requirement")} {onchange} />
+                    </td>
+                </tr>
+            )
+        })
+        .collect::<Html>();
+    include!("./components/pow.html")
+}
@@ -0,0 +1,31 @@
+use crate::ctxs::*;
+use crate::utils::*;
+use nostr_sdk::nostr::Timestamp;
+use yew::prelude::*;
+
+#[derive(Properties, PartialEq)]
+pub struct TimeProps {
+    pub timestamp: Timestamp,
+}
+
+// "3 min ago", updated by the clock
+#[function_component(RelativeTime)]
+pub fn relative_time(props: &TimeProps) -> Html {
+    let clock = use_context::<ClockContext>().unwrap();
+    // the formats follow the locale
+    let _locale = use_context::<I18nLocaleContext>().unwrap();
+    let title = format_local_timestamp(props.timestamp.as_i64());
+    html!(
+        <span {title}>{format_relative_time(props.timestamp, clock.now)}</span>
+    )
+}
+
+// "Yesterday, 9:30 PM", today and yesterday change at midnight
+#[function_component(LocalTime)]
+pub fn local_time(props: &TimeProps) -> Html {
+    let _clock = use_context::<ClockContext>().unwrap();
+    let _locale = use_context::<I18nLocaleContext>().unwrap();
+    html!(
+        <span>{format_local_timestamp(props.timestamp.as_i64())}</span>
+    )
+}
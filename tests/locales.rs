@@ -0,0 +1,108 @@
+// every key used by `t!` must be in every file of `locales/`,
+// rust-i18n shows the key itself when it's missing
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const LOCALES_DIR: &str = "locales";
+const SRC_DIR: &str = "src";
+
+// the top level `key: value` lines, the locale files are flat
+fn locale_keys(path: &Path) -> BTreeSet<String> {
+    let content = fs::read_to_string(path).unwrap();
+    content
+        .lines()
+        .filter(|line| !line.starts_with([' ', '\t', '#']) && !line.trim().is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, _)| key.trim().trim_matches('"').to_string())
+        .collect()
+}
+
+fn locales() -> BTreeMap<String, BTreeSet<String>> {
+    fs::read_dir(LOCALES_DIR)
+        .unwrap()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().map_or(false, |ext| ext == "yml"))
+        .map(|path| {
+            let name = path.file_stem().unwrap().to_string_lossy().to_string();
+            (name, locale_keys(&path))
+        })
+        .collect()
+}
+
+fn source_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.is_dir() {
+            source_files(&path, files);
+        } else if path
+            .extension()
+            .map_or(false, |ext| ext == "rs" || ext == "html")
+        {
+            files.push(path);
+        }
+    }
+}
+
+// the string literals in the first argument of `t!(...)`, so both
+// `t!("send")` and `t!(if edit {"confirm"} else {"modify"})` are found.
+// the keys passed by variables are literals somewhere else and aren't checked
+fn used_keys(content: &str) -> BTreeSet<String> {
+    let mut keys = BTreeSet::new();
+    for (start, _) in content.match_indices("t!(") {
+        // not `format!(` or `assert!(`
+        let prev = content[..start].chars().last();
+        if prev.map_or(false, |c| c.is_alphanumeric() || c == '_') {
+            continue;
+        }
+        let mut depth = 0;
+        let mut chars = content[start + 3..].chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '(' | '{' | '[' => depth += 1,
+                ')' | '}' | ']' if depth == 0 => break,
+                ')' | '}' | ']' => depth -= 1,
+                ',' if depth == 0 => break,
+                '"' => {
+                    let key = chars.by_ref().take_while(|c| *c != '"').collect();
+                    keys.insert(key);
+                }
+                _ => {}
+            }
+        }
+    }
+    keys
+}
+
+#[test]
+fn all_locales_have_the_same_keys() {
+    let locales = locales();
+    let all = locales.values().flatten().collect::<BTreeSet<_>>();
+    let mut missing = vec![];
+    for (name, keys) in &locales {
+        for key in all.iter().filter(|key| !keys.contains(**key)) {
+            missing.push(format!("{}: {}", name, key));
+        }
+    }
+    assert!(missing.is_empty(), "missing keys:\n{}", missing.join("\n"));
+}
+
+#[test]
+fn all_used_keys_are_translated() {
+    let locales = locales();
+    assert!(!locales.is_empty());
+    let mut files = vec![];
+    source_files(Path::new(SRC_DIR), &mut files);
+    let mut missing = vec![];
+    for file in files {
+        let content = fs::read_to_string(&file).unwrap();
+        for key in used_keys(&content) {
+            for (name, keys) in &locales {
+                if !keys.contains(&key) {
+                    missing.push(format!("{} {}: {}", file.display(), name, key));
+                }
+            }
+        }
+    }
+    assert!(missing.is_empty(), "missing keys:\n{}", missing.join("\n"));
+}
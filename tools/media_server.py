@@ -0,0 +1,184 @@
+#!/usr/bin/env python3
+# A local stand-in for a NIP-96 / Blossom media server, to try the uploads of
+# the compose box without a real one:
+#
+#     python3 tools/media_server.py --port 3300
+#
+# and set `http://127.0.0.1:3300/` as the media server in the settings. Both
+# kinds are served by the same process:
+#
+#   GET  /.well-known/nostr/nip96.json   NIP-96 discovery
+#   POST /api/upload                     NIP-96 upload, multipart `file`
+#   PUT  /upload                         Blossom upload (BUD-02), raw body
+#   GET  /<sha256>[.ext]                 the uploaded file
+#
+# The `Authorization: Nostr <base64 event>` header is checked for its kind,
+# tags and time, but not its signature, the standard library has no schnorr.
+import argparse
+import base64
+import hashlib
+import json
+import mimetypes
+import os
+import tempfile
+import time
+from email.parser import BytesParser
+from email.policy import HTTP
+from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
+from urllib.parse import urlparse
+
+HTTP_AUTH_KIND = 27235
+BLOSSOM_AUTH_KIND = 24242
+# NIP-98, the auth event must be recent
+HTTP_AUTH_WINDOW = 60
+
+
+def tag_value(event, name):
+    for tag in event.get("tags", []):
+        if len(tag) > 1 and tag[0] == name:
+            return tag[1]
+    return None
+
+
+def auth_event(header):
+    if not header or not header.startswith("Nostr "):
+        raise ValueError("no `Authorization: Nostr` header")
+    return json.loads(base64.b64decode(header[len("Nostr "):]))
+
+
+class Handler(BaseHTTPRequestHandler):
+    store = tempfile.mkdtemp(prefix="media-")
+
+    def base_url(self):
+        host = self.headers.get("Host") or "%s:%d" % self.server.server_address
+        return "http://%s" % host
+
+    def reply(self, status, body=b"", content_type="application/json"):
+        if isinstance(body, (dict, list)):
+            body = json.dumps(body).encode()
+        self.send_response(status)
+        self.send_header("Access-Control-Allow-Origin", "*")
+        self.send_header("Content-Type", content_type)
+        self.send_header("Content-Length", str(len(body)))
+        self.end_headers()
+        self.wfile.write(body)
+
+    def fail(self, status, message):
+        self.log_message("%s", message)
+        self.reply(status, {"status": "error", "message": message})
+
+    def read_body(self):
+        return self.rfile.read(int(self.headers.get("Content-Length") or 0))
+
+    def save(self, data, mime):
+        sha256 = hashlib.sha256(data).hexdigest()
+        ext = mimetypes.guess_extension(mime or "") or ""
+        with open(os.path.join(self.store, sha256), "wb") as f:
+            f.write(data)
+        with open(os.path.join(self.store, sha256 + ".type"), "w") as f:
+            f.write(mime or "application/octet-stream")
+        return sha256, "%s/%s%s" % (self.base_url(), sha256, ext)
+
+    def do_OPTIONS(self):
+        self.send_response(204)
+        self.send_header("Access-Control-Allow-Origin", "*")
+        self.send_header("Access-Control-Allow-Methods", "GET, POST, PUT, OPTIONS")
+        self.send_header("Access-Control-Allow-Headers", "Authorization, Content-Type")
+        self.end_headers()
+
+    def do_GET(self):
+        path = urlparse(self.path).path
+        if path == "/.well-known/nostr/nip96.json":
+            return self.reply(200, {
+                "api_url": self.base_url() + "/api/upload",
+                "download_url": self.base_url(),
+                "supported_nips": [94, 96, 98],
+                "content_types": ["image/*", "video/*", "audio/*"],
+            })
+        sha256 = path.strip("/").split(".")[0]
+        file = os.path.join(self.store, sha256)
+        if len(sha256) != 64 or not os.path.exists(file):
+            return self.fail(404, "not found")
+        with open(file + ".type") as f:
+            mime = f.read()
+        with open(file, "rb") as f:
+            self.reply(200, f.read(), mime)
+
+    # NIP-96 with NIP-98 auth
+    def do_POST(self):
+        if urlparse(self.path).path != "/api/upload":
+            return self.fail(404, "not found")
+        try:
+            event = auth_event(self.headers.get("Authorization"))
+        except Exception as e:
+            return self.fail(401, "invalid auth: %s" % e)
+        url = self.base_url() + self.path
+        if event.get("kind") != HTTP_AUTH_KIND:
+            return self.fail(401, "auth kind is not %d" % HTTP_AUTH_KIND)
+        if tag_value(event, "u") != url or tag_value(event, "method") != "POST":
+            return self.fail(401, "auth is not for POST %s" % url)
+        if abs(time.time() - event.get("created_at", 0)) > HTTP_AUTH_WINDOW:
+            return self.fail(401, "auth is expired")
+        body = self.read_body()
+        head = "Content-Type: %s\r\n\r\n" % self.headers.get("Content-Type")
+        form = BytesParser(policy=HTTP).parsebytes(head.encode() + body)
+        parts = {part.get_param("name", header="content-disposition"): part
+                 for part in form.iter_parts()}
+        if "file" not in parts:
+            return self.fail(400, "no `file` field")
+        data = parts["file"].get_payload(decode=True)
+        mime = parts["file"].get_content_type()
+        if "content_type" in parts:
+            mime = parts["content_type"].get_content().strip() or mime
+        payload = tag_value(event, "payload")
+        if payload and payload != hashlib.sha256(data).hexdigest():
+            return self.fail(401, "auth payload is not the sha256 of the file")
+        sha256, file_url = self.save(data, mime)
+        self.reply(201, {
+            "status": "success",
+            "message": "uploaded",
+            "nip94_event": {
+                "tags": [["url", file_url], ["ox", sha256], ["x", sha256],
+                         ["m", mime], ["size", str(len(data))]],
+                "content": "",
+            },
+        })
+
+    # Blossom with BUD-01 auth
+    def do_PUT(self):
+        if urlparse(self.path).path != "/upload":
+            return self.fail(404, "not found")
+        try:
+            event = auth_event(self.headers.get("Authorization"))
+        except Exception as e:
+            return self.fail(401, "invalid auth: %s" % e)
+        if event.get("kind") != BLOSSOM_AUTH_KIND or tag_value(event, "t") != "upload":
+            return self.fail(401, "auth is not for the upload")
+        if int(tag_value(event, "expiration") or 0) < time.time():
+            return self.fail(401, "auth is expired")
+        data = self.read_body()
+        if tag_value(event, "x") != hashlib.sha256(data).hexdigest():
+            return self.fail(401, "auth is for another file")
+        mime = self.headers.get("Content-Type") or "application/octet-stream"
+        sha256, file_url = self.save(data, mime)
+        self.reply(200, {
+            "url": file_url,
+            "sha256": sha256,
+            "size": len(data),
+            "type": mime,
+            "uploaded": int(time.time()),
+        })
+
+
+def main():
+    parser = argparse.ArgumentParser()
+    parser.add_argument("--host", default="127.0.0.1")
+    parser.add_argument("--port", type=int, default=3300)
+    args = parser.parse_args()
+    server = ThreadingHTTPServer((args.host, args.port), Handler)
+    print("serving on http://%s:%d/, files in %s" % (args.host, args.port, Handler.store))
+    server.serve_forever()
+
+
+if __name__ == "__main__":
+    main()